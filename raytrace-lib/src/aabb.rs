@@ -0,0 +1,163 @@
+use crate::{ray::Ray, vec3::Vec3, FLOAT_EPS};
+
+/// An axis-aligned bounding box described by its two extreme corners.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Create a bounding box from two opposite corners.
+    /// The corners are sorted per axis so the order does not matter.
+    pub fn new(a: Vec3, b: Vec3) -> Self {
+        Self {
+            min: Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    /// Returns the smallest box containing all `points`, padded slightly so that
+    /// flat boxes, e.g. around a triangle, are still hit by grazing rays.
+    pub fn enclosing(points: &[Vec3]) -> Self {
+        let Some((&first, rest)) = points.split_first() else {
+            return Self::new(Vec3::zero(), Vec3::zero());
+        };
+
+        let (min, max) = rest.iter().fold((first, first), |(min, max), p| {
+            (
+                Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )
+        });
+
+        let pad = Vec3::new(FLOAT_EPS, FLOAT_EPS, FLOAT_EPS);
+        Self::new(min - pad, max + pad)
+    }
+
+    /// Returns the smallest box containing both boxes.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(
+            Vec3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            Vec3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        )
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Returns the area of the six faces of the box.
+    pub fn surface_area(&self) -> f64 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// Returns the axis along which the box is the longest, 0, 1 and 2 for x, y and z.
+    pub fn longest_axis(&self) -> usize {
+        let size = self.max - self.min;
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Returns true if `point` lies inside or on the border of the box.
+    pub fn contains(&self, point: Vec3) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    /// Check if the ray hits the box in front of its origin.
+    pub fn hit(&self, ray: &Ray) -> bool {
+        self.hit_interval(ray).is_some()
+    }
+
+    /// Returns the distances along the ray where it enters and exits the box.
+    ///
+    /// The entry distance is clamped to `0.0` when the ray starts inside the box.
+    /// Uses the slab method.
+    /// <https://en.wikipedia.org/wiki/Slab_method>
+    pub fn hit_interval(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let origin = ray.origin;
+        let dir = ray.direction();
+
+        let mut t_near = 0.0_f64;
+        let mut t_far = f64::INFINITY;
+
+        for (o, d, min, max) in [
+            (origin.x, dir.x, self.min.x, self.max.x),
+            (origin.y, dir.y, self.min.y, self.max.y),
+            (origin.z, dir.z, self.min.z, self.max.z),
+        ] {
+            // Division by zero gives ±inf which the comparisons below handle,
+            // except when the origin lies exactly on a slab (0 * inf = NaN).
+            let inv_d = 1.0 / d;
+            let mut t0 = (min - o) * inv_d;
+            let mut t1 = (max - o) * inv_d;
+
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // `max`/`min` ignore NaN, keeping the previous interval.
+            t_near = t_near.max(t0);
+            t_far = t_far.min(t1);
+
+            if t_far < t_near {
+                return None;
+            }
+        }
+
+        Some((t_near, t_far))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_interval_through_box() {
+        let aabb = Aabb::new(Vec3::new(-1.0, -1.0, 2.0), Vec3::new(1.0, 1.0, 4.0));
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+
+        let (near, far) = aabb.hit_interval(&ray).unwrap();
+        assert!((near - 2.0).abs() < 1e-9, "near: {near}");
+        assert!((far - 4.0).abs() < 1e-9, "far: {far}");
+        assert!(aabb.hit(&ray));
+    }
+
+    #[test]
+    fn hit_interval_miss() {
+        let aabb = Aabb::new(Vec3::new(-1.0, -1.0, 2.0), Vec3::new(1.0, 1.0, 4.0));
+
+        let ray = Ray::new(Vec3::new(0.0, 3.0, 0.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert_eq!(aabb.hit_interval(&ray), None);
+        assert!(!aabb.hit(&ray));
+
+        // Box is behind the ray
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert_eq!(aabb.hit_interval(&ray), None);
+    }
+
+    #[test]
+    fn contains_point() {
+        let aabb = Aabb::new(Vec3::new(-1.0, -1.0, 2.0), Vec3::new(1.0, 1.0, 4.0));
+        assert!(aabb.contains(Vec3::new(0.5, -0.5, 3.0)));
+        assert!(!aabb.contains(Vec3::new(0.5, -0.5, 1.0)));
+        assert!(!aabb.contains(Vec3::new(2.0, 0.0, 3.0)));
+    }
+}
@@ -0,0 +1,453 @@
+use std::ops::{ControlFlow, Range};
+
+use crate::{
+    aabb::Aabb,
+    object::Object,
+    ray::{Ray, RayHit},
+};
+
+/// Largest number of objects kept in a leaf, larger nodes are always split.
+const MAX_LEAF_OBJECTS: usize = 4;
+
+/// Estimated cost of testing a ray against the two boxes of a branch,
+/// relative to [`Primitive::intersection_cost`](crate::primitive::Primitive::intersection_cost).
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Bounding volume hierarchy over the objects of a scene.
+///
+/// Rays only test the objects whose boxes they pass through instead of every object.
+/// Objects without a bounding box, like planes, are kept aside and always tested.
+/// <https://en.wikipedia.org/wiki/Bounding_volume_hierarchy>
+#[derive(Debug, Clone)]
+pub struct Bvh<'a> {
+    objects: &'a [Object],
+    nodes: Vec<Node>,
+    /// Indices into `objects`, the leaves refer to ranges of this.
+    order: Vec<usize>,
+    /// Indices of the objects without a bounding box.
+    unbounded: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    aabb: Aabb,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Leaf(Range<usize>),
+    Branch(usize, usize),
+}
+
+/// Shape of a [`Bvh`], the objects without a bounding box are not part of the tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BvhStats {
+    /// Number of nodes, both branches and leaves.
+    pub nodes: usize,
+    /// Number of nodes on the longest path from the root to a leaf.
+    pub max_depth: usize,
+    pub leaves: usize,
+    /// Total number of objects in the leaves.
+    pub leaf_objects: usize,
+}
+
+impl BvhStats {
+    pub fn avg_leaf_size(&self) -> f64 {
+        if self.leaves == 0 {
+            return 0.0;
+        }
+        self.leaf_objects as f64 / self.leaves as f64
+    }
+}
+
+/// A bounded object waiting to be placed in the hierarchy.
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    object: usize,
+    aabb: Aabb,
+    /// Estimated cost of intersecting the object.
+    cost: f64,
+}
+
+/// Sort `items` by the center of their boxes along `axis`.
+fn sort_along(items: &mut [Item], axis: usize) {
+    items.sort_by(|a, b| a.aabb.center()[axis].total_cmp(&b.aabb.center()[axis]));
+}
+
+/// Returns the cheapest way to split `items` in two, as the axis they are sorted along,
+/// the number of items in the first half and the estimated cost of the split.
+///
+/// The cost of each half is the cost of its objects weighted by the chance that
+/// a ray through the parent, with area `parent_area`, also passes through the half.
+fn sah_split(items: &mut [Item], parent_area: f64) -> (usize, usize, f64) {
+    let mut best = (0, items.len() / 2, f64::INFINITY);
+
+    for axis in 0..3 {
+        sort_along(items, axis);
+
+        // Area and cost of the items after each split point, swept from the back
+        let mut after = vec![(0.0, 0.0); items.len()];
+        let mut acc: Option<(Aabb, f64)> = None;
+        for (i, item) in items.iter().enumerate().rev() {
+            let (aabb, cost) = acc.map_or((item.aabb, item.cost), |(aabb, cost)| {
+                (aabb.union(&item.aabb), cost + item.cost)
+            });
+            after[i] = (aabb.surface_area(), cost);
+            acc = Some((aabb, cost));
+        }
+
+        let mut before = items[0].aabb;
+        let mut before_cost = 0.0;
+        for mid in 1..items.len() {
+            let previous = &items[mid - 1];
+            before = before.union(&previous.aabb);
+            before_cost += previous.cost;
+
+            let (after_area, after_cost) = after[mid];
+            let cost = TRAVERSAL_COST
+                + (before.surface_area() * before_cost + after_area * after_cost) / parent_area;
+            if cost < best.2 {
+                best = (axis, mid, cost);
+            }
+        }
+    }
+
+    best
+}
+
+impl<'a> Bvh<'a> {
+    /// Build the hierarchy by recursively splitting the objects where the
+    /// surface area heuristic estimates the cheapest traversal.
+    /// <https://pbr-book.org/3ed-2018/Primitives_and_Intersection_Acceleration/Bounding_Volume_Hierarchies#TheSurfaceAreaHeuristic>
+    pub fn new(objects: &'a [Object]) -> Self {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            match object.primitive.bounding_box() {
+                Some(aabb) => bounded.push(Item {
+                    object: index,
+                    aabb,
+                    cost: object.primitive.intersection_cost(),
+                }),
+                None => unbounded.push(index),
+            }
+        }
+
+        let mut bvh = Self {
+            objects,
+            nodes: Vec::new(),
+            order: Vec::with_capacity(bounded.len()),
+            unbounded,
+        };
+        if !bounded.is_empty() {
+            bvh.build(&mut bounded);
+        }
+        bvh
+    }
+
+    /// Add a node for `items` and its children, returns the index of the node.
+    ///
+    /// Small nodes become leaves when testing all their objects is estimated
+    /// to be cheaper than any split.
+    fn build(&mut self, items: &mut [Item]) -> usize {
+        let aabb = items
+            .iter()
+            .map(|item| item.aabb)
+            .reduce(|a, b| a.union(&b))
+            .expect("nodes are never empty");
+        let index = self.nodes.len();
+
+        let leaf_cost: f64 = items.iter().map(|item| item.cost).sum();
+        let split = (items.len() > 1)
+            .then(|| sah_split(items, aabb.surface_area()))
+            .filter(|&(_, _, cost)| items.len() > MAX_LEAF_OBJECTS || cost < leaf_cost);
+
+        let Some((axis, mid, _)) = split else {
+            let start = self.order.len();
+            self.order.extend(items.iter().map(|item| item.object));
+            self.nodes.push(Node {
+                aabb,
+                kind: NodeKind::Leaf(start..self.order.len()),
+            });
+            return index;
+        };
+        sort_along(items, axis);
+
+        // Children are filled in once they exist
+        self.nodes.push(Node {
+            aabb,
+            kind: NodeKind::Branch(0, 0),
+        });
+        let (left, right) = items.split_at_mut(mid);
+        let left = self.build(left);
+        let right = self.build(right);
+        self.nodes[index].kind = NodeKind::Branch(left, right);
+        index
+    }
+
+    /// Count the nodes of the tree and how deep and full its leaves are.
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats::default();
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![(0, 1)]
+        };
+
+        while let Some((node, depth)) = stack.pop() {
+            stats.nodes += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            match &self.nodes[node].kind {
+                NodeKind::Leaf(range) => {
+                    stats.leaves += 1;
+                    stats.leaf_objects += range.len();
+                }
+                &NodeKind::Branch(left, right) => {
+                    stack.extend([(left, depth + 1), (right, depth + 1)]);
+                }
+            }
+        }
+
+        stats
+    }
+
+    /// Returns the objects the hierarchy was built from.
+    pub fn objects(&self) -> &'a [Object] {
+        self.objects
+    }
+
+    /// Calls `visit` with the index of every object that `ray` might hit closer than
+    /// `max_dist`, visiting nearer boxes first.
+    ///
+    /// `visit` continues with a new `max_dist`, e.g. the distance to a hit,
+    /// so boxes behind it are skipped, or breaks to end the traversal.
+    pub fn traverse(
+        &self,
+        ray: &Ray,
+        mut max_dist: f64,
+        mut visit: impl FnMut(usize, &'a Object) -> ControlFlow<(), f64>,
+    ) {
+        for &index in &self.unbounded {
+            match visit(index, &self.objects[index]) {
+                ControlFlow::Continue(dist) => max_dist = dist,
+                ControlFlow::Break(()) => return,
+            }
+        }
+
+        let Some(root) = self.nodes.first() else {
+            return;
+        };
+        let mut stack = Vec::new();
+        if let Some((near, _)) = root.aabb.hit_interval(ray) {
+            stack.push((0, near));
+        }
+
+        while let Some((node, near)) = stack.pop() {
+            if near > max_dist {
+                continue;
+            }
+
+            match &self.nodes[node].kind {
+                NodeKind::Leaf(range) => {
+                    for &index in &self.order[range.clone()] {
+                        match visit(index, &self.objects[index]) {
+                            ControlFlow::Continue(dist) => max_dist = dist,
+                            ControlFlow::Break(()) => return,
+                        }
+                    }
+                }
+                &NodeKind::Branch(left, right) => {
+                    let hit = |child: usize| {
+                        self.nodes[child]
+                            .aabb
+                            .hit_interval(ray)
+                            .map(|(near, _)| (child, near))
+                    };
+                    let mut children = [hit(left), hit(right)];
+                    // The nearer child is pushed last so it is visited first
+                    if let [Some((_, a)), Some((_, b))] = children {
+                        if a < b {
+                            children.swap(0, 1);
+                        }
+                    }
+                    stack.extend(children.into_iter().flatten());
+                }
+            }
+        }
+    }
+
+    /// Returns the closest object hit by the ray together with its index.
+    /// Hits at the same distance go to the object that comes first.
+    pub fn closest_hit(&self, ray: &Ray) -> Option<(usize, RayHit, &'a Object)> {
+        let mut closest: Option<(f64, usize, RayHit, &Object)> = None;
+
+        self.traverse(ray, f64::INFINITY, |index, object| {
+            if let Some(ray_hit) = ray.trace(object) {
+                let dist = (ray_hit.intersection - ray.origin).length();
+                let closer = closest
+                    .as_ref()
+                    .is_none_or(|&(best, best_index, _, _)| (dist, index) < (best, best_index));
+                if closer {
+                    closest = Some((dist, index, ray_hit, object));
+                }
+            }
+            ControlFlow::Continue(closest.as_ref().map_or(f64::INFINITY, |hit| hit.0))
+        });
+
+        closest.map(|(_, index, ray_hit, object)| (index, ray_hit, object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::MaterialTemplate,
+        primitive::{Mesh, Plane, Sphere, Triangle},
+        rng::XorShift64,
+        Color, Vec3,
+    };
+
+    /// Returns the closest hit by testing every object, the first object wins ties.
+    fn linear_closest(world: &[Object], ray: &Ray) -> Option<(usize, f64)> {
+        world
+            .iter()
+            .enumerate()
+            .filter_map(|(index, object)| {
+                ray.trace(object)
+                    .map(|hit| (index, (hit.intersection - ray.origin).length()))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)))
+    }
+
+    #[test]
+    fn matches_linear_scan() {
+        let material = MaterialTemplate::Red.get_material(Color::new(255, 0, 0));
+        let mut rng = XorShift64::new(3);
+        let mut coord = |scale: f64| (rng.next_f64() - 0.5) * scale;
+
+        let mut world: Vec<Object> = (0..400)
+            .map(|_| {
+                let center = Vec3::new(coord(40.0), coord(40.0), coord(40.0));
+                let radius = 0.2 + coord(0.6).abs();
+                Object::new(Sphere::new(center, radius).into(), material)
+            })
+            .collect();
+        world.push(Object::new(
+            Plane::new(Vec3::new(0.0, -15.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).into(),
+            material,
+        ));
+        let bvh = Bvh::new(&world);
+
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = Vec3::new(coord(50.0), coord(50.0), coord(50.0));
+            let direction = Vec3::new(coord(2.0), coord(2.0), coord(2.0));
+            let Ok(ray) = Ray::new(origin, direction) else {
+                continue;
+            };
+
+            let linear = linear_closest(&world, &ray);
+            let found = bvh.closest_hit(&ray).map(|(index, _, _)| index);
+            assert_eq!(found, linear.map(|(index, _)| index));
+            hits += usize::from(found.is_some());
+        }
+        assert!(hits > 100, "{hits} hits");
+    }
+
+    #[test]
+    fn skips_boxes_the_ray_misses() {
+        let material = MaterialTemplate::Red.get_material(Color::new(255, 0, 0));
+        let world: Vec<Object> = (0..256)
+            .map(|i| {
+                let center = Vec3::new((i % 16) as f64 * 3.0, (i / 16) as f64 * 3.0, 10.0);
+                Object::new(Sphere::new(center, 1.0).into(), material)
+            })
+            .collect();
+        let bvh = Bvh::new(&world);
+
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        let mut visited = 0;
+        bvh.traverse(&ray, f64::INFINITY, |_, _| {
+            visited += 1;
+            ControlFlow::Continue(f64::INFINITY)
+        });
+        assert!(visited <= MAX_LEAF_OBJECTS, "{visited} visited");
+        assert_eq!(bvh.closest_hit(&ray).map(|hit| hit.0), Some(0));
+    }
+
+    /// Returns the indices of the objects below `node`.
+    fn objects_below(bvh: &Bvh, node: usize) -> Vec<usize> {
+        let mut objects = match &bvh.nodes[node].kind {
+            NodeKind::Leaf(range) => bvh.order[range.clone()].to_vec(),
+            &NodeKind::Branch(left, right) => {
+                let mut objects = objects_below(bvh, left);
+                objects.extend(objects_below(bvh, right));
+                objects
+            }
+        };
+        objects.sort_unstable();
+        objects
+    }
+
+    #[test]
+    fn expensive_objects_get_smaller_nodes() {
+        let material = MaterialTemplate::Red.get_material(Color::new(255, 0, 0));
+        let sphere =
+            |x: f64| Object::new(Sphere::new(Vec3::new(x, 0.0, 0.0), 0.5).into(), material);
+        // Fits the same box as a sphere, but is far more expensive to test
+        let mesh = |x: f64| {
+            let triangle = Triangle::new(
+                Vec3::new(x - 0.5, 0.0, 0.0),
+                Vec3::new(x + 0.5, 0.0, 0.0),
+                Vec3::new(x, 0.5, 0.0),
+            );
+            Object::new(Mesh::new(vec![triangle; 10]).into(), material)
+        };
+
+        let root_split = |world: &[Object]| {
+            let bvh = Bvh::new(world);
+            let NodeKind::Branch(left, right) = bvh.nodes[0].kind else {
+                panic!("root is a leaf");
+            };
+            (objects_below(&bvh, left), objects_below(&bvh, right))
+        };
+
+        let cheap = [sphere(0.0), sphere(2.0), sphere(4.0), sphere(6.0)];
+        assert_eq!(root_split(&cheap), (vec![0, 1], vec![2, 3]));
+
+        let expensive = [sphere(0.0), sphere(2.0), sphere(4.0), mesh(6.0)];
+        assert_eq!(root_split(&expensive), (vec![0, 1, 2], vec![3]));
+    }
+
+    #[test]
+    fn stats_of_a_row_of_spheres() {
+        let material = MaterialTemplate::Red.get_material(Color::new(255, 0, 0));
+        let mut world: Vec<Object> = (0..8)
+            .map(|i| {
+                let center = Vec3::new(f64::from(i) * 10.0, 0.0, 0.0);
+                Object::new(Sphere::new(center, 1.0).into(), material)
+            })
+            .collect();
+        world.push(Object::new(
+            Plane::new(Vec3::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).into(),
+            material,
+        ));
+
+        // Far apart spheres are cheapest to test one per leaf
+        let stats = Bvh::new(&world).stats();
+        assert_eq!(
+            stats,
+            BvhStats {
+                nodes: 15,
+                max_depth: 4,
+                leaves: 8,
+                leaf_objects: 8,
+            }
+        );
+        assert_eq!(stats.avg_leaf_size(), 1.0);
+
+        assert_eq!(Bvh::new(&world[8..]).stats(), BvhStats::default());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{ray::Ray, Rotation, Vec3};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Camera {
     /// The position of the camera.
     position: Vec3,
@@ -10,8 +11,61 @@ pub struct Camera {
     viewport: Viewport,
     /// The field-of-view in radians for the camera.
     fov: f64,
+    /// Counter-clockwise rotation in radians around the view direction.
+    roll: f64,
+    /// The image axis that `fov` spans.
+    fov_axis: FovAxis,
+    /// How pixels are mapped to ray directions.
+    projection: Projection,
     /// The distance from the camera to the viewport.
     distance: f64,
+    /// How long the shutter stays open, objects with a velocity are blurred
+    /// along the distance they move in this time.
+    shutter: f64,
+    /// Diameter of the lens, `0.0` is a pinhole with everything in focus.
+    aperture: f64,
+    /// Distance along each ray at which the image is in focus.
+    focus_distance: f64,
+}
+
+/// Which dimension of the image the field of view refers to.
+/// The other dimension follows from the aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FovAxis {
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+impl std::str::FromStr for FovAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "horizontal" => Ok(Self::Horizontal),
+            "vertical" => Ok(Self::Vertical),
+            _ => Err(format!(
+                "No fov axis named '{s}', expected 'horizontal' or 'vertical'"
+            )),
+        }
+    }
+}
+
+/// How the camera maps pixels to ray directions.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Projection {
+    /// Straight lines stay straight, the camera's fov spans the image.
+    #[default]
+    Pinhole,
+    /// Equidistant fisheye, the angle from the view direction grows linearly
+    /// with the distance from the image center.
+    /// `fov` is in radians and may exceed 180°.
+    Fisheye { fov: f64 },
+    /// Parallel projection, every ray has the view direction and starts on the image plane.
+    /// `scale` is half the width of the image in world units, along the fov axis.
+    Orthographic { scale: f64 },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -39,12 +93,14 @@ impl Camera {
     /// * `position` - The position of the camera
     /// * `view_dir` - The direction that the camera looks in
     /// * `fov`      - Field of view in degrees [0, 180)
+    /// * `roll`     - Rotation in degrees around the view direction
     pub fn new(
         width: u32,
         height: u32,
         position: Vec3,
         view_dir: Vec3,
         fov: f64,
+        roll: f64,
     ) -> Result<Self, CameraNewError> {
         let fov_rad = (fov / 2.0) * std::f64::consts::PI / 180.0;
 
@@ -57,10 +113,92 @@ impl Camera {
             rotation: view_dir.into(),
             viewport: Viewport::new(width, height),
             fov: fov_rad,
+            roll: roll.to_radians(),
+            fov_axis: FovAxis::default(),
+            projection: Projection::default(),
             distance: 1.0 / (fov_rad / 2.0).tan(),
+            shutter: 0.0,
+            aperture: 0.0,
+            focus_distance: 1.0,
         })
     }
 
+    /// Create a camera at `position` that looks at `target`,
+    /// rolled so that `up` points up in the image.
+    ///
+    /// `fov` and `roll` are in degrees like in [`Camera::new`].
+    pub fn look_at(
+        width: u32,
+        height: u32,
+        position: Vec3,
+        target: Vec3,
+        up: Vec3,
+        fov: f64,
+        roll: f64,
+    ) -> Result<Self, CameraNewError> {
+        let view_dir = target - position;
+        let mut camera = Self::new(width, height, position, view_dir, fov, roll)?;
+        camera.rotation = Rotation::look_along(view_dir, up);
+        Ok(camera)
+    }
+
+    /// Returns the unit direction that the camera looks in.
+    pub fn view_dir(&self) -> Vec3 {
+        Vec3::new(0.0, 0.0, 1.0).rotate(&self.rotation)
+    }
+
+    /// Turn the camera around its view direction so that `up` points up in the image,
+    /// before the roll is applied.
+    pub fn set_up(&mut self, up: Vec3) {
+        self.rotation = Rotation::look_along(self.view_dir(), up);
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    pub fn shutter(&self) -> f64 {
+        self.shutter
+    }
+
+    pub fn set_shutter(&mut self, shutter: f64) {
+        self.shutter = shutter;
+    }
+
+    pub fn aperture(&self) -> f64 {
+        self.aperture
+    }
+
+    /// Set the diameter of the lens, larger apertures blur everything
+    /// away from the [`Camera::set_focus_distance`] more.
+    pub fn set_aperture(&mut self, aperture: f64) {
+        self.aperture = aperture.max(0.0);
+    }
+
+    pub fn focus_distance(&self) -> f64 {
+        self.focus_distance
+    }
+
+    pub fn set_focus_distance(&mut self, focus_distance: f64) {
+        self.focus_distance = focus_distance;
+    }
+
+    pub fn set_fov_axis(&mut self, fov_axis: FovAxis) {
+        self.fov_axis = fov_axis;
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
     pub fn set_width(&mut self, width: u32) {
         self.viewport = Viewport::new(width, self.viewport.pixels_y);
     }
@@ -73,22 +211,86 @@ impl Camera {
     /// and in the direction of the pixel.
     /// `x` should be in the range [-`num_pixels_x`, `num_pixels_x`]
     /// `y` should be in the range [-`num_pixels_y`, 0]
-    pub fn ray_from_pixel(&self, pixel_x: f64, pixel_y: f64) -> Ray {
-        let scale = (self.fov * 0.5).tan();
-        let x = ((2.0 * (pixel_x + 0.5)) / self.viewport.pixels_x as f64) * scale;
-        let y = (1.0 - 2.0 * (pixel_y + 0.5) / self.viewport.pixels_y as f64)
-            * scale
-            * (1.0 / self.viewport.aspect_ratio);
+    pub fn ray_from_pixel(&self, pixel_x: f64, pixel_y: f64) -> Option<Ray> {
+        self.ray_from_pixel_lens(pixel_x, pixel_y, 0.0, 0.0)
+    }
+
+    /// Same as [`Camera::ray_from_pixel`] but the ray starts at a point of the lens,
+    /// aimed at where the pinhole ray is [`Camera::focus_distance`] away.
+    ///
+    /// `lens_u` and `lens_v` in `[0, 1)` pick the point on the lens, `(0, 0)` is its center.
+    /// With an aperture of `0.0` the ray is the pinhole ray.
+    /// <https://en.wikipedia.org/wiki/Depth_of_field>
+    pub fn ray_from_pixel_lens(
+        &self,
+        pixel_x: f64,
+        pixel_y: f64,
+        lens_u: f64,
+        lens_v: f64,
+    ) -> Option<Ray> {
+        let aspect_ratio = self.viewport.aspect_ratio;
+        // Distance from the center is 1.0 at the edge of the image along the fov axis
+        let (scale_x, scale_y) = match self.fov_axis {
+            FovAxis::Horizontal => (1.0, 1.0 / aspect_ratio),
+            FovAxis::Vertical => (aspect_ratio, 1.0),
+        };
+        let x = ((2.0 * (pixel_x + 0.5)) / self.viewport.pixels_x as f64) * scale_x;
+        let y = (1.0 - 2.0 * (pixel_y + 0.5) / self.viewport.pixels_y as f64) * scale_y;
+
+        let (sin, cos) = self.roll.sin_cos();
+        let (x, y) = (x * cos - y * sin, x * sin + y * cos);
 
         // // Map x to range [-aspect_ratio, aspect_ratio]
         // let x = (pixel_x + 0.5) * self.viewport.pixel_width - self.viewport.aspect_ratio;
         // // Map y to range [-1, 1]
         // let y = (pixel_y + 0.5) * self.viewport.pixel_height - 1.0;
 
-        let direction = Vec3::new(x, y, self.distance).rotate(&self.rotation);
+        let mut origin = self.position;
+        let direction = match self.projection {
+            Projection::Pinhole => {
+                let scale = (self.fov * 0.5).tan();
+                Vec3::new(x * scale, y * scale, self.distance)
+            }
+            Projection::Orthographic { scale } => {
+                origin += Vec3::new(x * scale, y * scale, 0.0).rotate(&self.rotation);
+                Vec3::new(0.0, 0.0, 1.0)
+            }
+            Projection::Fisheye { fov } => {
+                let r = x.hypot(y);
+                let (sin, cos) = (r * fov * 0.5).sin_cos();
+                if r == 0.0 {
+                    Vec3::new(0.0, 0.0, 1.0)
+                } else {
+                    Vec3::new(x / r * sin, y / r * sin, cos)
+                }
+            }
+        }
+        .rotate(&self.rotation);
+
+        let ray = Ray::new(origin, direction).ok()?;
+        if self.aperture <= 0.0 {
+            return Some(ray);
+        }
+
+        // Uniform point on a disk, its square root keeps the density even
+        let radius = self.aperture / 2.0 * lens_u.sqrt();
+        let (sin, cos) = (2.0 * std::f64::consts::PI * lens_v).sin_cos();
+        let lens_point = origin + Vec3::new(radius * cos, radius * sin, 0.0).rotate(&self.rotation);
+        let focus_point = origin + ray.direction() * self.focus_distance;
+        Ray::new(lens_point, focus_point - lens_point).ok()
+    }
 
-        let origin = self.position;
-        Ray::new(origin, direction)
+    /// Returns the ray through the pixel together with the rays through
+    /// the neighbouring pixels at `x + 1` and `y + 1`, in that order.
+    ///
+    /// The difference between the rays estimates the footprint of the pixel,
+    /// e.g. for choosing a texture level of detail.
+    pub fn ray_from_pixel_with_diffs(&self, pixel_x: f64, pixel_y: f64) -> Option<(Ray, Ray, Ray)> {
+        Some((
+            self.ray_from_pixel(pixel_x, pixel_y)?,
+            self.ray_from_pixel(pixel_x + 1.0, pixel_y)?,
+            self.ray_from_pixel(pixel_x, pixel_y + 1.0)?,
+        ))
     }
 
     /// Returns the number of pixels in the resulting image.
@@ -96,13 +298,31 @@ impl Camera {
     pub fn pixels(&self) -> (u32, u32) {
         (self.viewport.pixels_x, self.viewport.pixels_y)
     }
+
+    /// Returns the unit direction that points right in the image, including the roll.
+    pub fn right(&self) -> Vec3 {
+        let (sin, cos) = self.roll.sin_cos();
+        Vec3::new(cos, sin, 0.0).rotate(&self.rotation).normalize()
+    }
+
+    /// Returns the cameras of the left and right eye,
+    /// moved `eye_separation / 2` to either side along [`Camera::right`].
+    pub fn stereo_pair(&self, eye_separation: f64) -> (Self, Self) {
+        let offset = self.right() * (eye_separation / 2.0);
+        let eye = |position| Self {
+            position,
+            ..self.clone()
+        };
+        (eye(self.position - offset), eye(self.position + offset))
+    }
 }
 
 /// A plane in front of the camera.
 ///
 /// The plane has dimensions:
 /// Top left: (-`aspect_ratio`,-1), Bottom right: (`aspect_ratio`,1)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Viewport {
     /// `width / height`
     aspect_ratio: f64,
@@ -127,3 +347,212 @@ impl Viewport {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roll_swaps_axes() {
+        let dir = Vec3::new(0.0, 0.0, 1.0);
+        let flat = Camera::new(64, 64, Vec3::zero(), dir, 90.0, 0.0).unwrap();
+        let rolled = Camera::new(64, 64, Vec3::zero(), dir, 90.0, 90.0).unwrap();
+
+        // A pixel to the right of the vertical center line
+        let (px, py) = (16.0, 31.5);
+
+        let flat = flat.ray_from_pixel(px, py).unwrap().direction();
+        assert!(flat.x > 0.0);
+        assert!(flat.y.abs() < 1e-9);
+
+        let rolled = rolled.ray_from_pixel(px, py).unwrap().direction();
+        assert!(rolled.x.abs() < 1e-9);
+        assert!((rolled.y - flat.x).abs() < 1e-9);
+        assert!((rolled.z - flat.z).abs() < 1e-9);
+    }
+
+    #[test]
+    fn up_vector_rolls_camera() {
+        let dir = Vec3::new(0.0, 0.0, 1.0);
+        let mut sideways = Camera::new(16, 16, Vec3::zero(), dir, 90.0, 0.0).unwrap();
+        sideways.set_up(Vec3::new(1.0, 0.0, 0.0));
+        let rolled = Camera::new(16, 16, Vec3::zero(), dir, 90.0, -90.0).unwrap();
+
+        assert_eq!(sideways.view_dir(), dir);
+        for (px, py) in [(-8.0, 0.0), (3.0, 7.5), (7.0, 15.0)] {
+            let a = sideways.ray_from_pixel(px, py).unwrap().direction();
+            let b = rolled.ray_from_pixel(px, py).unwrap().direction();
+            assert!((a - b).length() < 1e-9, "{a:?} {b:?}");
+        }
+
+        // The default up keeps the camera level
+        let mut level = Camera::new(16, 16, Vec3::zero(), dir, 90.0, 0.0).unwrap();
+        let before = level.clone();
+        level.set_up(Vec3::new(0.0, 3.0, 0.0));
+        assert_eq!(level, before);
+    }
+
+    #[test]
+    fn fov_axis_on_wide_image() {
+        let horizontal =
+            Camera::new(64, 32, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+        let mut vertical = horizontal.clone();
+        vertical.set_fov_axis("vertical".parse().unwrap());
+
+        // Right edge and top edge of the image, (slope x, slope y)
+        let slope = |camera: &Camera, px, py| {
+            let dir = camera.ray_from_pixel(px, py).unwrap().direction();
+            (dir.x / dir.z, dir.y / dir.z)
+        };
+
+        let (h_right, _) = slope(&horizontal, 31.5, 15.5);
+        let (v_right, _) = slope(&vertical, 31.5, 15.5);
+        assert!((v_right - 2.0 * h_right).abs() < 1e-9);
+
+        let (_, h_top) = slope(&horizontal, -0.5, -0.5);
+        let (_, v_top) = slope(&vertical, -0.5, -0.5);
+        assert!((v_top - 2.0 * h_top).abs() < 1e-9);
+
+        // The vertical fov covers as much vertically as the horizontal fov does horizontally
+        assert!((v_top - h_right).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ray_differentials_step_one_pixel() {
+        let camera =
+            Camera::new(64, 32, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+
+        // The pixel whose ray goes straight through the center of the viewport
+        let (center, dx, dy) = camera.ray_from_pixel_with_diffs(-0.5, 15.5).unwrap();
+        assert!(center.direction().x.abs() < 1e-9);
+        assert!(center.direction().y.abs() < 1e-9);
+
+        // One pixel on the viewport is `2 * tan(fov / 2) / pixels` wide
+        let scale = (camera.fov * 0.5).tan();
+        let step_x = (2.0 * scale / 64.0 / camera.distance).atan();
+        let step_y = (2.0 * scale / 32.0 / camera.viewport.aspect_ratio / camera.distance).atan();
+
+        let angle_x = center.direction().angle_between(dx.direction());
+        let angle_y = center.direction().angle_between(dy.direction());
+        assert!((angle_x - step_x).abs() < 1e-9, "{angle_x} {step_x}");
+        assert!((angle_y - step_y).abs() < 1e-9, "{angle_y} {step_y}");
+
+        assert!(dx.direction().x > 0.0);
+        assert!(dy.direction().y < 0.0);
+    }
+
+    #[test]
+    fn lens_rays_meet_at_focus_distance() {
+        let mut camera = Camera::new(
+            16,
+            16,
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(0.3, 0.2, 1.0),
+            60.0,
+            0.0,
+        )
+        .unwrap();
+        let (px, py) = (3.0, 11.0);
+        let pinhole = camera.ray_from_pixel(px, py).unwrap();
+        let is_pinhole = |ray: Option<Ray>| {
+            ray.is_some_and(|ray| {
+                ray.origin == pinhole.origin && ray.direction() == pinhole.direction()
+            })
+        };
+
+        // Without an aperture every point of the lens gives the pinhole ray
+        for (u, v) in [(0.0, 0.0), (0.9, 0.3), (0.5, 0.75)] {
+            assert!(is_pinhole(camera.ray_from_pixel_lens(px, py, u, v)));
+        }
+
+        camera.set_aperture(0.5);
+        camera.set_focus_distance(4.0);
+        let focus_point = pinhole.origin + pinhole.direction() * 4.0;
+        assert!(is_pinhole(camera.ray_from_pixel(px, py)));
+        for (u, v) in [(0.9, 0.3), (0.5, 0.75), (1.0, 0.0)] {
+            let ray = camera.ray_from_pixel_lens(px, py, u, v).unwrap();
+            assert_ne!(ray.origin, pinhole.origin);
+            assert!((ray.origin - pinhole.origin).length() <= 0.25 + 1e-9);
+            let to_focus = ray.origin.direction_to(focus_point);
+            assert!((ray.direction().dot(to_focus) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn look_at_matches_direction() {
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+
+        let ahead = Camera::look_at(
+            32,
+            16,
+            position,
+            position + Vec3::new(0.0, 0.0, 7.0),
+            up,
+            60.0,
+            0.0,
+        )
+        .unwrap();
+        let center = ahead.ray_from_pixel(-0.5, 7.5).unwrap();
+        assert_eq!(center.origin, position);
+        assert_eq!(center.direction(), Vec3::new(0.0, 0.0, 1.0));
+
+        // Same rays as the direction based constructor, also when rolled
+        let dir = Vec3::new(3.0, -4.0, 12.0);
+        let target = position + dir * 0.5;
+        for roll in [0.0, 30.0] {
+            let by_dir = Camera::new(32, 16, position, dir, 60.0, roll).unwrap();
+            let by_target = Camera::look_at(32, 16, position, target, up, 60.0, roll).unwrap();
+            for (px, py) in [(-0.5, 7.5), (-16.0, 0.0), (15.0, 15.0)] {
+                let a = by_dir.ray_from_pixel(px, py).unwrap();
+                let b = by_target.ray_from_pixel(px, py).unwrap();
+                assert_eq!(a.origin, b.origin);
+                assert_eq!(a.direction(), b.direction());
+            }
+        }
+
+        // Looking at the camera's own position has no direction
+        assert!(Camera::look_at(32, 16, position, position, up, 60.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn camera_looking_straight_down() {
+        let camera = Camera::new(
+            8,
+            8,
+            Vec3::new(0.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            90.0,
+            0.0,
+        )
+        .unwrap();
+        for (px, py) in [(-4.0, 0.0), (-0.5, 3.5), (3.0, 7.0)] {
+            let ray = camera.ray_from_pixel(px, py).unwrap();
+            assert!(ray.direction().is_finite() && ray.direction().y < 0.0);
+        }
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let mut camera = Camera::new(
+            32,
+            16,
+            Vec3::new(1.0, 0.0, -2.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            90.0,
+            0.0,
+        )
+        .unwrap();
+        camera.set_projection(Projection::Orthographic { scale: 4.0 });
+
+        let a = camera.ray_from_pixel(-16.0, 16.0).unwrap();
+        let b = camera.ray_from_pixel(15.0, 1.0).unwrap();
+        assert_eq!(a.direction(), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(a.direction(), b.direction());
+
+        // The origins lie on the image plane, 8 units wide for 32 pixels
+        assert_eq!((a.origin.z, b.origin.z), (-2.0, -2.0));
+        assert!((b.origin.x - a.origin.x - 31.0 * 0.25).abs() < 1e-9);
+        assert!((b.origin.y - a.origin.y - 15.0 * 0.25).abs() < 1e-9);
+    }
+}
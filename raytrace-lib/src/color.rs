@@ -1,7 +1,8 @@
 use std::str::FromStr;
 
 /// RGB color
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     /// [0, 1]
     r: f64,
@@ -20,7 +21,7 @@ impl Color {
         }
     }
 
-    pub fn new_f(r: f64, g: f64, b: f64) -> Self {
+    pub const fn new_f(r: f64, g: f64, b: f64) -> Self {
         Self { r, g, b }
     }
 
@@ -32,31 +33,158 @@ impl Color {
         }
     }
 
+    /// Multiply each channel by `s`.
+    /// The result is not clamped, see [`Color::clamped`].
     pub fn scale(&self, s: f64) -> Self {
         Self {
-            r: (self.r * s).min(1.0),
-            g: (self.g * s).min(1.0),
-            b: (self.b * s).min(1.0),
+            r: self.r * s,
+            g: self.g * s,
+            b: self.b * s,
         }
     }
 
+    /// Clamp each channel to `[0, 1]`.
+    /// Should only be applied once to the final color of a pixel.
+    pub fn clamped(&self) -> Self {
+        Self {
+            r: self.r.clamp(0.0, 1.0),
+            g: self.g.clamp(0.0, 1.0),
+            b: self.b.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Clamp negative channels to `0.0`, keeping values above `1.0`.
+    pub fn non_negative(&self) -> Self {
+        Self {
+            r: self.r.max(0.0),
+            g: self.g.max(0.0),
+            b: self.b.max(0.0),
+        }
+    }
+
+    /// Linear interpolation from `self` at `t = 0` to `other` at `t = 1`.
+    /// The result is not clamped.
+    pub fn lerp(&self, other: Self, t: f64) -> Self {
+        Self {
+            r: self.r + (other.r - self.r) * t,
+            g: self.g + (other.g - self.g) * t,
+            b: self.b + (other.b - self.b) * t,
+        }
+    }
+
+    /// Compress each channel from `[0, ∞)` into `[0, 1)` with the Reinhard
+    /// operator `c / (1 + c)`, bright channels keep their differences instead
+    /// of all clamping to `1.0`. Negative channels become `0.0`.
+    /// <https://en.wikipedia.org/wiki/Tone_mapping>
+    pub fn reinhard(&self) -> Self {
+        let map = |c: f64| {
+            let c = c.max(0.0);
+            c / (1.0 + c)
+        };
+        Self {
+            r: map(self.r),
+            g: map(self.g),
+            b: map(self.b),
+        }
+    }
+
+    /// Relative luminance of the linear color.
+    /// <https://en.wikipedia.org/wiki/Relative_luminance>
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// Scale the color down so its luminance is at most `max`, keeping its hue.
+    /// Colors at or below `max` are returned unchanged.
+    pub fn clamp_luminance(&self, max: f64) -> Self {
+        let luminance = self.luminance();
+        if luminance > max {
+            self.scale(max.max(0.0) / luminance)
+        } else {
+            *self
+        }
+    }
+
+    /// Composite `other` on top of `self` with `mode`.
+    /// `alpha` is the opacity of `other`, `0.0` returns `self` unchanged.
+    /// The result is not clamped.
+    pub fn blend(self, other: Self, mode: BlendMode, alpha: f64) -> Self {
+        let channel = |base: f64, layer: f64| match mode {
+            BlendMode::Normal => layer,
+            BlendMode::Add => base + layer,
+            BlendMode::Multiply => base * layer,
+            BlendMode::Screen => 1.0 - (1.0 - base) * (1.0 - layer),
+        };
+        let blended = Self {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+        };
+
+        self.lerp(blended, alpha)
+    }
+
     pub fn is_zero(&self) -> bool {
         self.r <= 0.0 && self.g <= 0.0 && self.b <= 0.0
     }
+
+    /// Returns the mean of all colors without clamping the intermediate sum.
+    /// Returns black if `colors` is empty.
+    pub fn average(colors: &[Self]) -> Self {
+        if colors.is_empty() {
+            return Self::zero();
+        }
+
+        let n = colors.len() as f64;
+        let (r, g, b) = colors
+            .iter()
+            .fold((0.0, 0.0, 0.0), |(r, g, b), c| (r + c.r, g + c.g, b + c.b));
+
+        Self {
+            r: r / n,
+            g: g / n,
+            b: b / n,
+        }
+    }
 }
 
+/// The result is not clamped, see [`Color::clamped`].
 impl std::ops::Add for Color {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
         Self {
-            r: (self.r + rhs.r).min(1.0),
-            g: (self.g + rhs.g).min(1.0),
-            b: (self.b + rhs.b).min(1.0),
+            r: self.r + rhs.r,
+            g: self.g + rhs.g,
+            b: self.b + rhs.b,
         }
     }
 }
 
+impl std::ops::AddAssign for Color {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::Sub for Color {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            r: self.r - rhs.r,
+            g: self.g - rhs.g,
+            b: self.b - rhs.b,
+        }
+    }
+}
+
+impl std::ops::SubAssign for Color {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
 impl std::ops::Mul for Color {
     type Output = Self;
 
@@ -69,106 +197,496 @@ impl std::ops::Mul for Color {
     }
 }
 
+impl std::ops::Mul<f64> for Color {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        self.scale(rhs)
+    }
+}
+
+impl std::ops::MulAssign<f64> for Color {
+    fn mul_assign(&mut self, rhs: f64) {
+        *self = *self * rhs;
+    }
+}
+
+impl std::ops::Mul<Color> for f64 {
+    type Output = Color;
+
+    fn mul(self, rhs: Color) -> Self::Output {
+        rhs.scale(self)
+    }
+}
+
+impl Color {
+    /// Returns the linear channels as floats, without clamping.
+    pub fn to_linear_array(self) -> [f32; 3] {
+        [self.r as f32, self.g as f32, self.b as f32]
+    }
+
+    /// Convert to 16-bit channels for high bit depth output.
+    /// Channels outside of `[0, 1]` are clamped.
+    pub fn to_u16_array(self) -> [u16; 3] {
+        let Self { r, g, b } = self.clamped();
+        [
+            (r * 65535.0).round() as u16,
+            (g * 65535.0).round() as u16,
+            (b * 65535.0).round() as u16,
+        ]
+    }
+}
+
+impl Color {
+    /// Decode 8-bit sRGB channels, e.g. from an image file, to linear channels.
+    pub fn from_srgb_bytes([r, g, b]: [u8; 3]) -> Self {
+        let decode = |c: u8| srgb_to_linear(f64::from(c) / 255.0);
+        Self::new_f(decode(r), decode(g), decode(b))
+    }
+
+    /// Encode to 8-bit sRGB channels for display, channels are clamped first.
+    /// Lighting is computed on linear channels, which look too dark
+    /// when written to an image as they are.
+    pub fn to_srgb_bytes(self) -> [u8; 3] {
+        let Self { r, g, b } = self.clamped();
+        let encode = |c: f64| (linear_to_srgb(c) * 255.0).round() as u8;
+        [encode(r), encode(g), encode(b)]
+    }
+
+    /// Same as [`Color::to_srgb_bytes`] with 16-bit channels.
+    pub fn to_srgb_u16_array(self) -> [u16; 3] {
+        let Self { r, g, b } = self.clamped();
+        let encode = |c: f64| (linear_to_srgb(c) * 65535.0).round() as u16;
+        [encode(r), encode(g), encode(b)]
+    }
+}
+
+/// The sRGB transfer function, for a linear channel in `[0, 1]`.
+/// <https://en.wikipedia.org/wiki/SRGB#Transfer_function_(%22gamma%22)>
+fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of [`linear_to_srgb`].
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Channels outside of `[0, 1]` are clamped.
 impl From<Color> for [u8; 3] {
     fn from(value: Color) -> Self {
-        debug_assert!(
-            (0.0..=1.0).contains(&value.r)
-                && (0.0..=1.0).contains(&value.g)
-                && (0.0..=1.0).contains(&value.b)
-        );
+        let Color { r, g, b } = value.clamped();
         [
-            (value.r * 255.0).round() as u8,
-            (value.g * 255.0).round() as u8,
-            (value.b * 255.0).round() as u8,
+            (r * 255.0).round() as u8,
+            (g * 255.0).round() as u8,
+            (b * 255.0).round() as u8,
         ]
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum ColorNames {
-    // Base
-    White,
-    Black,
-    Red,
-    Green,
-    Blue,
-    Yellow,
-    Cyan,
-    Magenta,
-    // Gold
-    Gold,
-    GoldenYellow,
-    MetallicGold,
-    OldGold,
-    GoldenPoppy,
+/// How a layer is combined with the color below it, see [`Color::blend`].
+/// <https://en.wikipedia.org/wiki/Blend_modes>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// The layer replaces the color below.
+    #[default]
+    Normal,
+    /// The channels are summed, brightening the result.
+    Add,
+    /// The channels are multiplied, darkening the result.
+    Multiply,
+    /// The inverted channels are multiplied, brightening the result.
+    Screen,
 }
 
-macro_rules! color {
-    ($r:expr,$b:expr,$g:expr) => {
-        Color {
-            r: $r,
-            g: $g,
-            b: $b,
-        }
-    };
+/// A color that can be referred to by name, e.g. `"tomato"` in a scene.
+///
+/// Contains the CSS named colors and a few shades of gold.
+/// <https://www.w3.org/TR/css-color-4/#named-colors>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorNames {
+    name: &'static str,
+    rgb: [u8; 3],
 }
 
-macro_rules! color_255 {
-    ($r:expr,$b:expr,$g:expr) => {
-        Color {
-            r: ($r / 255u8) as f64,
-            g: ($g / 255u8) as f64,
-            b: ($b / 255u8) as f64,
+impl ColorNames {
+    const fn new(name: &'static str, rgb: [u8; 3]) -> Self {
+        Self { name, rgb }
+    }
+
+    /// Every named color, sorted by name.
+    pub fn all() -> &'static [Self] {
+        &COLOR_NAMES
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the name closest to `name` if it looks like a typo of it.
+    pub fn suggest(name: &str) -> Option<&'static str> {
+        let name = name.to_ascii_lowercase();
+        COLOR_NAMES
+            .iter()
+            .map(|c| (edit_distance(&name, c.name), c.name))
+            .filter(|&(distance, _)| distance <= 2)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, name)| name)
+    }
+}
+
+/// Number of single byte insertions, deletions and substitutions that turn `a` into `b`.
+/// <https://en.wikipedia.org/wiki/Levenshtein_distance>
+fn edit_distance(a: &str, b: &str) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.bytes().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
         }
-    };
+    }
+    row[b.len()]
 }
 
 impl From<ColorNames> for Color {
     fn from(value: ColorNames) -> Self {
-        use ColorNames::*;
-
-        match value {
-            White => color!(1.0, 1.0, 1.0),
-            Black => color!(0.0, 0.0, 0.0),
-            Red => color!(1.0, 0.0, 0.0),
-            Green => color!(0.0, 1.0, 0.0),
-            Blue => color!(0.0, 0.0, 1.0),
-            Yellow => color!(1.0, 1.0, 0.0),
-            Cyan => color!(0.0, 1.0, 1.0),
-            Magenta => color!(1.0, 0.0, 1.0),
-            Gold => color_255!(255, 215, 0),
-            GoldenYellow => color_255!(255, 223, 0),
-            MetallicGold => color_255!(212, 175, 55),
-            OldGold => color_255!(207, 181, 59),
-            GoldenPoppy => color_255!(252, 194, 0),
-        }
+        let [r, g, b] = value.rgb;
+        Self::new(r, g, b)
     }
 }
 
 impl FromStr for ColorNames {
     type Err = String;
 
+    /// Names are case insensitive.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use ColorNames::*;
-
-        let color = match s {
-            "white" => White,
-            "black" => Black,
-            "red" => Red,
-            "green" => Green,
-            "blue" => Blue,
-            "yellow" => Yellow,
-            "cyan" => Cyan,
-            "magenta" => Magenta,
-            "gold" => Gold,
-            "golden_yellow" => GoldenYellow,
-            "metallic_gold" => MetallicGold,
-            "old_gold" => OldGold,
-            "golden_poppy" => GoldenPoppy,
-            _ => {
-                return Err(format!("No color named '{}'", s));
-            }
-        };
-        Ok(color)
+        let name = s.to_ascii_lowercase();
+        match COLOR_NAMES.binary_search_by(|c| c.name.cmp(&name)) {
+            Ok(index) => Ok(COLOR_NAMES[index]),
+            Err(_) => Err(match Self::suggest(s) {
+                Some(suggestion) => format!("No color named '{s}', did you mean '{suggestion}'?"),
+                None => format!("No color named '{s}'"),
+            }),
+        }
+    }
+}
+
+/// Sorted by name so that it can be binary searched.
+const COLOR_NAMES: [ColorNames; 152] = [
+    ColorNames::new("aliceblue", [240, 248, 255]),
+    ColorNames::new("antiquewhite", [250, 235, 215]),
+    ColorNames::new("aqua", [0, 255, 255]),
+    ColorNames::new("aquamarine", [127, 255, 212]),
+    ColorNames::new("azure", [240, 255, 255]),
+    ColorNames::new("beige", [245, 245, 220]),
+    ColorNames::new("bisque", [255, 228, 196]),
+    ColorNames::new("black", [0, 0, 0]),
+    ColorNames::new("blanchedalmond", [255, 235, 205]),
+    ColorNames::new("blue", [0, 0, 255]),
+    ColorNames::new("blueviolet", [138, 43, 226]),
+    ColorNames::new("brown", [165, 42, 42]),
+    ColorNames::new("burlywood", [222, 184, 135]),
+    ColorNames::new("cadetblue", [95, 158, 160]),
+    ColorNames::new("chartreuse", [127, 255, 0]),
+    ColorNames::new("chocolate", [210, 105, 30]),
+    ColorNames::new("coral", [255, 127, 80]),
+    ColorNames::new("cornflowerblue", [100, 149, 237]),
+    ColorNames::new("cornsilk", [255, 248, 220]),
+    ColorNames::new("crimson", [220, 20, 60]),
+    ColorNames::new("cyan", [0, 255, 255]),
+    ColorNames::new("darkblue", [0, 0, 139]),
+    ColorNames::new("darkcyan", [0, 139, 139]),
+    ColorNames::new("darkgoldenrod", [184, 134, 11]),
+    ColorNames::new("darkgray", [169, 169, 169]),
+    ColorNames::new("darkgreen", [0, 100, 0]),
+    ColorNames::new("darkgrey", [169, 169, 169]),
+    ColorNames::new("darkkhaki", [189, 183, 107]),
+    ColorNames::new("darkmagenta", [139, 0, 139]),
+    ColorNames::new("darkolivegreen", [85, 107, 47]),
+    ColorNames::new("darkorange", [255, 140, 0]),
+    ColorNames::new("darkorchid", [153, 50, 204]),
+    ColorNames::new("darkred", [139, 0, 0]),
+    ColorNames::new("darksalmon", [233, 150, 122]),
+    ColorNames::new("darkseagreen", [143, 188, 143]),
+    ColorNames::new("darkslateblue", [72, 61, 139]),
+    ColorNames::new("darkslategray", [47, 79, 79]),
+    ColorNames::new("darkslategrey", [47, 79, 79]),
+    ColorNames::new("darkturquoise", [0, 206, 209]),
+    ColorNames::new("darkviolet", [148, 0, 211]),
+    ColorNames::new("deeppink", [255, 20, 147]),
+    ColorNames::new("deepskyblue", [0, 191, 255]),
+    ColorNames::new("dimgray", [105, 105, 105]),
+    ColorNames::new("dimgrey", [105, 105, 105]),
+    ColorNames::new("dodgerblue", [30, 144, 255]),
+    ColorNames::new("firebrick", [178, 34, 34]),
+    ColorNames::new("floralwhite", [255, 250, 240]),
+    ColorNames::new("forestgreen", [34, 139, 34]),
+    ColorNames::new("fuchsia", [255, 0, 255]),
+    ColorNames::new("gainsboro", [220, 220, 220]),
+    ColorNames::new("ghostwhite", [248, 248, 255]),
+    ColorNames::new("gold", [255, 215, 0]),
+    ColorNames::new("golden_poppy", [252, 194, 0]),
+    ColorNames::new("golden_yellow", [255, 223, 0]),
+    ColorNames::new("goldenrod", [218, 165, 32]),
+    ColorNames::new("gray", [128, 128, 128]),
+    ColorNames::new("green", [0, 128, 0]),
+    ColorNames::new("greenyellow", [173, 255, 47]),
+    ColorNames::new("grey", [128, 128, 128]),
+    ColorNames::new("honeydew", [240, 255, 240]),
+    ColorNames::new("hotpink", [255, 105, 180]),
+    ColorNames::new("indianred", [205, 92, 92]),
+    ColorNames::new("indigo", [75, 0, 130]),
+    ColorNames::new("ivory", [255, 255, 240]),
+    ColorNames::new("khaki", [240, 230, 140]),
+    ColorNames::new("lavender", [230, 230, 250]),
+    ColorNames::new("lavenderblush", [255, 240, 245]),
+    ColorNames::new("lawngreen", [124, 252, 0]),
+    ColorNames::new("lemonchiffon", [255, 250, 205]),
+    ColorNames::new("lightblue", [173, 216, 230]),
+    ColorNames::new("lightcoral", [240, 128, 128]),
+    ColorNames::new("lightcyan", [224, 255, 255]),
+    ColorNames::new("lightgoldenrodyellow", [250, 250, 210]),
+    ColorNames::new("lightgray", [211, 211, 211]),
+    ColorNames::new("lightgreen", [144, 238, 144]),
+    ColorNames::new("lightgrey", [211, 211, 211]),
+    ColorNames::new("lightpink", [255, 182, 193]),
+    ColorNames::new("lightsalmon", [255, 160, 122]),
+    ColorNames::new("lightseagreen", [32, 178, 170]),
+    ColorNames::new("lightskyblue", [135, 206, 250]),
+    ColorNames::new("lightslategray", [119, 136, 153]),
+    ColorNames::new("lightslategrey", [119, 136, 153]),
+    ColorNames::new("lightsteelblue", [176, 196, 222]),
+    ColorNames::new("lightyellow", [255, 255, 224]),
+    ColorNames::new("lime", [0, 255, 0]),
+    ColorNames::new("limegreen", [50, 205, 50]),
+    ColorNames::new("linen", [250, 240, 230]),
+    ColorNames::new("magenta", [255, 0, 255]),
+    ColorNames::new("maroon", [128, 0, 0]),
+    ColorNames::new("mediumaquamarine", [102, 205, 170]),
+    ColorNames::new("mediumblue", [0, 0, 205]),
+    ColorNames::new("mediumorchid", [186, 85, 211]),
+    ColorNames::new("mediumpurple", [147, 112, 219]),
+    ColorNames::new("mediumseagreen", [60, 179, 113]),
+    ColorNames::new("mediumslateblue", [123, 104, 238]),
+    ColorNames::new("mediumspringgreen", [0, 250, 154]),
+    ColorNames::new("mediumturquoise", [72, 209, 204]),
+    ColorNames::new("mediumvioletred", [199, 21, 133]),
+    ColorNames::new("metallic_gold", [212, 175, 55]),
+    ColorNames::new("midnightblue", [25, 25, 112]),
+    ColorNames::new("mintcream", [245, 255, 250]),
+    ColorNames::new("mistyrose", [255, 228, 225]),
+    ColorNames::new("moccasin", [255, 228, 181]),
+    ColorNames::new("navajowhite", [255, 222, 173]),
+    ColorNames::new("navy", [0, 0, 128]),
+    ColorNames::new("old_gold", [207, 181, 59]),
+    ColorNames::new("oldlace", [253, 245, 230]),
+    ColorNames::new("olive", [128, 128, 0]),
+    ColorNames::new("olivedrab", [107, 142, 35]),
+    ColorNames::new("orange", [255, 165, 0]),
+    ColorNames::new("orangered", [255, 69, 0]),
+    ColorNames::new("orchid", [218, 112, 214]),
+    ColorNames::new("palegoldenrod", [238, 232, 170]),
+    ColorNames::new("palegreen", [152, 251, 152]),
+    ColorNames::new("paleturquoise", [175, 238, 238]),
+    ColorNames::new("palevioletred", [219, 112, 147]),
+    ColorNames::new("papayawhip", [255, 239, 213]),
+    ColorNames::new("peachpuff", [255, 218, 185]),
+    ColorNames::new("peru", [205, 133, 63]),
+    ColorNames::new("pink", [255, 192, 203]),
+    ColorNames::new("plum", [221, 160, 221]),
+    ColorNames::new("powderblue", [176, 224, 230]),
+    ColorNames::new("purple", [128, 0, 128]),
+    ColorNames::new("rebeccapurple", [102, 51, 153]),
+    ColorNames::new("red", [255, 0, 0]),
+    ColorNames::new("rosybrown", [188, 143, 143]),
+    ColorNames::new("royalblue", [65, 105, 225]),
+    ColorNames::new("saddlebrown", [139, 69, 19]),
+    ColorNames::new("salmon", [250, 128, 114]),
+    ColorNames::new("sandybrown", [244, 164, 96]),
+    ColorNames::new("seagreen", [46, 139, 87]),
+    ColorNames::new("seashell", [255, 245, 238]),
+    ColorNames::new("sienna", [160, 82, 45]),
+    ColorNames::new("silver", [192, 192, 192]),
+    ColorNames::new("skyblue", [135, 206, 235]),
+    ColorNames::new("slateblue", [106, 90, 205]),
+    ColorNames::new("slategray", [112, 128, 144]),
+    ColorNames::new("slategrey", [112, 128, 144]),
+    ColorNames::new("snow", [255, 250, 250]),
+    ColorNames::new("springgreen", [0, 255, 127]),
+    ColorNames::new("steelblue", [70, 130, 180]),
+    ColorNames::new("tan", [210, 180, 140]),
+    ColorNames::new("teal", [0, 128, 128]),
+    ColorNames::new("thistle", [216, 191, 216]),
+    ColorNames::new("tomato", [255, 99, 71]),
+    ColorNames::new("turquoise", [64, 224, 208]),
+    ColorNames::new("violet", [238, 130, 238]),
+    ColorNames::new("wheat", [245, 222, 179]),
+    ColorNames::new("white", [255, 255, 255]),
+    ColorNames::new("whitesmoke", [245, 245, 245]),
+    ColorNames::new("yellow", [255, 255, 0]),
+    ColorNames::new("yellowgreen", [154, 205, 50]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn css_color_names() {
+        let rgb = |name: &str| ColorNames::from_str(name).unwrap().rgb;
+        assert_eq!(rgb("tomato"), [255, 99, 71]);
+        assert_eq!(rgb("rebeccapurple"), [102, 51, 153]);
+        assert_eq!(rgb("green"), [0, 128, 0]);
+        assert_eq!(rgb("blue"), [0, 0, 255]);
+        assert_eq!(rgb("Gold"), [255, 215, 0]);
+        assert_eq!(rgb("golden_poppy"), [252, 194, 0]);
+        assert_eq!(
+            Color::from(ColorNames::from_str("tomato").unwrap()),
+            Color::new(255, 99, 71)
+        );
+
+        let err = ColorNames::from_str("tomatoe").unwrap_err();
+        assert_eq!(err, "No color named 'tomatoe', did you mean 'tomato'?");
+        let err = ColorNames::from_str("definitely-not-a-color").unwrap_err();
+        assert_eq!(err, "No color named 'definitely-not-a-color'");
+
+        let names: Vec<_> = ColorNames::all().iter().map(ColorNames::name).collect();
+        assert!(names.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(names.len(), 148 + 4);
+    }
+
+    #[test]
+    fn scale_does_not_clamp() {
+        let white = Color::new_f(1.0, 1.0, 1.0);
+
+        let scaled = white.scale(2.0);
+        assert_eq!(scaled, Color::new_f(2.0, 2.0, 2.0));
+        assert_eq!(scaled.clamped(), white);
+    }
+
+    #[test]
+    fn mul_scalar() {
+        let c = Color::new_f(0.25, 0.5, 0.75);
+        let expected = Color::new_f(0.5, 1.0, 1.5);
+
+        assert_eq!(c * 2.0, expected);
+        assert_eq!(2.0 * c, expected);
+    }
+
+    #[test]
+    fn clamp_luminance() {
+        let max = 2.0;
+        let normal = Color::new_f(0.9, 0.5, 1.3);
+        assert_eq!(normal.clamp_luminance(max), normal);
+
+        let firefly = Color::new_f(40.0, 20.0, 10.0);
+        let clamped = firefly.clamp_luminance(max);
+        assert!((clamped.luminance() - max).abs() < 1e-12);
+        // Keeps the hue
+        assert!((clamped.r / clamped.g - 2.0).abs() < 1e-12);
+        assert!((clamped.g / clamped.b - 2.0).abs() < 1e-12);
+
+        let average = Color::average(&[normal, normal, firefly.clamp_luminance(max)]);
+        assert!(average.luminance() <= max);
+    }
+
+    #[test]
+    fn blend_modes() {
+        let gray = Color::new_f(0.5, 0.5, 0.5);
+        let base = Color::new_f(0.25, 0.5, 1.0);
+
+        assert_eq!(base.blend(gray, BlendMode::Normal, 1.0), gray);
+        assert_eq!(
+            base.blend(gray, BlendMode::Add, 1.0),
+            Color::new_f(0.75, 1.0, 1.5)
+        );
+        assert_eq!(
+            base.blend(gray, BlendMode::Multiply, 1.0),
+            Color::new_f(0.125, 0.25, 0.5)
+        );
+        assert_eq!(
+            gray.blend(gray, BlendMode::Screen, 1.0),
+            Color::new_f(0.75, 0.75, 0.75)
+        );
+
+        // Alpha mixes the blended color with the base
+        assert_eq!(
+            base.blend(gray, BlendMode::Normal, 0.5),
+            Color::new_f(0.375, 0.5, 0.75)
+        );
+        assert_eq!(
+            gray.blend(gray, BlendMode::Screen, 0.5),
+            Color::new_f(0.625, 0.625, 0.625)
+        );
+        assert_eq!(base.blend(gray, BlendMode::Multiply, 0.0), base);
+    }
+
+    #[test]
+    fn add_is_associative_above_one() {
+        let a = Color::new_f(0.75, 0.5, 1.0);
+        let b = Color::new_f(0.5, 0.75, 0.25);
+        let c = Color::new_f(0.25, 0.5, 0.75);
+
+        assert_eq!((a + b) + c, a + (b + c));
+        assert_eq!(a + b + c, Color::new_f(1.5, 1.75, 2.0));
+        assert_eq!((a + b + c).clamped(), Color::new_f(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn assign_operators() {
+        let colors = [
+            Color::new_f(0.1, 0.2, 0.3),
+            Color::new_f(0.25, 0.0, 0.5),
+            Color::new_f(0.5, 0.5, 0.125),
+        ];
+
+        let mut acc = Color::zero();
+        for &c in &colors {
+            acc += c;
+        }
+        acc -= colors[0];
+        acc *= 0.5;
+
+        assert_eq!(acc, (colors[0] + colors[1] + colors[2] - colors[0]) * 0.5);
+    }
+
+    #[test]
+    fn out_of_gamut_conversion() {
+        let c = Color::new_f(-0.001, 0.5, 1.2);
+
+        let bytes: [u8; 3] = c.into();
+        assert_eq!(bytes, [0, 128, 255]);
+        assert_eq!(c.to_u16_array(), [0, 32768, 65535]);
+
+        assert_eq!(c.non_negative(), Color::new_f(0.0, 0.5, 1.2));
+        assert_eq!(c.clamped(), Color::new_f(0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn srgb_encoding() {
+        let gray = Color::new_f(0.5, 0.5, 0.5);
+        assert_eq!(gray.to_srgb_bytes(), [188, 188, 188]);
+
+        let c = Color::new_f(-0.001, 0.0, 1.2);
+        assert_eq!(c.to_srgb_bytes(), [0, 0, 255]);
+        assert_eq!(c.to_srgb_u16_array(), [0, 0, 65535]);
+
+        // Decoding undoes the encoding
+        for byte in [0, 1, 10, 64, 128, 188, 254, 255] {
+            let color = Color::from_srgb_bytes([byte, byte, byte]);
+            assert_eq!(color.to_srgb_bytes(), [byte, byte, byte]);
+        }
+        assert!((Color::from_srgb_bytes([188, 0, 0]).r - 0.5).abs() < 0.005);
     }
 }
@@ -1,28 +1,46 @@
 //! A simple raytracer.
 
+pub mod aabb;
+pub mod bvh;
 pub mod camera;
 pub mod color;
 pub mod light;
 pub mod material;
+pub mod obj;
 pub mod object;
+pub mod postprocess;
 pub mod primitive;
+pub mod progressive;
 pub mod ray;
+pub mod rng;
 pub mod rotation;
+pub mod scene;
+pub mod texture;
+pub mod transform;
 pub mod vec3;
 
-pub use camera::Camera;
+pub use camera::{Camera, FovAxis, Projection};
 pub use color::Color;
 pub use light::Light;
 pub use material::Material;
 pub use object::Object;
-use rayon::prelude::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use rayon::prelude::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+pub use scene::{Scene, SceneChange};
 pub use vec3::Vec3;
 
+use bvh::{Bvh, BvhStats};
+use material::{normal_reflectance, schlick};
 use primitive::Primitive;
-use ray::{Ray, RayHit};
+use ray::Ray;
+use rng::XorShift64;
 use rotation::Rotation;
-
-use std::sync::Arc;
+use std::{
+    borrow::Cow,
+    ops::ControlFlow,
+    time::{Duration, Instant},
+};
 
 pub enum SceneObject {
     Camera(Camera),
@@ -33,6 +51,12 @@ pub enum SceneObject {
 /// Precision of comparisons.
 pub const FLOAT_EPS: f64 = 0.00000001;
 
+/// Minimum distance along a ray for an intersection to count.
+pub const DISTANCE_EPS: f64 = 0.000001;
+
+/// Default distance that shadow rays start above the surface, along its normal.
+pub const SHADOW_BIAS: f64 = FLOAT_EPS * 1000.0;
+
 /// The direction of “up”.
 const UP_DIRECTION: Vec3 = Vec3 {
     x: 0.0,
@@ -40,10 +64,148 @@ const UP_DIRECTION: Vec3 = Vec3 {
     z: 0.0,
 };
 
-#[derive(Debug)]
+/// Information about the primary ray of a single pixel.
+#[derive(Debug, Clone)]
+pub struct PixelDebug {
+    /// Index of the hit object in the world.
+    pub object_index: usize,
+    /// Name of the hit object, if it has one.
+    pub object_name: Option<String>,
+    /// The intersection point.
+    pub intersection: Vec3,
+    /// The normal at the intersection point.
+    pub normal: Vec3,
+    /// The final color of the pixel.
+    pub color: Color,
+}
+
+impl std::fmt::Display for PixelDebug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.object_name {
+            Some(name) => write!(f, "object '{name}' (index {})", self.object_index)?,
+            None => write!(f, "object index {}", self.object_index)?,
+        }
+        write!(
+            f,
+            "\n  intersection: {:?}\n  normal: {:?}\n  color: {:?}",
+            self.intersection, self.normal, self.color
+        )
+    }
+}
+
+/// Terms of the shading model, see [`Raytracer::set_shading_terms`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShadingTerms {
+    /// Ambient light and emission.
+    pub ambient: bool,
+    /// Lambertian reflection of the lights, and light passing through translucent surfaces.
+    pub diffuse: bool,
+    /// Mirror reflection and clear coat highlights.
+    pub specular: bool,
+}
+
+impl ShadingTerms {
+    pub const ALL: Self = Self {
+        ambient: true,
+        diffuse: true,
+        specular: true,
+    };
+    pub const AMBIENT: Self = Self {
+        ambient: true,
+        diffuse: false,
+        specular: false,
+    };
+    pub const DIFFUSE: Self = Self {
+        ambient: false,
+        diffuse: true,
+        specular: false,
+    };
+    pub const SPECULAR: Self = Self {
+        ambient: false,
+        diffuse: false,
+        specular: true,
+    };
+}
+
+/// How many primitives the primary rays of a frame test, and the hierarchy they traverse.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraversalStats {
+    /// Number of objects in the world.
+    pub objects: usize,
+    /// Number of primary rays traced.
+    pub rays: usize,
+    /// Total number of primitives tested by all rays.
+    pub primitives_tested: usize,
+    /// Shape of the hierarchy the rays traversed.
+    pub bvh: BvhStats,
+}
+
+impl TraversalStats {
+    pub fn tested_per_ray(&self) -> f64 {
+        if self.rays == 0 {
+            return 0.0;
+        }
+        self.primitives_tested as f64 / self.rays as f64
+    }
+}
+
+impl std::fmt::Display for TraversalStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "objects: {}\nbvh nodes: {}\nbvh max depth: {}\nobjects per leaf: {:.2}\n\
+             rays: {}\nprimitives tested: {}\nprimitives tested per ray: {:.2}",
+            self.objects,
+            self.bvh.nodes,
+            self.bvh.max_depth,
+            self.bvh.avg_leaf_size(),
+            self.rays,
+            self.primitives_tested,
+            self.tested_per_ray()
+        )
+    }
+}
+
+/// Where a primary ray goes through its pixel and the camera lens.
+#[derive(Debug, Clone, Copy, Default)]
+struct PixelSample {
+    /// Offset from the center of the pixel, in pixels.
+    offset: (f64, f64),
+    /// Point on the lens, see [`Camera::ray_from_pixel_lens`].
+    lens: (f64, f64),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Raytracer {
     camera: Camera,
     recurse_depth: u32,
+    /// Number of instants rendered while the shutter is open.
+    time_samples: u32,
+    /// Seed for the random offsets of the time samples.
+    seed: u64,
+    /// Maximum luminance of a single sample, suppresses fireflies.
+    luminance_clamp: Option<f64>,
+    /// Distance from a surface within which rays leaving it ignore hits,
+    /// so they don't hit the surface they start on.
+    epsilon: f64,
+    /// Whether primary rays go through a random point of their pixel
+    /// instead of its center.
+    pixel_jitter: bool,
+    /// Number of jittered primary rays averaged into each pixel.
+    samples_per_pixel: u32,
+    /// Whether each shading point samples a single light chosen by importance
+    /// instead of every light.
+    light_sampling: bool,
+    /// Terms of the shading model included for surfaces hit by primary rays.
+    shading_terms: ShadingTerms,
+    /// Distance that shadow rays start above the surface, along its normal.
+    shadow_bias: f64,
+    /// Exposure that [`Raytracer::raycast`] tone maps its output with, if any.
+    exposure: Option<f64>,
+    /// Color of the pixels whose primary ray misses every object.
+    background: Color,
 }
 
 impl Raytracer {
@@ -51,6 +213,17 @@ impl Raytracer {
         Self {
             camera,
             recurse_depth,
+            time_samples: 1,
+            seed: 0,
+            luminance_clamp: None,
+            epsilon: DISTANCE_EPS,
+            pixel_jitter: false,
+            samples_per_pixel: 1,
+            light_sampling: false,
+            shading_terms: ShadingTerms::ALL,
+            shadow_bias: SHADOW_BIAS,
+            exposure: None,
+            background: Color::zero(),
         }
     }
 
@@ -62,33 +235,227 @@ impl Raytracer {
         self.camera.set_height(height);
     }
 
+    pub fn set_fov_axis(&mut self, fov_axis: FovAxis) {
+        self.camera.set_fov_axis(fov_axis);
+    }
+
     pub fn set_recurse_depth(&mut self, depth: u32) {
         self.recurse_depth = depth;
     }
+
+    pub fn set_shutter(&mut self, shutter: f64) {
+        self.camera.set_shutter(shutter);
+    }
+
+    pub fn set_time_samples(&mut self, samples: u32) {
+        self.time_samples = samples.max(1);
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Send primary rays through a random point of their pixel.
+    /// Averaging renders with different seeds then antialiases the image.
+    pub fn set_pixel_jitter(&mut self, jitter: bool) {
+        self.pixel_jitter = jitter;
+    }
+
+    /// Tone map the images of [`Raytracer::raycast`] and [`Raytracer::par_raycast`]
+    /// with [`postprocess::tone_map`] at `exposure`. `None` returns linear colors.
+    pub fn set_exposure(&mut self, exposure: Option<f64>) {
+        self.exposure = exposure;
+    }
+
+    /// Paint the pixels that don't hit any object with `background` instead of black.
+    pub fn set_background(&mut self, background: Color) {
+        self.background = background;
+    }
+
+    pub fn background(&self) -> Color {
+        self.background
+    }
+
+    /// Average `samples` primary rays through random points of each pixel,
+    /// antialiasing the image in a single render.
+    pub fn set_samples_per_pixel(&mut self, samples: u32) {
+        self.samples_per_pixel = samples.max(1);
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    /// Shade each point with one light, picked with a probability proportional
+    /// to its intensity and weighted by the inverse of that probability.
+    /// Noisier than tracing every light, but the cost no longer grows with the
+    /// number of lights. Averaging renders with different seeds converges to the same image.
+    pub fn set_light_sampling(&mut self, sampling: bool) {
+        self.light_sampling = sampling;
+    }
+
+    /// Only include `terms` when shading the surfaces hit by primary rays.
+    /// Rendering each term on its own shows what it contributes to the image.
+    pub fn set_shading_terms(&mut self, terms: ShadingTerms) {
+        self.shading_terms = terms;
+    }
+
+    /// Set the distance from a surface within which rays leaving it ignore hits.
+    /// Scenes far from the origin need a larger value than [`DISTANCE_EPS`].
+    pub fn set_epsilon(&mut self, epsilon: f64) {
+        self.epsilon = epsilon;
+    }
+
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    /// Set the distance that shadow rays start above the surface, along its normal.
+    /// Too small a value lets surfaces shadow themselves, too large a value
+    /// detaches shadows from the objects casting them.
+    pub fn set_shadow_bias(&mut self, bias: f64) {
+        self.shadow_bias = bias;
+    }
+
+    /// Clamp the luminance of every sample to `max` before it is averaged.
+    /// Removes bright outliers at the cost of some energy.
+    pub fn set_luminance_clamp(&mut self, max: Option<f64>) {
+        self.luminance_clamp = max;
+    }
+
+    /// Move the camera to the origin, moving every object and light along with it.
+    ///
+    /// Intersection math subtracts positions from each other, which loses precision
+    /// when the scene is far from the origin. After the move the geometry near the
+    /// camera is also near the origin. Returns the offset added to every position,
+    /// subtract it from positions found after the move to get world coordinates.
+    pub fn make_camera_relative(&mut self, world: &mut [Object], lights: &mut [Light]) -> Vec3 {
+        let offset = -self.camera.position();
+
+        self.camera.set_position(Vec3::zero());
+        for object in world {
+            object.primitive = object.primitive.translated(offset);
+        }
+        for light in lights {
+            light.pos += offset;
+        }
+
+        offset
+    }
+
+    /// Returns the number of pixels in the resulting image.
+    /// (width, height)
+    pub fn pixels(&self) -> (u32, u32) {
+        self.camera.pixels()
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
 }
 
 impl Raytracer {
-    /// Return the position of any visible lights together with their intensity.
-    fn trace_to_lights(world: &[Object], lights: &[Light], pos: Vec3) -> Vec<(Vec3, f64)> {
-        let mut visible = vec![];
-
-        for light in lights.iter() {
-            let ray = Ray::new(pos, pos - light.pos);
-            for object in world.iter() {
-                if ray.trace(object).is_none() {
-                    visible.push((light.pos, light.intensity));
-                }
+    /// Returns a ray leaving the surface at `pos` in `direction`, ignoring hits
+    /// closer than [`Raytracer::epsilon`] to avoid hitting the surface itself.
+    fn surface_ray(&self, pos: Vec3, direction: Vec3) -> Option<Ray> {
+        Ray::new(pos, direction)
+            .ok()
+            .map(|ray| ray.with_min_dist(self.epsilon))
+    }
+
+    /// Returns true if anything in the world blocks the line of sight between `a` and `b`.
+    ///
+    /// Hits closer than [`Raytracer::epsilon`] to `a` or `b` are ignored.
+    pub fn occluded_between(&self, world: &[Object], a: Vec3, b: Vec3) -> bool {
+        self.occluded(&Bvh::new(world), a, b)
+    }
+
+    /// Same as [`Raytracer::occluded_between`] with the hierarchy already built.
+    /// Objects that don't cast shadows are skipped.
+    fn occluded(&self, world: &Bvh, a: Vec3, b: Vec3) -> bool {
+        let Some(ray) = self.surface_ray(a, b - a) else {
+            return false;
+        };
+        let max_dist = (b - ray.origin).length() - self.epsilon.max(FLOAT_EPS);
+
+        let mut occluded = false;
+        world.traverse(&ray, max_dist, |_, object| {
+            occluded = object.casts_shadows
+                && ray
+                    .trace(object)
+                    .is_some_and(|hit| (hit.intersection - ray.origin).length() < max_dist);
+            if occluded {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(max_dist)
             }
+        });
+        occluded
+    }
+
+    /// Returns the fraction of shadow rays from `pos` that reach `light`.
+    pub fn light_visibility(&self, world: &[Object], pos: Vec3, light: &Light) -> f64 {
+        self.visibility(&Bvh::new(world), pos, light)
+    }
+
+    /// Same as [`Raytracer::light_visibility`] with the hierarchy already built.
+    fn visibility(&self, world: &Bvh, pos: Vec3, light: &Light) -> f64 {
+        let targets = light.shadow_targets(pos);
+        let visible = targets
+            .iter()
+            .filter(|&&target| !self.occluded(world, pos, target))
+            .count();
+
+        visible as f64 / targets.len() as f64
+    }
+
+    /// Return the position of any visible lights together with their intensity
+    /// scaled by how much of the light is visible.
+    ///
+    /// Shadow rays start [`Raytracer::set_shadow_bias`] above the surface along `normal`.
+    fn trace_to_lights(
+        &self,
+        world: &Bvh,
+        lights: &[Light],
+        pos: Vec3,
+        normal: Vec3,
+    ) -> Vec<(Vec3, f64)> {
+        let origin = pos + normal * self.shadow_bias;
+
+        if self.light_sampling && lights.len() > 1 {
+            // Seeded by the position so the choice doesn't depend on the thread
+            let stream =
+                pos.x.to_bits() ^ pos.y.to_bits().rotate_left(21) ^ pos.z.to_bits().rotate_left(42);
+            let u = XorShift64::for_stream(self.seed, stream).next_f64();
+            let Some((light, probability)) = light::choose_light(lights, pos, u) else {
+                return Vec::new();
+            };
+            let visibility = self.visibility(world, origin, light);
+            return if visibility > 0.0 {
+                vec![(
+                    light.pos,
+                    light.intensity_at(pos) * visibility / probability,
+                )]
+            } else {
+                Vec::new()
+            };
         }
 
-        visible
+        lights
+            .iter()
+            .filter_map(|light| {
+                let visibility = self.visibility(world, origin, light);
+                (visibility > 0.0).then_some((light.pos, light.intensity_at(pos) * visibility))
+            })
+            .collect()
     }
 
     /// Lambertian reflection is the dot product of the surface normal
     /// and the light direction.
     /// <https://en.wikipedia.org/wiki/Lambertian_reflectance>
     fn lambertian(
-        world: &[Object],
+        &self,
+        world: &Bvh,
         lights: &[Light],
         material: &Material,
         intersection_pos: Vec3,
@@ -98,111 +465,328 @@ impl Raytracer {
             return Color::zero();
         }
 
-        let mut brightness = 0.0;
-        // TODO: Support multiple lights
-        if let Some(&(light_pos, light_intensity)) =
-            Self::trace_to_lights(world, lights, intersection_pos).first()
-        {
-            let contribution = intersection_pos
-                .direction_to(light_pos)
-                .normalize()
-                .dot(intersection_normal)
-                * light_intensity;
-
-            if contribution > 0.0 {
-                brightness += contribution;
-            }
-        }
+        let brightness: f64 = self
+            .trace_to_lights(world, lights, intersection_pos, intersection_normal)
+            .into_iter()
+            .map(|(light_pos, light_intensity)| {
+                intersection_pos
+                    .direction_to(light_pos)
+                    .normalize()
+                    .dot(intersection_normal)
+                    * light_intensity
+            })
+            .filter(|&contribution| contribution > 0.0)
+            .sum();
 
-        material.lambert.scale(brightness.min(1.0))
+        material.lambert * brightness.min(1.0)
     }
 
-    /// Reflect
+    /// Reflect, with `material.specular` as the head on reflectance.
+    /// Grazing hits reflect more, following Schlick's approximation.
     /// <https://en.wikipedia.org/wiki/Specular_reflection>
+    #[allow(clippy::too_many_arguments)]
     fn specular(
-        world: &[Object],
+        &self,
+        world: &Bvh,
         lights: &[Light],
         material: &Material,
         intersection_pos: Vec3,
         intersection_normal: Vec3,
+        view_dir: Vec3,
         depth: u32,
     ) -> Color {
         if material.specular.is_zero() {
             return Color::zero();
         }
 
-        let reflected_dir = intersection_pos.normalize().reflect(intersection_normal);
-        let new_ray = Ray::new(intersection_pos, reflected_dir);
+        let view_dir = view_dir.normalize();
+        let reflected_dir = view_dir.reflect(intersection_normal);
+        let Some(new_ray) = self.surface_ray(intersection_pos, reflected_dir) else {
+            return Color::zero();
+        };
 
-        Self::trace(world, lights, new_ray, depth.saturating_sub(1))
-            .map(|c| c * material.specular)
+        // Schlick per channel, r0 + (1 - r0) * (1 - cos)^5
+        let grazing = schlick(view_dir, intersection_normal, 0.0);
+        let reflectance = material.specular.lerp(Color::new_f(1.0, 1.0, 1.0), grazing);
+
+        self.trace(world, lights, new_ray, depth.saturating_sub(1))
+            .map(|c| c * reflectance)
             .unwrap_or(Color::zero())
     }
 
+    /// Refract through a dielectric surface, blended with the reflection by the
+    /// Schlick approximation of the Fresnel equations.
+    /// Falls back to reflection only on total internal reflection.
+    ///
+    /// The indices of refraction on either side come from the media there,
+    /// so where dielectrics overlap the one with the highest priority applies.
+    /// Surfaces that don't enclose a volume, like triangles, are glass behind
+    /// their front face and air in front of it.
+    /// <https://en.wikipedia.org/wiki/Snell%27s_law>
+    /// <https://en.wikipedia.org/wiki/Schlick%27s_approximation>
+    #[allow(clippy::too_many_arguments)]
+    fn refract(
+        &self,
+        world: &Bvh,
+        lights: &[Light],
+        material: &Material,
+        intersection_pos: Vec3,
+        intersection_normal: Vec3,
+        front_face: bool,
+        view_dir: Vec3,
+        depth: u32,
+    ) -> Color {
+        if !material.is_transmissive() {
+            return Color::zero();
+        }
+
+        // The normal points to the side the ray comes from
+        let offset = intersection_normal * self.epsilon;
+        let medium = |point, inside: bool| {
+            ior_at(world, point).unwrap_or(if inside { material.ior } else { 1.0 })
+        };
+        let eta = medium(intersection_pos + offset, !front_face)
+            / medium(intersection_pos - offset, front_face);
+        let view_dir = view_dir.normalize();
+        let trace_dir = |dir| {
+            self.surface_ray(intersection_pos, dir)
+                .and_then(|ray| self.trace(world, lights, ray, depth.saturating_sub(1)))
+                .unwrap_or(Color::zero())
+        };
+
+        let reflected = trace_dir(view_dir.reflect(intersection_normal));
+        let Some(refracted_dir) = view_dir.refract(intersection_normal, eta) else {
+            return reflected;
+        };
+
+        let reflectance = schlick(view_dir, intersection_normal, normal_reflectance(eta));
+        trace_dir(refracted_dir).lerp(reflected, reflectance)
+    }
+
+    /// Sum of the Blinn-Phong highlights of the visible lights with the given exponent.
+    /// <https://en.wikipedia.org/wiki/Blinn%E2%80%93Phong_reflection_model>
+    fn blinn_phong(
+        &self,
+        world: &Bvh,
+        lights: &[Light],
+        intersection_pos: Vec3,
+        intersection_normal: Vec3,
+        view_dir: Vec3,
+        exponent: f64,
+    ) -> f64 {
+        let to_viewer = -view_dir.normalize();
+
+        self.trace_to_lights(world, lights, intersection_pos, intersection_normal)
+            .into_iter()
+            .map(|(light_pos, light_intensity)| {
+                let to_light = intersection_pos.direction_to(light_pos).normalize();
+                let half = (to_light + to_viewer).normalize();
+                let n_dot_h = half.dot(intersection_normal);
+
+                if n_dot_h > 0.0 && to_light.dot(intersection_normal) > 0.0 {
+                    n_dot_h.powf(exponent) * light_intensity
+                } else {
+                    0.0
+                }
+            })
+            .sum()
+    }
+
+    /// Specular highlight where the lights reflect off the surface,
+    /// tinted by `material.specular`.
+    fn highlight(
+        &self,
+        world: &Bvh,
+        lights: &[Light],
+        material: &Material,
+        intersection_pos: Vec3,
+        intersection_normal: Vec3,
+        view_dir: Vec3,
+    ) -> Color {
+        if !material.has_highlight() {
+            return Color::zero();
+        }
+
+        let brightness = self.blinn_phong(
+            world,
+            lights,
+            intersection_pos,
+            intersection_normal,
+            view_dir,
+            material.shininess,
+        );
+        material.specular * brightness
+    }
+
+    /// Blinn-Phong highlight of a clear coat layer on top of the base shading.
+    /// The coat is clear so the highlight has the color of the light.
+    fn clearcoat(
+        &self,
+        world: &Bvh,
+        lights: &[Light],
+        material: &Material,
+        intersection_pos: Vec3,
+        intersection_normal: Vec3,
+        view_dir: Vec3,
+    ) -> Color {
+        if !material.has_clearcoat() {
+            return Color::zero();
+        }
+
+        // Roughness to Blinn-Phong exponent, from the Beckmann distribution
+        let roughness = material.clearcoat_roughness.max(0.01);
+        let exponent = 2.0 / (roughness * roughness) - 2.0;
+        let brightness = self.blinn_phong(
+            world,
+            lights,
+            intersection_pos,
+            intersection_normal,
+            view_dir,
+            exponent,
+        );
+
+        Color::new_f(1.0, 1.0, 1.0) * (material.clearcoat * brightness)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn shading(
-        world: &[Object],
+        &self,
+        world: &Bvh,
         lights: &[Light],
         material: &Material,
         intersection_pos: Vec3,
         intersection_normal: Vec3,
+        uv: Option<(f64, f64)>,
+        view_dir: Vec3,
         depth: u32,
     ) -> Color {
         debug_assert!(intersection_normal.is_unit());
 
-        let color = material.color
-            * Self::lambertian(
-                world,
-                lights,
-                material,
-                intersection_pos,
-                intersection_normal,
-            );
+        let terms = self.terms_at(depth);
+        let base_color = material.color_at(uv);
+        let mut color = Color::zero();
 
-        let color = color
-            + Self::specular(
-                world,
-                lights,
-                material,
-                intersection_pos,
-                intersection_normal,
-                depth,
-            );
+        if terms.diffuse {
+            color = base_color
+                * self.lambertian(
+                    world,
+                    lights,
+                    material,
+                    intersection_pos,
+                    intersection_normal,
+                );
+        }
+
+        if terms.specular {
+            color = color
+                + self.specular(
+                    world,
+                    lights,
+                    material,
+                    intersection_pos,
+                    intersection_normal,
+                    view_dir,
+                    depth,
+                )
+                + self.highlight(
+                    world,
+                    lights,
+                    material,
+                    intersection_pos,
+                    intersection_normal,
+                    view_dir,
+                )
+                + self.clearcoat(
+                    world,
+                    lights,
+                    material,
+                    intersection_pos,
+                    intersection_normal,
+                    view_dir,
+                );
+        }
 
-        color + material.color * material.ambient
+        if !terms.ambient {
+            return color.non_negative();
+        }
+
+        // Negative light intensities can push channels below zero
+        let color = (color + base_color * material.ambient).non_negative();
+        // Not clamped, so bright emitters keep their intensity in the HDR buffer
+        color + material.emission.non_negative()
     }
 
-    /// Raycast from point with recursion level equal to `depth`.
-    fn trace(world: &[Object], lights: &[Light], ray: Ray, depth: u32) -> Option<Color> {
-        if depth == 0 {
-            return None;
+    /// Returns the shading terms used at recursion level `depth`.
+    /// Only the surfaces hit by primary rays are split into terms,
+    /// reflections and transmitted light are shaded in full.
+    fn terms_at(&self, depth: u32) -> ShadingTerms {
+        if depth == self.recurse_depth {
+            self.shading_terms
+        } else {
+            ShadingTerms::ALL
         }
+    }
 
-        let mut hit: Option<(f64, RayHit, &Object)> = None;
+    /// Trace a primary ray, applying the luminance clamp to the result.
+    fn sample(&self, world: &Bvh, lights: &[Light], ray: Ray) -> Option<Color> {
+        let color = self.trace(world, lights, ray, self.recurse_depth)?;
+        Some(match self.luminance_clamp {
+            Some(max) => color.clamp_luminance(max),
+            None => color,
+        })
+    }
 
-        for object in world.iter() {
-            if let Some(ray_hit) = ray.trace(object) {
-                // Set minimum lambda as min of previous and this
-                let dist = ray_hit.intersection.length_squared();
-                if let Some((prev_dist, _, _)) = hit {
-                    if dist < prev_dist {
-                        hit = Some((dist, ray_hit, object));
-                    }
-                } else {
-                    hit = Some((dist, ray_hit, object));
-                }
-            }
+    /// Raycast from point with recursion level equal to `depth`.
+    fn trace(&self, world: &Bvh, lights: &[Light], ray: Ray, depth: u32) -> Option<Color> {
+        if depth == 0 {
+            return None;
         }
 
-        if let Some((_, ray_hit, object)) = hit {
-            let color = Self::shading(
+        if let Some((_, ray_hit, object)) = world.closest_hit(&ray) {
+            let color = self.shading(
                 world,
                 lights,
                 &object.material,
                 ray_hit.intersection,
                 ray_hit.normal,
+                ray_hit.uv,
+                ray.direction(),
                 depth,
             );
-            Some(color)
+
+            // Transmitted light counts as diffuse when shading terms are split
+            let color = if object.material.is_transmissive() {
+                let refracted = if self.terms_at(depth).diffuse {
+                    self.refract(
+                        world,
+                        lights,
+                        &object.material,
+                        ray_hit.intersection,
+                        ray_hit.normal,
+                        ray_hit.front_face,
+                        ray.direction(),
+                        depth,
+                    )
+                } else {
+                    Color::zero()
+                };
+                color.lerp(refracted, object.material.refraction)
+            } else {
+                color
+            };
+
+            if !object.material.is_translucent() {
+                return Some(color);
+            }
+
+            // Blend with whatever the ray hits when continuing past the surface.
+            // Passing through doesn't bounce, so the ray keeps its depth and
+            // splits its shading terms like the surface does.
+            let behind = self
+                .surface_ray(ray_hit.intersection, ray.direction())
+                .and_then(|ray| self.trace(world, lights, ray, depth))
+                .unwrap_or_else(Color::zero);
+            Some(behind.lerp(color, object.material.opacity))
         } else {
             None
         }
@@ -213,15 +797,134 @@ impl Raytracer {
     /// Returns the colors for each ray.
     /// Ordered by row then column.
     /// Traces using multiple threads.
-    pub fn par_raycast(&self, world: Arc<[Object]>, lights: Arc<[Light]>) -> Vec<Vec<Color>> {
-        let (px, py) = self.camera.pixels();
+    ///
+    /// The colors are linear and not clamped, see [`postprocess::clamp`],
+    /// unless an exposure is set with [`Raytracer::set_exposure`].
+    pub fn par_raycast(&self, world: &[Object], lights: &[Light]) -> Vec<Vec<Color>> {
+        self.par_raycast_timed(world, lights).0
+    }
 
-        let mut image = vec![vec![Color::zero(); px as usize]; py as usize];
+    /// Same as [`Raytracer::par_raycast`], also returning the time spent
+    /// building the bounding volume hierarchies of the world.
+    pub fn par_raycast_timed(
+        &self,
+        world: &[Object],
+        lights: &[Light],
+    ) -> (Vec<Vec<Color>>, Duration) {
+        let (mut image, build) = self.par_raycast_linear(world, lights);
+        self.apply_exposure(&mut image);
+        (image, build)
+    }
+
+    /// Same as [`Raytracer::par_raycast_timed`], without tone mapping.
+    fn par_raycast_linear(
+        &self,
+        world: &[Object],
+        lights: &[Light],
+    ) -> (Vec<Vec<Color>>, Duration) {
+        let (mut images, build) =
+            self.render_instants(world, |world| self.par_raycast_instant(world, lights));
+
+        if images.len() == 1 {
+            return (images.pop().unwrap_or_default(), build);
+        }
+
+        let rows = images[0].len();
+        let image = (0..rows)
+            .map(|row| average_images(images.iter().map(|image| image[row].as_slice())))
+            .collect();
+        (image, build)
+    }
+
+    /// Tone map a finished image if an exposure is set.
+    fn apply_exposure(&self, image: &mut [Vec<Color>]) {
+        if let Some(exposure) = self.exposure {
+            postprocess::tone_map(image, exposure);
+        }
+    }
+
+    /// Render the scene from the left and right eye of [`Camera::stereo_pair`],
+    /// returned in that order.
+    pub fn render_stereo(
+        &self,
+        world: &[Object],
+        lights: &[Light],
+        eye_separation: f64,
+    ) -> (Vec<Vec<Color>>, Vec<Vec<Color>>) {
+        let (left, right) = self.stereo_pair(eye_separation);
+        (
+            left.par_raycast(world, lights),
+            right.par_raycast(world, lights),
+        )
+    }
+
+    /// Returns raytracers seeing through the left and right eye of [`Camera::stereo_pair`].
+    pub fn stereo_pair(&self, eye_separation: f64) -> (Self, Self) {
+        let (left, right) = self.camera.stereo_pair(eye_separation);
+        let with_camera = |camera| Self {
+            camera,
+            ..self.clone()
+        };
+        (with_camera(left), with_camera(right))
+    }
+
+    /// Render the ambient, diffuse and specular terms into separate images, in that order.
+    /// Their sum is the full render, the background is only part of the ambient image.
+    pub fn raycast_terms(&self, world: &[Object], lights: &[Light]) -> [Vec<Vec<Color>>; 3] {
+        self.term_raytracers()
+            .map(|raytracer| raytracer.par_raycast(world, lights))
+    }
+
+    /// Returns raytracers rendering only the ambient, diffuse and specular terms,
+    /// in that order, see [`Raytracer::raycast_terms`].
+    pub fn term_raytracers(&self) -> [Self; 3] {
+        [
+            (ShadingTerms::AMBIENT, self.background),
+            (ShadingTerms::DIFFUSE, Color::zero()),
+            (ShadingTerms::SPECULAR, Color::zero()),
+        ]
+        .map(|(shading_terms, background)| Self {
+            shading_terms,
+            background,
+            ..self.clone()
+        })
+    }
+
+    /// Returns the linear colors for each ray as 32-bit floats, ordered by row then column.
+    /// Nothing is clamped or tone mapped, for processing in other tools.
+    pub fn render_hdr(&self, world: &[Object], lights: &[Light]) -> Vec<Vec<[f32; 3]>> {
+        self.par_raycast_linear(world, lights)
+            .0
+            .into_iter()
+            .map(|row| row.into_iter().map(Color::to_linear_array).collect())
+            .collect()
+    }
 
-        let px = f64::from(px);
-        let py = f64::from(py);
+    /// Render the world at each of the [`Raytracer::shutter_times`] with `render`.
+    /// Also returns the time spent building the hierarchies of the worlds.
+    fn render_instants<T>(
+        &self,
+        world: &[Object],
+        render: impl Fn(&Bvh) -> T,
+    ) -> (Vec<T>, Duration) {
+        let mut build = Duration::ZERO;
+        let renders = self
+            .shutter_worlds(world)
+            .iter()
+            .map(|world| {
+                let start = Instant::now();
+                let world = Bvh::new(world);
+                build += start.elapsed();
+                render(&world)
+            })
+            .collect();
+        (renders, build)
+    }
+
+    fn par_raycast_instant(&self, world: &Bvh, lights: &[Light]) -> Vec<Vec<Color>> {
+        let (px, py) = self.camera.pixels();
 
-        let depth = self.recurse_depth;
+        let mut image = vec![vec![Color::zero(); px as usize]; py as usize];
 
         image[..]
             .par_iter_mut()
@@ -231,39 +934,1425 @@ impl Raytracer {
                     .par_iter_mut()
                     .enumerate()
                     .for_each(|(col, img_cell)| {
-                        let py = py - (row as f64);
-                        let px = (col as f64) - px / 2.0;
+                        *img_cell = self.pixel_color(world, lights, row as u32, col as u32);
+                    });
+            });
+
+        image
+    }
+
+    /// Returns the ray sent through the pixel at `row` and `col` of the image
+    /// returned by [`Raytracer::raycast`], or `None` if the camera is not finite.
+    pub fn pixel_ray(&self, row: u32, col: u32) -> Option<Ray> {
+        self.pixel_ray_sample(row, col, PixelSample::default())
+    }
+
+    /// Same as [`Raytracer::pixel_ray`] through the point of the pixel and lens in `sample`.
+    fn pixel_ray_sample(&self, row: u32, col: u32, sample: PixelSample) -> Option<Ray> {
+        let (px, py) = self.camera.pixels();
+        let x = i64::from(col) - i64::from(px) / 2;
+        let y = i64::from(py) - i64::from(row);
+        let (dx, dy) = sample.offset;
+        let (lens_u, lens_v) = sample.lens;
+        self.camera
+            .ray_from_pixel_lens(x as f64 + dx, y as f64 + dy, lens_u, lens_v)
+    }
+
+    /// Returns where in the pixel and the lens the primary rays go through.
+    ///
+    /// Each pixel has its own random stream seeded by its index, so the samples
+    /// don't depend on which thread renders the pixel or in what order.
+    fn pixel_samples(&self, row: u32, col: u32) -> Vec<PixelSample> {
+        let jitter = self.pixel_jitter || self.samples_per_pixel > 1;
+        let lens = self.camera.aperture() > 0.0;
+        if !jitter && !lens {
+            return vec![PixelSample::default()];
+        }
+
+        let width = u64::from(self.camera.pixels().0);
+        let index = u64::from(row) * width + u64::from(col);
+        let mut rng = XorShift64::for_stream(self.seed, index);
+        let mut pair = |enabled: bool, center: f64| {
+            if enabled {
+                (rng.next_f64() - center, rng.next_f64() - center)
+            } else {
+                (0.0, 0.0)
+            }
+        };
+        (0..self.samples_per_pixel)
+            .map(|_| PixelSample {
+                offset: pair(jitter, 0.5),
+                lens: pair(lens, 0.0),
+            })
+            .collect()
+    }
+
+    /// Average the colors of the [`Raytracer::pixel_samples`] of a pixel.
+    /// Rays that miss count as the background color.
+    fn pixel_color(&self, world: &Bvh, lights: &[Light], row: u32, col: u32) -> Color {
+        let samples: Vec<_> = self
+            .pixel_samples(row, col)
+            .into_iter()
+            .map(|sample| {
+                self.pixel_ray_sample(row, col, sample)
+                    .and_then(|ray| self.sample(world, lights, ray))
+                    .unwrap_or(self.background)
+            })
+            .collect();
+        Color::average(&samples)
+    }
+
+    /// Returns whether the primary ray of each pixel hits any object.
+    /// Ordered by row then column, like [`Raytracer::raycast`].
+    pub fn hit_mask(&self, world: &[Object]) -> Vec<Vec<bool>> {
+        let (px, py) = self.camera.pixels();
+        let world = Bvh::new(world);
+
+        (0..py)
+            .into_par_iter()
+            .map(|row| {
+                (0..px)
+                    .map(|col| {
+                        self.pixel_ray(row, col)
+                            .is_some_and(|ray| world.closest_hit(&ray).is_some())
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Trace a single pixel and report what the primary ray hit.
+    ///
+    /// `row` and `col` index into the image returned by [`Raytracer::raycast`].
+    pub fn debug_pixel(
+        &self,
+        world: &[Object],
+        lights: &[Light],
+        row: u32,
+        col: u32,
+    ) -> Option<PixelDebug> {
+        let ray = self.pixel_ray(row, col)?;
+        let world = Bvh::new(world);
+        let (object_index, ray_hit, object) = world.closest_hit(&ray)?;
 
-                        let ray = self.camera.ray_from_pixel(px, py);
-                        if let Some(hit) = Self::trace(world.as_ref(), lights.as_ref(), ray, depth)
+        Some(PixelDebug {
+            object_index,
+            object_name: object.name.clone(),
+            intersection: ray_hit.intersection,
+            normal: ray_hit.normal,
+            color: self
+                .trace(&world, lights, ray, self.recurse_depth)
+                .map(|c| c.clamped())
+                .unwrap_or(Color::zero()),
+        })
+    }
+
+    /// Count how many primitives the primary rays test when looking for their closest hit.
+    pub fn traversal_stats(&self, world: &[Object]) -> TraversalStats {
+        let (px, py) = self.camera.pixels();
+        let bvh = Bvh::new(world);
+
+        let (rays, primitives_tested) = (0..py)
+            .into_par_iter()
+            .map(|row| {
+                let (mut rays, mut tested) = (0, 0);
+                for ray in (0..px).filter_map(|col| self.pixel_ray(row, col)) {
+                    rays += 1;
+                    let mut closest = f64::INFINITY;
+                    bvh.traverse(&ray, closest, |_, object| {
+                        if let Some(hit) = object.primitive.counted_intersection(&ray, &mut tested)
                         {
-                            *img_cell = hit;
+                            closest = closest.min((hit.pos - ray.origin).length());
                         }
+                        ControlFlow::Continue(closest)
                     });
-            });
+                }
+                (rays, tested)
+            })
+            .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
 
-        image
+        TraversalStats {
+            objects: world.len(),
+            rays,
+            primitives_tested,
+            bvh: bvh.stats(),
+        }
+    }
+
+    /// Returns the colors for each ray in a single buffer together with
+    /// the width and height of the image.
+    /// The pixel at `x`, `y` is found at index `y * width + x`.
+    ///
+    /// The colors are linear and not clamped, see [`postprocess::clamp`].
+    /// They are never tone mapped, so buffers of several renders can be averaged.
+    pub fn render_to_buffer(
+        &self,
+        world: &[Object],
+        lights: &[Light],
+    ) -> (Vec<Color>, usize, usize) {
+        let (px, py) = self.camera.pixels();
+        let (buffer, _) = self.render_buffer(world, lights);
+        (buffer, px as usize, py as usize)
+    }
+
+    /// Same as [`Raytracer::render_to_buffer`], returning the time spent building
+    /// the bounding volume hierarchies of the world instead of the image size.
+    fn render_buffer(&self, world: &[Object], lights: &[Light]) -> (Vec<Color>, Duration) {
+        let (mut buffers, build) =
+            self.render_instants(world, |world| self.render_instant(world, lights));
+
+        let buffer = if buffers.len() == 1 {
+            buffers.pop().unwrap_or_default()
+        } else {
+            average_images(buffers.iter().map(Vec::as_slice))
+        };
+
+        (buffer, build)
+    }
+
+    /// Render every pixel with the objects frozen in place.
+    fn render_instant(&self, world: &Bvh, lights: &[Light]) -> Vec<Color> {
+        let (px, py) = self.camera.pixels();
+
+        let mut buffer = Vec::with_capacity(px as usize * py as usize);
+        for row in 0..py {
+            for col in 0..px {
+                buffer.push(self.pixel_color(world, lights, row, col));
+            }
+        }
+
+        buffer
+    }
+
+    /// Returns the times within the shutter interval at which the scene is rendered.
+    ///
+    /// The shutter is split into equal strata with one randomly placed
+    /// sample each, the same seed always gives the same times.
+    pub fn shutter_times(&self) -> Vec<f64> {
+        let shutter = self.camera.shutter();
+        if shutter <= 0.0 || self.time_samples <= 1 {
+            return vec![0.0];
+        }
+
+        let mut rng = XorShift64::new(self.seed);
+        let n = f64::from(self.time_samples);
+        (0..self.time_samples)
+            .map(|i| (f64::from(i) + rng.next_f64()) / n * shutter)
+            .collect()
+    }
+
+    /// Returns the world at each of the [`Raytracer::shutter_times`].
+    fn shutter_worlds<'a>(&self, world: &'a [Object]) -> Vec<Cow<'a, [Object]>> {
+        match self.shutter_times().as_slice() {
+            [time] if *time == 0.0 => vec![Cow::Borrowed(world)],
+            times => times
+                .iter()
+                .map(|&time| Cow::Owned(world.iter().map(|o| o.at_time(time)).collect()))
+                .collect(),
+        }
     }
 
     /// Returns the colors for each ray.
     /// Ordered by row then column.
+    ///
+    /// The colors are linear and not clamped, see [`postprocess::clamp`],
+    /// unless an exposure is set with [`Raytracer::set_exposure`].
     pub fn raycast(&self, world: &[Object], lights: &[Light]) -> Vec<Vec<Color>> {
-        let (px, py) = self.camera.pixels();
+        self.raycast_timed(world, lights).0
+    }
 
-        let mut image = vec![vec![Color::zero(); px as usize]; py as usize];
+    /// Same as [`Raytracer::raycast`], also returning the time spent
+    /// building the bounding volume hierarchies of the world.
+    pub fn raycast_timed(&self, world: &[Object], lights: &[Light]) -> (Vec<Vec<Color>>, Duration) {
+        let width = self.camera.pixels().0 as usize;
+        let (buffer, build) = self.render_buffer(world, lights);
 
-        let px = i64::from(px);
-        let py = i64::from(py);
+        let mut image: Vec<_> = buffer.chunks(width.max(1)).map(<[Color]>::to_vec).collect();
+        self.apply_exposure(&mut image);
+        (image, build)
+    }
+}
 
-        for (row, y) in (-py..0).enumerate() {
-            for (col, x) in (-px / 2..px / 2).enumerate() {
-                let ray = self.camera.ray_from_pixel(x as f64, -y as f64);
-                if let Some(hit) = Self::trace(world, lights, ray, self.recurse_depth) {
-                    image[row][col] = hit;
-                }
+/// Returns the index of refraction of the medium at `point`, that of air inside
+/// objects that aren't refractive, or `None` if no object contains the point.
+fn ior_at(world: &Bvh, point: Vec3) -> Option<f64> {
+    object::medium_at(world.objects(), point).map(|object| {
+        if object.material.is_transmissive() {
+            object.material.ior
+        } else {
+            1.0
+        }
+    })
+}
+
+/// Returns the per pixel average of equally sized images.
+fn average_images<'a>(images: impl Iterator<Item = &'a [Color]> + Clone) -> Vec<Color> {
+    let len = images.clone().next().map_or(0, <[Color]>::len);
+    (0..len)
+        .map(|i| Color::average(&images.clone().map(|image| image[i]).collect::<Vec<_>>()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitive::{Cuboid, Mesh, Plane, Sphere, Triangle};
+
+    fn matte(color: Color) -> Material {
+        Material {
+            color,
+            lambert: Color::new_f(1.0, 1.0, 1.0),
+            ambient: Color::new_f(0.1, 0.1, 0.1),
+            ..Default::default()
+        }
+    }
+
+    /// Material that shows `color` as is, regardless of lights.
+    fn unlit(color: Color) -> Material {
+        Material {
+            lambert: Color::zero(),
+            ambient: Color::new_f(1.0, 1.0, 1.0),
+            ..matte(color)
+        }
+    }
+
+    fn camera(width: u32, height: u32) -> Camera {
+        Camera::new(
+            width,
+            height,
+            Vec3::zero(),
+            Vec3::new(0.0, 0.0, 1.0),
+            90.0,
+            0.0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn debug_pixel_reports_object_name() {
+        let raytracer = Raytracer::new(camera(9, 9), 2);
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(),
+            matte(Color::new(255, 0, 0)),
+        )
+        .with_name("red_ball")];
+
+        let debug = raytracer.debug_pixel(&world, &[], 4, 4).unwrap();
+        assert_eq!(debug.object_index, 0);
+        assert_eq!(debug.object_name.as_deref(), Some("red_ball"));
+        assert!(debug.to_string().contains("red_ball"));
+
+        assert!(raytracer.debug_pixel(&world, &[], 0, 0).is_none());
+    }
+
+    #[test]
+    fn occluded_between_points() {
+        let raytracer = Raytracer::new(camera(1, 1), 1);
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(),
+            matte(Color::new(255, 0, 0)),
+        )];
+
+        let a = Vec3::zero();
+        assert!(raytracer.occluded_between(&world, a, Vec3::new(0.0, 0.0, 10.0)));
+        assert!(!raytracer.occluded_between(&world, a, Vec3::new(5.0, 0.0, 5.0)));
+        // The sphere lies beyond the target point
+        assert!(!raytracer.occluded_between(&world, a, Vec3::new(0.0, 0.0, 3.0)));
+
+        let world = [world[0].clone().with_casts_shadows(false)];
+        assert!(!raytracer.occluded_between(&world, a, Vec3::new(0.0, 0.0, 10.0)));
+    }
+
+    #[test]
+    fn occluder_shadows_rear_sphere() {
+        let raytracer = Raytracer::new(camera(1, 1), 1);
+        let material = matte(Color::new(255, 255, 255));
+        let lights = [Light::new(Vec3::new(0.0, 10.0, 5.0), 1.0)];
+        let world = [
+            // Unrelated to the shadow ray, must not make the light visible
+            Object::new(Sphere::new(Vec3::new(10.0, 0.0, 0.0), 1.0).into(), material),
+            Object::new(Sphere::new(Vec3::new(0.0, 5.0, 5.0), 1.0).into(), material),
+            Object::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(), material),
+        ];
+
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let top_of_front = Vec3::new(0.0, 6.0, 5.0);
+        assert_eq!(
+            raytracer
+                .trace_to_lights(&Bvh::new(&world), &lights, top_of_front, up)
+                .len(),
+            1
+        );
+
+        let top_of_rear = Vec3::new(0.0, 1.0, 5.0);
+        assert!(raytracer
+            .trace_to_lights(&Bvh::new(&world), &lights, top_of_rear, up)
+            .is_empty());
+        let lit = raytracer.lambertian(&Bvh::new(&world), &lights, &material, top_of_rear, up);
+        assert!(lit.is_zero());
+    }
+
+    #[test]
+    fn lambertian_sums_lights() {
+        let raytracer = Raytracer::new(camera(16, 16), 1);
+        let material = matte(Color::new(255, 255, 255));
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(),
+            material,
+        )];
+        let left = Light::new(Vec3::new(-5.0, 0.0, 2.0), 0.4);
+        let right = Light::new(Vec3::new(5.0, 0.0, 2.0), 0.4);
+
+        let front = Vec3::new(0.0, 0.0, 4.0);
+        let normal = Vec3::new(0.0, 0.0, -1.0);
+        let brightness = |lights: &[Light]| {
+            raytracer
+                .lambertian(&Bvh::new(&world), lights, &material, front, normal)
+                .luminance()
+        };
+        let single = brightness(&[left]);
+        assert!(single > 0.0);
+        assert!((brightness(&[left, right]) - 2.0 * single).abs() < 1e-9);
+
+        // With both lights the dark side is lit as brightly as the lit side
+        let side_brightness = |lights: &[Light]| {
+            let image = raytracer.raycast(&world, lights);
+            let row = &image[8];
+            (row[5].luminance(), row[10].luminance())
+        };
+        let (lit, dark) = side_brightness(&[left]);
+        assert!(lit > dark);
+        let (left_side, right_side) = side_brightness(&[left, right]);
+        assert!(right_side > dark);
+        assert!(left_side >= lit);
+        assert!((left_side - right_side).abs() < 1e-9);
+    }
+
+    #[test]
+    fn objects_beyond_light_cast_no_shadow() {
+        let raytracer = Raytracer::new(camera(1, 1), 1);
+        let material = matte(Color::new(255, 255, 255));
+        let lights = [Light::new(Vec3::new(0.0, 5.0, 5.0), 1.0)];
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let pos = Vec3::new(0.0, 0.0, 5.0);
+
+        // On the line from the surface through the light, but farther away
+        let beyond = [Object::new(
+            Sphere::new(Vec3::new(0.0, 10.0, 5.0), 1.0).into(),
+            material,
+        )];
+        assert_eq!(
+            raytracer
+                .trace_to_lights(&Bvh::new(&beyond), &lights, pos, up)
+                .len(),
+            1
+        );
+
+        // On the opposite side of the surface from the light
+        let behind = [Object::new(
+            Sphere::new(Vec3::new(0.0, -5.0, 5.0), 1.0).into(),
+            material,
+        )];
+        assert_eq!(
+            raytracer
+                .trace_to_lights(&Bvh::new(&behind), &lights, pos, up)
+                .len(),
+            1
+        );
+
+        let between = [Object::new(
+            Sphere::new(Vec3::new(0.0, 2.5, 5.0), 1.0).into(),
+            material,
+        )];
+        assert!(raytracer
+            .trace_to_lights(&Bvh::new(&between), &lights, pos, up)
+            .is_empty());
+    }
+
+    #[test]
+    fn shadow_bias_removes_acne() {
+        let mut raytracer = Raytracer::new(camera(24, 24), 1);
+        raytracer.camera =
+            Camera::new(24, 24, Vec3::zero(), Vec3::new(0.0, -1.0, 1.0), 90.0, 0.0).unwrap();
+        // Only the shadow bias keeps shadow rays off the surface
+        raytracer.set_epsilon(0.0);
+
+        // Planes already have a tolerance that grows with their distance,
+        // a large sphere shows the acne of curved surfaces
+        let ground = |casts_shadows| {
+            [Object::new(
+                Sphere::new(Vec3::new(0.0, -1001.0, 0.0), 1000.0).into(),
+                matte(Color::new(255, 255, 255)),
+            )
+            .with_casts_shadows(casts_shadows)]
+        };
+        let lights = [Light::new(Vec3::new(0.0, 20.0, 5.0), 1.0)];
+        let unshadowed = raytracer.raycast(&ground(false), &lights);
+
+        assert_eq!(raytracer.raycast(&ground(true), &lights), unshadowed);
+
+        raytracer.set_shadow_bias(0.0);
+        assert_ne!(raytracer.raycast(&ground(true), &lights), unshadowed);
+    }
+
+    #[test]
+    fn shadow_candidates_match_linear_scan() {
+        let raytracer = Raytracer::new(camera(1, 1), 1);
+        let material = matte(Color::new(255, 0, 0));
+        let mut world: Vec<Object> = (0..100)
+            .map(|i| {
+                let center = Vec3::new((i % 10) as f64 - 4.5, 0.0, (i / 10) as f64 + 2.0);
+                Object::new(Sphere::new(center, 0.3).into(), material)
+            })
+            .collect();
+        world.push(Object::new(
+            Triangle::new(
+                Vec3::new(-1.0, 2.0, 4.0),
+                Vec3::new(1.0, 2.0, 4.0),
+                Vec3::new(0.0, 2.0, 6.0),
+            )
+            .into(),
+            material,
+        ));
+
+        let light = Vec3::new(0.0, 5.0, 5.0);
+        let mut occluded = 0;
+        for i in 0..50 {
+            let from = Vec3::new((i % 10) as f64 - 4.5, -1.0 + (i % 3) as f64, (i / 5) as f64);
+
+            let ray = raytracer.surface_ray(from, light - from).unwrap();
+            let max_dist = (light - ray.origin).length() - raytracer.epsilon();
+            let linear = world.iter().any(|object| {
+                ray.trace(object)
+                    .is_some_and(|hit| (hit.intersection - ray.origin).length() < max_dist)
+            });
+
+            assert_eq!(raytracer.occluded_between(&world, from, light), linear);
+            occluded += usize::from(linear);
+
+            let mut candidates = 0;
+            Bvh::new(&world).traverse(&ray, max_dist, |_, _| {
+                candidates += 1;
+                ControlFlow::Continue(max_dist)
+            });
+            assert!(candidates < world.len() / 4, "{candidates} candidates");
+        }
+        assert!(0 < occluded && occluded < 50, "{occluded} occluded");
+    }
+
+    #[test]
+    fn soft_shadow_visibility() {
+        let raytracer = Raytracer::new(camera(1, 1), 1);
+        // Blocks the straight line from the origin to the light
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.5, 0.0, 5.0), 1.0).into(),
+            matte(Color::new(255, 0, 0)),
+        )];
+
+        let mut light = Light::new(Vec3::new(0.0, 0.0, 10.0), 1.0);
+        assert_eq!(
+            raytracer.light_visibility(&world, Vec3::zero(), &light),
+            0.0
+        );
+
+        light.shadow_radius = 3.0;
+        light.shadow_samples = 32;
+        let visibility = raytracer.light_visibility(&world, Vec3::zero(), &light);
+        assert!(visibility > 0.0 && visibility < 1.0, "{visibility}");
+
+        // Same targets every time
+        assert_eq!(
+            visibility,
+            raytracer.light_visibility(&world, Vec3::zero(), &light)
+        );
+        assert_eq!(light.shadow_targets(Vec3::zero()).len(), 32);
+    }
+
+    #[test]
+    fn clearcoat_adds_highlight() {
+        let raytracer = Raytracer::new(camera(9, 9), 2);
+        let lights = [Light::new(Vec3::zero(), 1.0).with_attenuation(Light::NO_ATTENUATION)];
+
+        let base = Material {
+            specular: Color::new_f(0.5, 0.5, 0.5),
+            ..matte(Color::new_f(0.2, 0.2, 0.2))
+        };
+        let coated = Material {
+            clearcoat: 0.5,
+            ..base
+        };
+
+        // Shade points on a sphere around (0, 0, 5), the world is left empty
+        // so the sphere does not shadow itself
+        let shade = |material: &Material, pos: Vec3| {
+            let normal = (pos - Vec3::new(0.0, 0.0, 5.0)).normalize();
+            let view_dir = pos.normalize();
+            raytracer.shading(
+                &Bvh::new(&[]),
+                &lights,
+                material,
+                pos,
+                normal,
+                None,
+                view_dir,
+                2,
+            )
+        };
+
+        // Facing the light and the camera the coat adds a white highlight
+        let front = Vec3::new(0.0, 0.0, 4.0);
+        let highlight = shade(&coated, front);
+        let [r, g, b]: [u8; 3] = highlight.into();
+        let [br, bg, bb]: [u8; 3] = shade(&base, front).into();
+        assert_eq!((r - br, g - bg, b - bb), (128, 128, 128));
+
+        // The highlight is small and does not show up on the side of the sphere
+        let side = Vec3::new(0.0, 0.5f64.sqrt(), 5.0 - 0.5f64.sqrt());
+        let [r, _, _]: [u8; 3] = shade(&coated, side).into();
+        let [br, _, _]: [u8; 3] = shade(&base, side).into();
+        assert_eq!(r, br);
+    }
+
+    #[test]
+    fn blinn_phong_highlight_faces_light() {
+        let raytracer = Raytracer::new(camera(8, 8), 2);
+        // The light sits at the camera, so it reflects back where the sphere faces the camera
+        let lights = [Light::new(Vec3::zero(), 1.0).with_attenuation(Light::NO_ATTENUATION)];
+        let glossy = Material {
+            specular: Color::new_f(1.0, 1.0, 1.0),
+            lambert: Color::zero(),
+            ambient: Color::zero(),
+            shininess: 50.0,
+            ..matte(Color::new_f(1.0, 1.0, 1.0))
+        };
+
+        // Shade points on a sphere around (0, 0, 5), the world is left empty
+        // so the mirror reflection sees nothing
+        let shade = |material: &Material, pos: Vec3| {
+            let normal = (pos - Vec3::new(0.0, 0.0, 5.0)).normalize();
+            raytracer
+                .shading(&Bvh::new(&[]), &lights, material, pos, normal, None, pos, 2)
+                .luminance()
+        };
+        let on_sphere = |angle: f64| {
+            let angle = angle.to_radians();
+            Vec3::new(angle.sin(), 0.0, 5.0 - angle.cos())
+        };
+
+        let peak = shade(&glossy, on_sphere(0.0));
+        assert!((peak - 1.0).abs() < 1e-9, "{peak}");
+        let mut previous = peak;
+        for angle in [5.0, 10.0, 20.0, 40.0] {
+            let brightness = shade(&glossy, on_sphere(angle));
+            assert!(brightness < previous, "{angle}: {brightness}");
+            previous = brightness;
+        }
+
+        let dull = Material {
+            shininess: 0.0,
+            ..glossy
+        };
+        assert_eq!(shade(&dull, on_sphere(0.0)), 0.0);
+    }
+
+    #[test]
+    fn half_opaque_plane_tints_toward_sphere() {
+        let white = Color::new_f(1.0, 1.0, 1.0);
+        let red = Color::new_f(1.0, 0.0, 0.0);
+
+        let render = |opacity| {
+            let world = [
+                Object::new(
+                    Plane::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0)).into(),
+                    Material {
+                        opacity,
+                        ..unlit(white)
+                    },
+                ),
+                Object::new(
+                    Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(),
+                    unlit(red),
+                ),
+            ];
+            Raytracer::new(camera(9, 9), 2).raycast(&world, &[])[4][4]
+        };
+
+        assert_eq!(render(1.0), white);
+        assert_eq!(render(0.5), Color::new_f(1.0, 0.5, 0.5));
+        assert_eq!(render(0.0), red);
+    }
+
+    #[test]
+    fn glass_sphere_shows_plane_behind() {
+        let green = Color::new_f(0.0, 1.0, 0.0);
+
+        let render = |refraction| {
+            let glass = Material {
+                refraction,
+                ior: 1.5,
+                ..unlit(Color::zero())
+            };
+            let world = [
+                Object::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(), glass),
+                Object::new(
+                    Plane::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).into(),
+                    unlit(green),
+                ),
+            ];
+            // Entering and leaving the sphere takes two bounces before reaching the plane
+            Raytracer::new(camera(9, 9), 4).raycast(&world, &[])[4][4]
+        };
+
+        assert_eq!(render(0.0), Color::zero());
+
+        // Head on, Fresnel reflects 4% at each of the two surfaces
+        let [r, g, b] = render(1.0).to_linear_array();
+        assert!((f64::from(g) - 0.96 * 0.96).abs() < 0.01, "{g}");
+        assert_eq!((r, b), (0.0, 0.0));
+    }
+
+    #[test]
+    fn translucent_surface_at_depth_one() {
+        let half_red = Material {
+            opacity: 0.5,
+            ..unlit(Color::new_f(1.0, 0.0, 0.0))
+        };
+        let facing = |z| Plane::new(Vec3::new(0.0, 0.0, z), Vec3::new(0.0, 0.0, -1.0)).into();
+        let world = [
+            Object::new(facing(3.0), half_red),
+            Object::new(facing(10.0), unlit(Color::new_f(0.0, 1.0, 0.0))),
+        ];
+
+        // Seeing through the surface doesn't use up the single bounce
+        let [r, g, b] =
+            Raytracer::new(camera(9, 9), 1).raycast(&world, &[])[4][4].to_linear_array();
+        assert!((r - 0.5).abs() < 1e-6 && (g - 0.5).abs() < 1e-6, "{r} {g}");
+        assert_eq!(b, 0.0);
+    }
+
+    #[test]
+    fn overlapping_glass_spheres() {
+        let glass = Material {
+            refraction: 1.0,
+            ior: 1.5,
+            ..unlit(Color::zero())
+        };
+        let green = Color::new_f(0.0, 1.0, 0.0);
+
+        let render = |priority| {
+            let world = [
+                Object::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(), glass),
+                Object::new(Sphere::new(Vec3::new(0.0, 0.0, 5.8), 1.0).into(), glass)
+                    .with_priority(priority),
+                Object::new(
+                    Plane::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).into(),
+                    unlit(green),
+                ),
+            ];
+            // Four surfaces before reaching the plane
+            Raytracer::new(camera(9, 9), 6).raycast(&world, &[])[4][4]
+        };
+
+        // The surfaces inside the other sphere are glass on both sides and don't
+        // reflect, so only the outer two take 4% each, like a single piece of glass
+        for priority in [0, 1] {
+            let [r, g, b] = render(priority).to_linear_array();
+            assert!((f64::from(g) - 0.96 * 0.96).abs() < 0.01, "{g}");
+            assert_eq!((r, b), (0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn glass_mesh_refracts_like_cuboid() {
+        let checker = Material {
+            texture: Some(texture::Texture::Checker {
+                a: Color::new_f(1.0, 0.0, 0.0),
+                b: Color::new_f(0.0, 1.0, 0.0),
+                scale: 0.5,
+            }),
+            ..unlit(Color::zero())
+        };
+        let glass = |ior| Material {
+            refraction: 1.0,
+            ior,
+            ..unlit(Color::zero())
+        };
+        let cuboid = Cuboid::new(Vec3::new(-1.1, -0.9, 3.0), Vec3::new(0.8, 1.2, 5.0));
+
+        let render = |primitives: Vec<Primitive>, ior| {
+            let mut world: Vec<Object> = primitives
+                .into_iter()
+                .map(|primitive| Object::new(primitive, glass(ior)))
+                .collect();
+            world.push(Object::new(
+                Plane::new(Vec3::new(0.0, 0.0, 8.0), Vec3::new(0.0, 0.0, -1.0)).into(),
+                checker,
+            ));
+            Raytracer::new(camera(16, 16), 4).raycast(&world, &[])
+        };
+        let close = |a: &[Vec<Color>], b: &[Vec<Color>]| {
+            a.iter().flatten().zip(b.iter().flatten()).all(|(a, b)| {
+                let diff = (*a - *b).to_linear_array();
+                diff.iter().all(|c| c.abs() < 1e-6)
+            })
+        };
+
+        let reference = render(vec![cuboid.into()], 1.5);
+        assert!(!close(&reference, &render(vec![cuboid.into()], 1.0)));
+
+        // A closed mesh is a volume of its own
+        let mesh = render(vec![Mesh::new(cuboid.triangles()).into()], 1.5);
+        assert!(close(&mesh, &reference));
+
+        // Loose triangles don't enclose anything, the glass is behind their front faces
+        let triangles = cuboid
+            .triangles()
+            .into_iter()
+            .map(Primitive::from)
+            .collect();
+        assert!(close(&render(triangles, 1.5), &reference));
+    }
+
+    #[test]
+    fn fisheye_barrel_distortion() {
+        let red = Color::new_f(1.0, 0.0, 0.0);
+        let green = Color::new_f(0.0, 1.0, 0.0);
+
+        let world: Vec<Object> = [-6.0, 0.0, 6.0]
+            .iter()
+            .flat_map(|&x| [-6.0, 0.0, 6.0].map(|y| Vec3::new(x, y, 10.0)))
+            .map(|center| {
+                let color = match (center.x, center.y) {
+                    (x, y) if x > 0.0 && y > 0.0 => red,
+                    (x, y) if x == 0.0 && y == 0.0 => green,
+                    _ => Color::new_f(1.0, 1.0, 1.0),
+                };
+                Object::new(Sphere::new(center, 1.0).into(), unlit(color))
+            })
+            .collect();
+
+        // Distance in pixels from the center sphere to the corner sphere
+        let corner_distance = |camera: &Camera| {
+            let image = Raytracer::new(camera.clone(), 1).raycast(&world, &[]);
+            let centroid = |color| {
+                let pixels: Vec<(f64, f64)> = image
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &c)| (x, y, c)))
+                    .filter(|&(_, _, c)| c == color)
+                    .map(|(x, y, _)| (x as f64, y as f64))
+                    .collect();
+                assert!(!pixels.is_empty());
+
+                let n = pixels.len() as f64;
+                let x = pixels.iter().map(|p| p.0).sum::<f64>() / n;
+                let y = pixels.iter().map(|p| p.1).sum::<f64>() / n;
+                Vec3::new(x, y, 0.0)
+            };
+            (centroid(red) - centroid(green)).length()
+        };
+
+        let pinhole =
+            Camera::new(33, 33, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 170.0, 0.0).unwrap();
+
+        // Same magnification at the center of the image as the pinhole camera
+        let center = pinhole.ray_from_pixel(-0.5, 16.0).unwrap().direction();
+        let edge = pinhole.ray_from_pixel(16.0, 16.0).unwrap().direction();
+        let mut fisheye = pinhole.clone();
+        fisheye.set_projection(Projection::Fisheye {
+            fov: 2.0 * center.angle_between(edge).tan(),
+        });
+
+        assert!(corner_distance(&fisheye) + 1.0 < corner_distance(&pinhole));
+    }
+
+    #[test]
+    fn lat_long_grid_on_sphere() {
+        let line = Color::new_f(1.0, 1.0, 1.0);
+        let bg = Color::new_f(0.0, 0.0, 0.5);
+        let material = Material {
+            texture: Some(texture::Texture::LatLongGrid {
+                line_color: line,
+                bg_color: bg,
+                divisions: 4,
+            }),
+            ..unlit(Color::zero())
+        };
+        let center = Vec3::new(0.0, 0.0, 5.0);
+        let world = [Object::new(Sphere::new(center, 1.0).into(), material)];
+        let raytracer = Raytracer::new(camera(9, 9), 1);
+
+        // Trace from the origin to the point on the sphere at (u, v)
+        let color_at = |u: f64, v: f64| {
+            let lon = (u - 0.5) * 2.0 * std::f64::consts::PI;
+            let lat = (v - 0.5) * std::f64::consts::PI;
+            let normal = Vec3::new(lat.cos() * lon.cos(), lat.sin(), lat.cos() * lon.sin());
+            let ray = Ray::new(Vec3::zero(), center + normal).unwrap();
+            raytracer.trace(&Bvh::new(&world), &[], ray, 1).unwrap()
+        };
+
+        assert_eq!(color_at(0.26, 0.4), line);
+        assert_eq!(color_at(0.3, 0.51), line);
+        assert_eq!(color_at(0.3125, 0.375), bg);
+    }
+
+    #[test]
+    fn motion_blur_widens_silhouette() {
+        let material = unlit(Color::new_f(1.0, 1.0, 1.0));
+        let world = [
+            Object::new(Sphere::new(Vec3::new(-1.0, 0.0, 5.0), 1.0).into(), material)
+                .with_velocity(Vec3::new(2.0, 0.0, 0.0)),
+        ];
+
+        // Width and height of the box around all pixels that are not black
+        let extent = |image: &[Vec<Color>]| {
+            let lit: Vec<(usize, usize)> = (0..image.len())
+                .flat_map(|row| (0..image[row].len()).map(move |col| (row, col)))
+                .filter(|&(row, col)| !image[row][col].is_zero())
+                .collect();
+            let span = |values: Vec<usize>| {
+                values.iter().max().unwrap() - values.iter().min().unwrap() + 1
+            };
+            (
+                span(lit.iter().map(|&(_, col)| col).collect()),
+                span(lit.iter().map(|&(row, _)| row).collect()),
+            )
+        };
+
+        let mut raytracer = Raytracer::new(camera(48, 24), 1);
+        let still = raytracer.raycast(&world, &[]);
+
+        raytracer.set_shutter(1.0);
+        raytracer.set_time_samples(8);
+        raytracer.set_seed(42);
+        let blurred = raytracer.raycast(&world, &[]);
+
+        let (still_width, still_height) = extent(&still);
+        let (blurred_width, blurred_height) = extent(&blurred);
+        assert!(
+            blurred_width > still_width,
+            "{blurred_width} <= {still_width}"
+        );
+        assert_eq!(blurred_height, still_height);
+
+        // Deterministic for a fixed seed and the same in parallel
+        assert_eq!(blurred, raytracer.raycast(&world, &[]));
+        assert_eq!(blurred, raytracer.par_raycast(&world, &[]));
+    }
+
+    #[test]
+    fn epsilon_for_large_coordinates() {
+        // The same scene near the origin and far away from it
+        let render = |offset: Vec3, epsilon: Option<f64>| {
+            let camera = Camera::new(32, 32, offset, Vec3::new(0.0, -0.5, 1.0), 90.0, 0.0).unwrap();
+            let mut raytracer = Raytracer::new(camera, 1);
+            // Leave it to the epsilon to avoid self intersections
+            raytracer.set_shadow_bias(0.0);
+            if let Some(epsilon) = epsilon {
+                raytracer.set_epsilon(epsilon);
             }
+
+            let world = [Object::new(
+                Sphere::new(offset + Vec3::new(0.0, -20.0, 40.0), 20.0).into(),
+                matte(Color::new_f(1.0, 1.0, 1.0)),
+            )];
+            let light = Light::new(offset + Vec3::new(10.0, 30.0, 20.0), 1.0);
+            let lights = [light.with_attenuation(Light::NO_ATTENUATION)];
+            raytracer.raycast(&world, &lights)
+        };
+
+        let artifacts = |image: &[Vec<Color>], reference: &[Vec<Color>]| {
+            image
+                .iter()
+                .flatten()
+                .zip(reference.iter().flatten())
+                .filter(|(a, b)| (a.luminance() - b.luminance()).abs() > 0.05)
+                .count()
+        };
+
+        let reference = render(Vec3::zero(), None);
+        assert!(reference.iter().flatten().any(|c| c.luminance() > 0.5));
+        let far = Vec3::new(3e10, 1e10, -2e10);
+        assert!(artifacts(&render(far, None), &reference) > 0);
+        assert_eq!(artifacts(&render(far, Some(1e-2)), &reference), 0);
+    }
+
+    #[test]
+    fn radiant_power_inverse_square() {
+        let raytracer = Raytracer::new(camera(9, 9), 1);
+        let material = Material {
+            ambient: Color::zero(),
+            ..matte(Color::new_f(1.0, 1.0, 1.0))
+        };
+        let normal = Vec3::new(0.0, 0.0, -1.0);
+
+        let brightness = |distance: f64, light: fn(Vec3) -> Light| {
+            let lights = [light(Vec3::new(0.0, 0.0, -distance))];
+            let view_dir = Vec3::new(0.0, 0.0, 1.0);
+            raytracer
+                .shading(
+                    &Bvh::new(&[]),
+                    &lights,
+                    &material,
+                    Vec3::zero(),
+                    normal,
+                    None,
+                    view_dir,
+                    1,
+                )
+                .luminance()
+        };
+
+        let physical = |pos| Light::with_radiant_power(pos, 4.0 * std::f64::consts::PI);
+        assert!((brightness(2.0, physical) - 0.25).abs() < 1e-9);
+        assert!((brightness(4.0, physical) - 0.0625).abs() < 1e-9);
+
+        // An intensity without attenuation does not fall off
+        let constant = |pos| Light::new(pos, 0.5).with_attenuation(Light::NO_ATTENUATION);
+        assert_eq!(brightness(2.0, constant), brightness(4.0, constant));
+    }
+
+    #[test]
+    fn attenuation_falloff() {
+        let raytracer = Raytracer::new(camera(9, 9), 1);
+        let material = Material {
+            ambient: Color::zero(),
+            ..matte(Color::new_f(1.0, 1.0, 1.0))
+        };
+        let normal = Vec3::new(0.0, 0.0, -1.0);
+
+        let brightness = |distance: f64, attenuation| {
+            let light =
+                Light::new(Vec3::new(0.0, 0.0, -distance), 0.8).with_attenuation(attenuation);
+            raytracer
+                .lambertian(&Bvh::new(&[]), &[light], &material, Vec3::zero(), normal)
+                .luminance()
+        };
+
+        let near = brightness(1.0, Light::INVERSE_SQUARE);
+        assert!((near - 0.8).abs() < 1e-9);
+        assert!((brightness(2.0, Light::INVERSE_SQUARE) - near / 4.0).abs() < 1e-9);
+
+        // 1 / (1 + 0.5 d)
+        let linear = (1.0, 0.5, 0.0);
+        assert!((brightness(2.0, linear) - 0.4).abs() < 1e-9);
+        assert_eq!(
+            brightness(1.0, Light::NO_ATTENUATION),
+            brightness(3.0, Light::NO_ATTENUATION)
+        );
+    }
+
+    #[test]
+    fn supersampling_blends_diagonal_edge() {
+        let white = unlit(Color::new_f(1.0, 1.0, 1.0));
+        // Covers everything below the diagonal of the image
+        let world = [Object::new(
+            Triangle::new(
+                Vec3::new(-100.0, 100.0, 5.0),
+                Vec3::new(100.0, -100.0, 5.0),
+                Vec3::new(-100.0, -100.0, 5.0),
+            )
+            .into(),
+            white,
+        )];
+        let is_blend = |c: &Color| {
+            let [r, _, _] = c.to_linear_array();
+            r > 0.0 && r < 1.0
+        };
+
+        let mut raytracer = Raytracer::new(camera(16, 16), 1);
+        let aliased = raytracer.raycast(&world, &[]);
+        assert!(!aliased.iter().flatten().any(is_blend));
+
+        raytracer.set_samples_per_pixel(16);
+        let smooth = raytracer.raycast(&world, &[]);
+        assert!(smooth.iter().flatten().filter(|c| is_blend(c)).count() >= 8);
+        assert_eq!(smooth, raytracer.par_raycast(&world, &[]));
+    }
+
+    #[test]
+    fn parallel_matches_serial_for_odd_sizes() {
+        let world = [
+            Object::new(
+                Sphere::new(Vec3::new(0.4, -0.3, 4.0), 1.0).into(),
+                matte(Color::new(255, 0, 0)),
+            ),
+            Object::new(
+                Plane::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).into(),
+                matte(Color::new(0, 0, 255)),
+            ),
+        ];
+        let lights = [Light::new(Vec3::new(2.0, 5.0, 0.0), 1.0)];
+
+        for (width, height) in [(9, 7), (10, 5), (1, 1)] {
+            let raytracer = Raytracer::new(camera(width, height), 2);
+            let serial = raytracer.raycast(&world, &lights);
+            assert_eq!(serial.len(), height as usize);
+            assert_eq!(serial[0].len(), width as usize);
+            assert_eq!(
+                serial,
+                raytracer.par_raycast(&world, &lights),
+                "{width}x{height}"
+            );
         }
+    }
 
-        image
+    #[test]
+    fn jitter_independent_of_thread_count() {
+        let mut raytracer = Raytracer::new(camera(16, 16), 1);
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.5).into(),
+            matte(Color::new(255, 0, 0)),
+        )];
+        let lights = [Light::new(Vec3::new(0.0, 5.0, 0.0), 1.0)];
+
+        let centered = raytracer.par_raycast(&world, &lights);
+
+        raytracer.set_pixel_jitter(true);
+        raytracer.set_seed(7);
+        let render = |raytracer: &Raytracer, threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| raytracer.par_raycast(&world, &lights))
+        };
+
+        let jittered = render(&raytracer, 1);
+        assert_ne!(jittered, centered);
+        assert_eq!(jittered, render(&raytracer, 8));
+        assert_eq!(jittered, raytracer.raycast(&world, &lights));
+
+        raytracer.set_seed(8);
+        assert_ne!(jittered, render(&raytracer, 8));
+    }
+
+    #[test]
+    fn light_sampling_matches_all_lights() {
+        let mut raytracer = Raytracer::new(camera(4, 4), 1);
+        let pos = Vec3::zero();
+        let lights = [
+            Light::new(Vec3::new(0.0, 5.0, 0.0), 0.2),
+            Light::new(Vec3::new(5.0, 0.0, 0.0), 0.6),
+            Light::with_radiant_power(Vec3::new(0.0, 0.0, 2.0), 30.0),
+            Light::new(Vec3::new(-5.0, 0.0, 0.0), 0.4),
+        ];
+        // Hides the last light
+        let world = [Object::new(
+            Sphere::new(Vec3::new(-2.5, 0.0, 0.0), 1.0).into(),
+            matte(Color::new(255, 0, 0)),
+        )];
+
+        let total = |raytracer: &Raytracer| -> f64 {
+            let hits = raytracer.trace_to_lights(&Bvh::new(&world), &lights, pos, Vec3::zero());
+            hits.iter().map(|&(_, intensity)| intensity).sum()
+        };
+        let expected = total(&raytracer);
+
+        raytracer.set_light_sampling(true);
+        let samples = 4000;
+        let mut sum = 0.0;
+        for seed in 0..samples {
+            raytracer.set_seed(seed);
+            let hits = raytracer.trace_to_lights(&Bvh::new(&world), &lights, pos, Vec3::zero());
+            assert!(hits.len() <= 1);
+            sum += total(&raytracer);
+        }
+        let average = sum / samples as f64;
+
+        assert!(
+            (average - expected).abs() < expected * 0.03,
+            "{average} vs {expected}"
+        );
+    }
+
+    #[test]
+    fn camera_relative_far_from_origin() {
+        let lights = [Light::new(Vec3::new(0.0, 4.0, -2.0), 1.0)];
+        let world = |offset: Vec3| {
+            [Object::new(
+                Sphere::new(Vec3::new(0.0, 0.0, 3.0) + offset, 1.0).into(),
+                matte(Color::new(255, 0, 0)),
+            )]
+        };
+        let render = |offset: Vec3, relative: bool| {
+            let mut raytracer = Raytracer::new(camera(32, 32), 1);
+            // The bias would hide the precision loss this test is about
+            raytracer.set_shadow_bias(0.0);
+            raytracer
+                .camera
+                .set_position(Vec3::new(0.1, 0.2, 0.3) + offset);
+            let mut world = world(offset);
+            let mut lights = lights.map(|l| Light {
+                pos: l.pos + offset,
+                ..l
+            });
+            if relative {
+                raytracer.make_camera_relative(&mut world, &mut lights);
+            }
+            raytracer.raycast(&world, &lights)
+        };
+
+        let reference = render(Vec3::zero(), false);
+        let artifacts = |image: Vec<Vec<Color>>| {
+            let pixels = image.iter().flatten().zip(reference.iter().flatten());
+            pixels
+                .filter(|(a, b)| (a.luminance() - b.luminance()).abs() > 1e-3)
+                .count()
+        };
+
+        let near = Vec3::new(1e7, 0.0, 0.0);
+        assert_eq!(artifacts(render(near, true)), 0);
+
+        // Far enough that positions are less precise than the surface epsilon
+        let far = Vec3::new(1e11, 0.0, 0.0);
+        assert!(artifacts(render(far, false)) > 0);
+        assert_eq!(artifacts(render(far, true)), 0);
+    }
+
+    #[test]
+    fn hdr_keeps_bright_emission() {
+        let raytracer = Raytracer::new(camera(8, 8), 1);
+        let material = Material {
+            emission: Color::new_f(20.0, 10.0, 5.0),
+            ..matte(Color::new(255, 255, 255))
+        };
+        let world = [Object::new(
+            Plane::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).into(),
+            material,
+        )];
+
+        let hdr = raytracer.render_hdr(&world, &[]);
+        assert_eq!(hdr.len(), 8);
+        for &[r, g, b] in hdr.iter().flatten() {
+            assert!(r > 20.0 && g > 10.0 && b > 5.0, "{r} {g} {b}");
+        }
+    }
+
+    #[test]
+    fn background_fills_misses() {
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(),
+            matte(Color::new(255, 0, 0)),
+        )];
+        let light = Light::new(Vec3::zero(), 1.0);
+        let blue = Color::new(0, 0, 255);
+        let mut raytracer = Raytracer::new(camera(16, 16), 1);
+        raytracer.set_background(blue);
+
+        let image = raytracer.raycast(&world, &[light]);
+        let hits = raytracer.hit_mask(&world);
+        assert!(hits.iter().flatten().any(|&hit| hit));
+        for (row, hit_row) in image.iter().zip(&hits) {
+            for (&color, &hit) in row.iter().zip(hit_row) {
+                assert_eq!(color == blue, !hit);
+            }
+        }
+    }
+
+    #[test]
+    fn exposure_tone_maps_bright_surfaces() {
+        let emissive = |emission: f64| Material {
+            emission: Color::new_f(emission, emission, emission),
+            ambient: Color::zero(),
+            ..matte(Color::new(255, 255, 255))
+        };
+        // Bright on the left half of the image and brighter on the right
+        let world = [
+            Object::new(
+                Plane::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).into(),
+                emissive(4.0),
+            ),
+            Object::new(
+                Cuboid::new(Vec3::new(0.0, -10.0, 4.0), Vec3::new(10.0, 10.0, 4.5)).into(),
+                emissive(12.0),
+            ),
+        ];
+        let mut raytracer = Raytracer::new(camera(8, 8), 1);
+        let bytes = |image: Vec<Vec<Color>>| -> Vec<u8> {
+            image
+                .iter()
+                .flatten()
+                .map(|c| c.to_srgb_bytes()[0])
+                .collect()
+        };
+
+        let clamped = bytes(raytracer.raycast(&world, &[]));
+        assert!(clamped.iter().all(|&b| b == 255));
+
+        raytracer.set_exposure(Some(1.0));
+        let mut mapped = bytes(raytracer.raycast(&world, &[]));
+        assert_eq!(mapped, bytes(raytracer.par_raycast(&world, &[])));
+        mapped.sort_unstable();
+        mapped.dedup();
+        assert_eq!(mapped.len(), 2, "{mapped:?}");
+        assert!(mapped[1] < 255);
+
+        // Raw colors are still available
+        let hdr = raytracer.render_hdr(&world, &[]);
+        assert!(hdr.iter().flatten().all(|&[r, _, _]| r >= 4.0));
+    }
+
+    #[test]
+    fn stereo_eyes_are_symmetric() {
+        let mut raytracer = Raytracer::new(camera(16, 8), 1);
+        raytracer.camera =
+            Camera::new(16, 8, Vec3::zero(), Vec3::new(1.0, 0.0, 1.0), 90.0, 17.0).unwrap();
+        let world = [Object::new(
+            Sphere::new(Vec3::new(4.0, 0.0, 4.0), 1.0).into(),
+            matte(Color::new(255, 0, 0)),
+        )];
+
+        let (left, right) = raytracer.camera.stereo_pair(0.5);
+        let center = raytracer.camera.position();
+        assert!(((left.position() + right.position()) * 0.5 - center).length() < FLOAT_EPS);
+        assert!(
+            ((right.position() - left.position()) - raytracer.camera.right() * 0.5).length()
+                < FLOAT_EPS
+        );
+        assert!(raytracer.camera.right().dot(Vec3::new(1.0, 0.0, 1.0)).abs() < FLOAT_EPS);
+
+        let (left, right) = raytracer.render_stereo(&world, &[], 0.5);
+        assert_eq!(left.len(), 8);
+        assert_eq!(right.len(), 8);
+        assert_ne!(left, right);
+        assert_eq!(
+            raytracer.render_stereo(&world, &[], 0.0).0,
+            raytracer.raycast(&world, &[])
+        );
+    }
+
+    #[test]
+    fn shading_terms_sum_to_full_render() {
+        let mut raytracer = Raytracer::new(camera(16, 16), 3);
+        raytracer.camera.set_position(Vec3::new(0.0, 1.0, 0.0));
+        let background = Color::new_f(0.1, 0.2, 0.3);
+        raytracer.set_background(background);
+        let shiny = Material {
+            specular: Color::new_f(0.3, 0.3, 0.3),
+            clearcoat: 0.2,
+            ..matte(Color::new_f(0.3, 0.4, 0.6))
+        };
+        let glass = Material {
+            opacity: 0.5,
+            ..matte(Color::new_f(0.2, 0.5, 0.2))
+        };
+        let world = [
+            Object::new(Sphere::new(Vec3::new(-0.6, 0.0, 4.0), 0.5).into(), shiny),
+            Object::new(Sphere::new(Vec3::new(0.6, 0.0, 4.0), 0.5).into(), glass),
+            Object::new(
+                Plane::new(Vec3::new(0.0, -0.5, 0.0), Vec3::new(0.0, 1.0, 0.0)).into(),
+                matte(Color::new_f(0.4, 0.3, 0.3)),
+            ),
+        ];
+        let lights = [Light::new(Vec3::new(2.0, 3.0, 0.0), 0.5)];
+
+        let full = raytracer.raycast(&world, &lights);
+        let [ambient, diffuse, specular] = raytracer.raycast_terms(&world, &lights);
+        assert_ne!(ambient, diffuse);
+        assert_ne!(diffuse, specular);
+
+        // Pixels that miss every object only add the background to the ambient term
+        let mask = raytracer.hit_mask(&world);
+        let misses: Vec<_> = (0..16)
+            .flat_map(|row| (0..16).map(move |col| (row, col)))
+            .filter(|&(row, col)| !mask[row][col])
+            .collect();
+        assert!(!misses.is_empty());
+        for (row, col) in misses {
+            assert_eq!(full[row][col], background);
+            assert_eq!(ambient[row][col], background);
+            assert_eq!(diffuse[row][col], Color::zero());
+            assert_eq!(specular[row][col], Color::zero());
+        }
+
+        let pixels = full.iter().flatten().zip(ambient.iter().flatten());
+        let pixels = pixels.zip(diffuse.iter().flatten().zip(specular.iter().flatten()));
+        for ((&full, &a), (&d, &s)) in pixels {
+            let sum = a + d + s;
+            let diff = (sum - full).to_linear_array();
+            assert!(diff.iter().all(|c| c.abs() < 1e-6), "{sum:?} vs {full:?}");
+        }
+    }
+
+    #[test]
+    fn traversal_stats_count_mesh_triangles() {
+        let raytracer = Raytracer::new(camera(4, 4), 1);
+        let material = matte(Color::new(255, 0, 0));
+        let mesh = |size: f64, z| {
+            let [a, b, c, d] = [
+                Vec3::new(-size, -size, z),
+                Vec3::new(size, -size, z),
+                Vec3::new(size, size, z),
+                Vec3::new(-size, size, z),
+            ];
+            primitive::Mesh::new(vec![Triangle::new(a, b, c), Triangle::new(a, c, d)])
+        };
+
+        // Every ray hits the bounds of the mesh in front of the camera
+        let mut world = vec![Object::new(mesh(100.0, 10.0).into(), material)];
+        let stats = raytracer.traversal_stats(&world);
+        assert_eq!(stats.objects, 1);
+        assert_eq!(stats.bvh.nodes, 1);
+        assert_eq!(stats.rays, 16);
+        assert_eq!(stats.primitives_tested, 16 * 2);
+        assert_eq!(stats.tested_per_ray(), 2.0);
+
+        // The mesh behind the camera is never tested
+        world[0] = Object::new(mesh(1.0, -10.0).into(), material);
+        let stats = raytracer.traversal_stats(&world);
+        assert_eq!(stats.primitives_tested, 0);
+    }
+
+    #[test]
+    fn flat_buffer_matches_nested() {
+        let raytracer = Raytracer::new(camera(8, 6), 2);
+        let world = [Object::new(
+            Sphere::new(Vec3::new(1.0, 0.0, 5.0), 2.0).into(),
+            matte(Color::new(255, 0, 0)),
+        )];
+        let lights = [Light::new(Vec3::new(0.0, 5.0, 0.0), 1.0)];
+
+        let (buffer, width, height) = raytracer.render_to_buffer(&world, &lights);
+        assert_eq!((width, height), (8, 6));
+
+        let nested = raytracer.par_raycast(&world, &lights);
+        for (y, row) in nested.iter().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                assert_eq!(buffer[y * width + x], *color, "pixel ({x}, {y})");
+            }
+        }
+        assert!(buffer.iter().any(|c| !c.is_zero()));
+    }
+
+    #[test]
+    fn non_finite_rays_render_black() {
+        assert_eq!(
+            Ray::new(Vec3::new(f64::NAN, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)).unwrap_err(),
+            ray::RayNewError::NonFinite
+        );
+        assert_eq!(
+            Ray::new(Vec3::zero(), Vec3::zero()).unwrap_err(),
+            ray::RayNewError::DirectionZero
+        );
+
+        let camera = Camera::new(
+            4,
+            4,
+            Vec3::new(f64::NAN, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            90.0,
+            0.0,
+        )
+        .unwrap();
+        let raytracer = Raytracer::new(camera, 2);
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(),
+            matte(Color::new(255, 0, 0)),
+        )];
+
+        let image = raytracer.raycast(&world, &[]);
+        assert!(image.iter().flatten().all(|c| c.is_zero()));
+        assert_eq!(image, raytracer.par_raycast(&world, &[]));
     }
 }
@@ -1,7 +1,134 @@
-use crate::vec3::Vec3;
+use crate::{vec3::Vec3, FLOAT_EPS};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Light {
     pub pos: Vec3,
+    /// Brightness of the light, falling off with the distance by `attenuation`.
+    /// Ignored if `radiant_power` is set.
     pub intensity: f64,
+    /// Total power emitted by the light in all directions, in watts.
+    ///
+    /// When set, the light falls off with the inverse square of the distance,
+    /// a surface at distance `d` receives `radiant_power / (4π d²)`.
+    /// The legacy `intensity` equals that irradiance at a single fixed distance.
+    pub radiant_power: Option<f64>,
+    /// Constant, linear and quadratic falloff of `intensity` with distance,
+    /// a surface at distance `d` receives `intensity / (c + l d + q d²)`.
+    /// Defaults to [`Light::INVERSE_SQUARE`], ignored if `radiant_power` is set.
+    pub attenuation: (f64, f64, f64),
+    /// Number of shadow rays used when `shadow_radius` is positive.
+    pub shadow_samples: u32,
+    /// Radius of the disk that shadow rays are spread over.
+    /// A radius of `0.0` gives hard shadows.
+    pub shadow_radius: f64,
+}
+
+impl Light {
+    /// The same intensity at any distance.
+    pub const NO_ATTENUATION: (f64, f64, f64) = (1.0, 0.0, 0.0);
+    /// Intensity falls off with the square of the distance,
+    /// `intensity` is received at distance 1.
+    pub const INVERSE_SQUARE: (f64, f64, f64) = (0.0, 0.0, 1.0);
+
+    /// Create a light with hard shadows, `intensity` is received at distance 1
+    /// and falls off with the square of the distance.
+    pub fn new(pos: Vec3, intensity: f64) -> Self {
+        Self {
+            pos,
+            intensity,
+            radiant_power: None,
+            attenuation: Self::INVERSE_SQUARE,
+            shadow_samples: 1,
+            shadow_radius: 0.0,
+        }
+    }
+
+    /// Create a light with hard shadows that follows the inverse-square law.
+    pub fn with_radiant_power(pos: Vec3, radiant_power: f64) -> Self {
+        Self {
+            radiant_power: Some(radiant_power),
+            ..Self::new(pos, 0.0)
+        }
+    }
+
+    /// Set the constant, linear and quadratic falloff of the intensity.
+    pub fn with_attenuation(mut self, attenuation: (f64, f64, f64)) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
+    /// Returns the intensity of the light as received at `point`.
+    pub fn intensity_at(&self, point: Vec3) -> f64 {
+        let distance_squared = (self.pos - point).length_squared();
+        match self.radiant_power {
+            Some(power) => power / (4.0 * std::f64::consts::PI * distance_squared),
+            None => {
+                let (constant, linear, quadratic) = self.attenuation;
+                let falloff =
+                    constant + linear * distance_squared.sqrt() + quadratic * distance_squared;
+                self.intensity / falloff.max(FLOAT_EPS)
+            }
+        }
+    }
+
+    /// Returns the points that shadow rays towards the light should target.
+    ///
+    /// The points are spread deterministically over a disk facing `from`
+    /// using a sunflower spiral, so renders are reproducible.
+    pub fn shadow_targets(&self, from: Vec3) -> Vec<Vec3> {
+        if self.shadow_radius <= 0.0 || self.shadow_samples <= 1 {
+            return vec![self.pos];
+        }
+
+        let w = from.direction_to(self.pos);
+        // Any vector not parallel to `w` gives a basis for the disk
+        let helper = if w.x.abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let u = w.cross(helper).normalize();
+        let v = w.cross(u);
+
+        let golden_angle = std::f64::consts::PI * (3.0 - 5.0_f64.sqrt());
+        let n = f64::from(self.shadow_samples);
+
+        (0..self.shadow_samples)
+            .map(|i| {
+                let i = f64::from(i);
+                let r = self.shadow_radius * ((i + 0.5) / n).sqrt();
+                let (sin, cos) = (i * golden_angle).sin_cos();
+                self.pos + u * (r * cos) + v * (r * sin)
+            })
+            .collect()
+    }
+}
+
+/// Pick one of `lights` with a probability proportional to its intensity at `point`,
+/// using `u` in `[0, 1)` as the random number.
+///
+/// Returns the light together with the probability of picking it,
+/// dividing its contribution by that probability keeps the estimate unbiased.
+/// Returns `None` if no light reaches `point`.
+pub fn choose_light(lights: &[Light], point: Vec3, u: f64) -> Option<(&Light, f64)> {
+    let total: f64 = lights.iter().map(|l| l.intensity_at(point).max(0.0)).sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut remaining = u * total;
+    let mut chosen = None;
+    for light in lights {
+        let importance = light.intensity_at(point).max(0.0);
+        if importance <= 0.0 {
+            continue;
+        }
+        chosen = Some((light, importance / total));
+        if remaining < importance {
+            break;
+        }
+        remaining -= importance;
+    }
+    chosen
 }
@@ -1,8 +1,15 @@
 use std::str::FromStr;
 
-use crate::Color;
+use crate::{texture::Texture, Color, Vec3};
 
-#[derive(Debug, Clone, Copy)]
+/// Clear coat roughness used when none is given.
+pub const DEFAULT_CLEARCOAT_ROUGHNESS: f64 = 0.1;
+
+/// Index of refraction used when none is given, roughly that of glass.
+pub const DEFAULT_IOR: f64 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Material {
     pub color: Color,
     /// Specular reflection defines how much of light the object reflects.
@@ -14,6 +21,102 @@ pub struct Material {
     /// Ambient lighting defines how strong the “base light” should be interpreted.
     /// <https://en.wikipedia.org/wiki/Shading#Ambient_lighting>
     pub ambient: Color,
+    /// Light given off by the object itself, independent of any lights.
+    pub emission: Color,
+    /// Strength of a clear coat highlight on top of the base shading, in `[0, 1]`.
+    pub clearcoat: f64,
+    /// Roughness of the clear coat, lower values give a smaller and sharper highlight.
+    pub clearcoat_roughness: f64,
+    /// Replaces `color` where the surface has texture coordinates.
+    pub texture: Option<Texture>,
+    /// How much of the surface covers what is behind it, in `[0, 1]`.
+    /// Unlike refraction, rays pass through without changing direction.
+    pub opacity: f64,
+    /// How much of the light is refracted through the surface, in `[0, 1]`.
+    /// <https://en.wikipedia.org/wiki/Refraction>
+    pub refraction: f64,
+    /// Index of refraction of the material, only used when `refraction` is non-zero.
+    /// <https://en.wikipedia.org/wiki/Refractive_index>
+    pub ior: f64,
+    /// Blinn-Phong exponent of the highlight where lights reflect off the surface,
+    /// higher values give a smaller highlight. `0.0` disables the highlight.
+    /// <https://en.wikipedia.org/wiki/Blinn%E2%80%93Phong_reflection_model>
+    pub shininess: f64,
+}
+
+impl Default for Material {
+    /// A black, opaque material that neither reflects nor gives off light.
+    fn default() -> Self {
+        Self {
+            color: Color::zero(),
+            specular: Color::zero(),
+            lambert: Color::zero(),
+            ambient: Color::zero(),
+            emission: Color::zero(),
+            clearcoat: 0.0,
+            clearcoat_roughness: DEFAULT_CLEARCOAT_ROUGHNESS,
+            texture: None,
+            opacity: 1.0,
+            refraction: 0.0,
+            ior: DEFAULT_IOR,
+            shininess: 0.0,
+        }
+    }
+}
+
+impl Material {
+    /// Returns the base color at the texture coordinates `uv`.
+    pub fn color_at(&self, uv: Option<(f64, f64)>) -> Color {
+        match (self.texture, uv) {
+            (Some(texture), Some(uv)) => texture.sample(uv),
+            _ => self.color,
+        }
+    }
+
+    /// Returns true if the material gives off light on its own.
+    pub fn is_emissive(&self) -> bool {
+        !self.emission.is_zero()
+    }
+
+    /// Returns true if the material has a clear coat highlight.
+    pub fn has_clearcoat(&self) -> bool {
+        self.clearcoat > 0.0
+    }
+
+    /// Returns true if lights leave a highlight on the surface.
+    pub fn has_highlight(&self) -> bool {
+        self.shininess > 0.0 && !self.specular.is_zero()
+    }
+
+    /// Returns true if objects behind the surface show through it.
+    pub fn is_translucent(&self) -> bool {
+        self.opacity < 1.0
+    }
+
+    /// Returns true if light is refracted through the surface.
+    pub fn is_transmissive(&self) -> bool {
+        self.refraction > 0.0
+    }
+
+    /// Returns true if the material reflects other objects.
+    pub fn is_reflective(&self) -> bool {
+        !self.specular.is_zero()
+    }
+}
+
+/// Fraction of light reflected by a surface, from Schlick's approximation of the
+/// Fresnel equations. `r0` is the reflectance for light hitting the surface head on,
+/// grazing hits approach full reflection.
+/// <https://en.wikipedia.org/wiki/Schlick%27s_approximation>
+pub fn schlick(incident: Vec3, normal: Vec3, r0: f64) -> f64 {
+    let cos_i = -incident.normalize().dot(normal.normalize());
+    let cos_i = cos_i.abs().min(1.0);
+    r0 + (1.0 - r0) * (1.0 - cos_i).powi(5)
+}
+
+/// Head on reflectance of a boundary where `eta` is the ratio of the refractive indices.
+pub fn normal_reflectance(eta: f64) -> f64 {
+    ((1.0 - eta) / (1.0 + eta)).powi(2)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -28,7 +131,7 @@ impl FromStr for MaterialTemplate {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use MaterialTemplate::{Red, Green, Blue, Bronze};
+        use MaterialTemplate::{Blue, Bronze, Green, Red};
         let m = match s {
             "red" => Red,
             "green" => Green,
@@ -42,7 +145,7 @@ impl FromStr for MaterialTemplate {
 
 impl MaterialTemplate {
     pub fn get_name_tuples() -> [(&'static str, Self); 4] {
-        use MaterialTemplate::{Red, Green, Blue, Bronze};
+        use MaterialTemplate::{Blue, Bronze, Green, Red};
 
         [
             ("red", Red),
@@ -53,40 +156,63 @@ impl MaterialTemplate {
     }
 
     pub fn get_material(&self, color: Color) -> Material {
-        use MaterialTemplate::{Red, Green, Blue, Bronze};
+        use MaterialTemplate::{Blue, Bronze, Green, Red};
 
         match self {
             Red => Material {
                 color,
-                ambient: Color::zero(),
                 lambert: Color::new_f(1.0, 0.0, 0.0),
                 specular: Color::new_f(0.0225, 0.0225, 0.0225),
+                ..Default::default()
             },
             Green => Material {
                 color,
-                ambient: Color::zero(),
                 lambert: Color::new_f(0.0, 1.0, 0.0),
                 specular: Color::new_f(0.0225, 0.0225, 0.0225),
+                ..Default::default()
             },
             Blue => Material {
                 color,
-                ambient: Color::zero(),
                 lambert: Color::new_f(0.0, 0.0, 1.0),
                 specular: Color::new_f(0.0225, 0.0225, 0.0225),
+                ..Default::default()
             },
             Bronze => Material {
                 color,
                 ambient: Color::new_f(0.2125, 0.1275, 0.054),
                 lambert: Color::new_f(0.714, 0.4284, 0.18144),
                 specular: Color::new_f(0.393548, 0.271906, 0.166721),
+                ..Default::default()
             },
         }
     }
+
+    /// Same as [`MaterialTemplate::get_material`], with the coefficients
+    /// that are `Some` replacing the ones of the template.
+    pub fn get_material_with_overrides(
+        &self,
+        color: Color,
+        lambert: Option<Color>,
+        specular: Option<Color>,
+        ambient: Option<Color>,
+        emission: Option<Color>,
+    ) -> Material {
+        let template = self.get_material(color);
+
+        Material {
+            lambert: lambert.unwrap_or(template.lambert),
+            specular: specular.unwrap_or(template.specular),
+            ambient: ambient.unwrap_or(template.ambient),
+            emission: emission.unwrap_or(template.emission),
+            ..template
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::MaterialTemplate;
+    use super::{normal_reflectance, schlick, Material, MaterialTemplate};
+    use crate::{Color, Vec3};
 
     #[test]
     fn all_materials_have_names() {
@@ -94,4 +220,73 @@ mod tests {
             assert_eq!(m, s.parse().unwrap());
         }
     }
+
+    #[test]
+    fn override_bronze_specular() {
+        let color = Color::new(255, 255, 255);
+        let bronze = MaterialTemplate::Bronze.get_material(color);
+        let specular = Color::new_f(0.5, 0.5, 0.5);
+
+        let material = MaterialTemplate::Bronze.get_material_with_overrides(
+            color,
+            None,
+            Some(specular),
+            None,
+            None,
+        );
+        assert_eq!(material, Material { specular, ..bronze });
+
+        let unchanged =
+            MaterialTemplate::Bronze.get_material_with_overrides(color, None, None, None, None);
+        assert_eq!(unchanged, bronze);
+    }
+
+    #[test]
+    fn classify_materials() {
+        let bronze = MaterialTemplate::Bronze.get_material(Color::new(255, 255, 255));
+        assert!(bronze.is_reflective());
+        assert!(!bronze.is_emissive());
+
+        let matte = Material {
+            specular: Color::zero(),
+            ..bronze
+        };
+        assert!(!matte.is_reflective());
+
+        let lamp = Material {
+            emission: Color::new_f(4.0, 4.0, 4.0),
+            ..matte
+        };
+        assert!(lamp.is_emissive());
+        assert!(!lamp.is_reflective());
+        assert!(!lamp.has_clearcoat());
+        assert!(!lamp.is_transmissive());
+
+        let glass = Material {
+            refraction: 1.0,
+            ior: 1.5,
+            ..matte
+        };
+        assert!(glass.is_transmissive());
+        assert!(!glass.is_reflective() && !glass.is_emissive());
+        assert!(!bronze.is_transmissive());
+    }
+
+    #[test]
+    fn schlick_reference_values() {
+        let normal = Vec3::new(0.0, 0.0, -1.0);
+        let incident = |degrees: f64| {
+            let angle = degrees.to_radians();
+            Vec3::new(angle.sin(), 0.0, angle.cos())
+        };
+        let r0 = normal_reflectance(1.0 / 1.5);
+        assert!((r0 - 0.04).abs() < 1e-12);
+
+        assert!((schlick(incident(0.0), normal, r0) - 0.04).abs() < 1e-9);
+        // 0.04 + 0.96 * (1 - cos 45°)^5
+        assert!((schlick(incident(45.0), normal, r0) - 0.042_069).abs() < 1e-6);
+        // 0.04 + 0.96 * (1 - cos 89°)^5
+        assert!((schlick(incident(89.0), normal, r0) - 0.919_102).abs() < 1e-6);
+        assert!((schlick(incident(90.0), normal, r0) - 1.0).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,162 @@
+//! Loading of triangle meshes from Wavefront OBJ files.
+//!
+//! Only vertex positions (`v`), texture coordinates (`vt`) and faces (`f`)
+//! are read, every other statement is ignored.
+//! <https://en.wikipedia.org/wiki/Wavefront_.obj_file>
+
+use std::io::BufRead;
+
+use crate::{primitive::Triangle, vec3::Vec3};
+
+/// Read the triangles of an OBJ file.
+///
+/// Faces with more than three vertices are split into a fan of triangles.
+/// Faces where any vertex is missing a texture coordinate get no UVs.
+pub fn load_obj<R: BufRead>(reader: R) -> Result<Vec<Triangle>, String> {
+    let mut positions: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f64, f64)> = vec![];
+    let mut triangles = vec![];
+
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Could not read OBJ file!\n{e}"))?;
+        let line_num = line_num + 1;
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("v") => {
+                let [x, y, z] = parse_floats(parts, line_num)?;
+                positions.push(Vec3::new(x, y, z));
+            }
+            Some("vt") => {
+                let [u, v] = parse_floats(parts, line_num)?;
+                uvs.push((u, v));
+            }
+            Some("f") => {
+                let vertices = parts
+                    .map(|vertex| parse_face_vertex(vertex, &positions, &uvs, line_num))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if vertices.len() < 3 {
+                    return Err(format!("Line {line_num}: face needs at least 3 vertices"));
+                }
+
+                let (p1, uv1) = vertices[0];
+                for pair in vertices[1..].windows(2) {
+                    let [(p2, uv2), (p3, uv3)] = [pair[0], pair[1]];
+                    let triangle = Triangle::new(p1, p2, p3);
+
+                    triangles.push(match (uv1, uv2, uv3) {
+                        (Some(a), Some(b), Some(c)) => triangle.with_uvs([a, b, c]),
+                        _ => triangle,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(triangles)
+}
+
+/// Parse the first `N` numbers of a statement. Extra numbers, like the
+/// optional `w` of a vertex, are ignored.
+fn parse_floats<'a, const N: usize>(
+    mut parts: impl Iterator<Item = &'a str>,
+    line_num: usize,
+) -> Result<[f64; N], String> {
+    let mut values = [0.0; N];
+    for value in values.iter_mut() {
+        let part = parts
+            .next()
+            .ok_or_else(|| format!("Line {line_num}: expected {N} numbers"))?;
+        *value = part
+            .parse()
+            .map_err(|_| format!("Line {line_num}: '{part}' is not a number"))?;
+    }
+    Ok(values)
+}
+
+/// Parse a face vertex of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+fn parse_face_vertex(
+    vertex: &str,
+    positions: &[Vec3],
+    uvs: &[(f64, f64)],
+    line_num: usize,
+) -> Result<(Vec3, Option<(f64, f64)>), String> {
+    let mut indices = vertex.split('/');
+
+    let position = indices.next().unwrap_or_default();
+    let position = *resolve_index(position, positions, line_num)?;
+
+    let uv = match indices.next() {
+        Some(uv) if !uv.is_empty() => Some(*resolve_index(uv, uvs, line_num)?),
+        _ => None,
+    };
+
+    Ok((position, uv))
+}
+
+/// Look up a 1-based index, negative indices count from the end.
+fn resolve_index<'a, T>(index: &str, values: &'a [T], line_num: usize) -> Result<&'a T, String> {
+    let i: i64 = index
+        .parse()
+        .map_err(|_| format!("Line {line_num}: '{index}' is not an index"))?;
+
+    let i = if i < 0 {
+        values.len() as i64 + i
+    } else {
+        i - 1
+    };
+
+    usize::try_from(i)
+        .ok()
+        .and_then(|i| values.get(i))
+        .ok_or_else(|| format!("Line {line_num}: index {index} is out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive::Intersectable, ray::Ray};
+
+    const QUAD: &str = "
+# A unit quad in the plane z = 1
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+f 1/1 2/2 3/3 4/4
+f -4 -3 -2
+";
+
+    #[test]
+    fn load_quad_with_uvs() {
+        let triangles = load_obj(QUAD.as_bytes()).unwrap();
+        assert_eq!(triangles.len(), 3);
+        assert!(triangles[0].uvs.is_some());
+        assert!(triangles[2].uvs.is_none());
+
+        let ray = Ray::new(Vec3::new(0.75, 0.25, 0.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        let hit = triangles[0].intersection(&ray).unwrap();
+        let (u, v) = hit.uv.unwrap();
+        assert!((u - 0.75).abs() < 1e-9, "{u}");
+        assert!((v - 0.25).abs() < 1e-9, "{v}");
+
+        // Falls back to the barycentric coordinates
+        let hit = triangles[2].intersection(&ray).unwrap();
+        let (u, v) = hit.uv.unwrap();
+        assert!((u - 0.5).abs() < 1e-9, "{u}");
+        assert!((v - 0.25).abs() < 1e-9, "{v}");
+    }
+
+    #[test]
+    fn load_errors() {
+        assert!(load_obj("v 0 0".as_bytes()).is_err());
+        assert!(load_obj("v 0 0 0\nf 1 2 3".as_bytes()).is_err());
+        assert!(load_obj("v 0 0 0\nv 1 0 0\nf 1 2".as_bytes()).is_err());
+    }
+}
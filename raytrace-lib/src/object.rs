@@ -2,16 +2,112 @@ use crate::{
     material::Material,
     primitive::{Intersectable, Primitive},
     ray::Ray,
+    vec3::Vec3,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Object {
     pub primitive: Primitive,
     pub material: Material,
+    /// Optional name used in debug output.
+    pub name: Option<String>,
+    /// Decides which object's medium applies where objects overlap.
+    /// The object with the highest priority wins.
+    pub priority: i32,
+    /// Distance moved per unit of time, used for motion blur.
+    pub velocity: Vec3,
+    /// Whether the object blocks shadow rays.
+    pub casts_shadows: bool,
+}
+
+impl Object {
+    pub fn new(primitive: Primitive, material: Material) -> Self {
+        Self {
+            primitive,
+            material,
+            name: None,
+            priority: 0,
+            velocity: Vec3::zero(),
+            casts_shadows: true,
+        }
+    }
+
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec3) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_casts_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+
+    /// Returns the object moved to where it is at `time`.
+    pub fn at_time(&self, time: f64) -> Self {
+        Self {
+            primitive: self.primitive.translated(self.velocity * time),
+            ..self.clone()
+        }
+    }
+}
+
+/// Returns the object whose medium `point` lies in.
+///
+/// When `point` is inside several overlapping objects the one with the highest
+/// priority is returned, ties go to the object that comes first in `world`.
+pub fn medium_at(world: &[Object], point: Vec3) -> Option<&Object> {
+    world
+        .iter()
+        .filter(|object| object.primitive.contains(point))
+        .rev()
+        .max_by_key(|object| object.priority)
 }
 
 impl Intersectable for Object {
     fn intersection(&self, ray: &Ray) -> Option<crate::primitive::Intersection> {
         self.primitive.intersection(ray)
     }
+
+    fn all_intersections(&self, ray: &Ray) -> Vec<crate::primitive::Intersection> {
+        self.primitive.all_intersections(ray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::MaterialTemplate, primitive::Sphere, Color};
+
+    #[test]
+    fn overlapping_media() {
+        let material = MaterialTemplate::Blue.get_material(Color::new(255, 255, 255));
+        let world = [
+            Object::new(Sphere::new(Vec3::zero(), 2.0).into(), material).with_name("outer"),
+            Object::new(Sphere::new(Vec3::new(1.5, 0.0, 0.0), 1.0).into(), material)
+                .with_name("inner")
+                .with_priority(1),
+        ];
+
+        let name = |point| medium_at(&world, point).and_then(|o| o.name.as_deref());
+        assert_eq!(name(Vec3::new(-1.0, 0.0, 0.0)), Some("outer"));
+        assert_eq!(name(Vec3::new(1.5, 0.0, 0.0)), Some("inner"));
+        assert_eq!(name(Vec3::new(2.2, 0.0, 0.0)), Some("inner"));
+        assert_eq!(name(Vec3::new(5.0, 0.0, 0.0)), None);
+
+        // Equal priority keeps the first object
+        let world = [world[0].clone(), world[1].clone().with_priority(0)];
+        let first = medium_at(&world, Vec3::new(1.5, 0.0, 0.0)).unwrap();
+        assert_eq!(first.name.as_deref(), Some("outer"));
+    }
 }
@@ -0,0 +1,192 @@
+//! Operations on rendered images.
+
+use crate::Color;
+
+/// Multiply every pixel by `2^stops`.
+pub fn exposure(image: &mut [Vec<Color>], stops: f64) {
+    let factor = stops.exp2();
+    for color in image.iter_mut().flatten() {
+        *color *= factor;
+    }
+}
+
+/// Multiply every pixel by `exposure` and compress it into `[0, 1)`
+/// with [`Color::reinhard`], replaces clamping for bright images.
+pub fn tone_map(image: &mut [Vec<Color>], exposure: f64) {
+    for color in image.iter_mut().flatten() {
+        *color = (*color * exposure).reinhard();
+    }
+}
+
+/// Clamp every pixel with [`Color::clamped`].
+/// Should be the last step before the image is converted to bytes.
+pub fn clamp(image: &mut [Vec<Color>]) {
+    for color in image.iter_mut().flatten() {
+        *color = color.clamped();
+    }
+}
+
+/// Box-downsample an image by an integer `factor`.
+///
+/// Each pixel of the result is the average of a `factor × factor` block.
+/// Trailing rows and columns that don't fill a whole block are dropped.
+pub fn downsample(image: &[Vec<Color>], factor: u32) -> Vec<Vec<Color>> {
+    let factor = factor.max(1) as usize;
+    let height = image.len() / factor;
+    let width = image.first().map_or(0, |row| row.len()) / factor;
+
+    let mut block = Vec::with_capacity(factor * factor);
+
+    (0..height)
+        .map(|row| {
+            (0..width)
+                .map(|col| {
+                    block.clear();
+                    for src_row in &image[row * factor..(row + 1) * factor] {
+                        block.extend_from_slice(&src_row[col * factor..(col + 1) * factor]);
+                    }
+                    Color::average(&block)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the fraction of covered pixels in each `factor × factor` block of `mask`.
+///
+/// Uses the same block layout as [`downsample`].
+pub fn coverage(mask: &[Vec<bool>], factor: u32) -> Vec<Vec<f64>> {
+    let factor = factor.max(1) as usize;
+    let height = mask.len() / factor;
+    let width = mask.first().map_or(0, |row| row.len()) / factor;
+    let block_size = (factor * factor) as f64;
+
+    (0..height)
+        .map(|row| {
+            (0..width)
+                .map(|col| {
+                    let covered = mask[row * factor..(row + 1) * factor]
+                        .iter()
+                        .flat_map(|src_row| &src_row[col * factor..(col + 1) * factor])
+                        .filter(|&&hit| hit)
+                        .count();
+                    covered as f64 / block_size
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        primitive::{Primitive, Triangle},
+        Camera, Material, Object, Raytracer, Vec3,
+    };
+
+    /// Render a white triangle whose hypotenuse runs diagonally across the frame.
+    fn render_edge(width: u32, height: u32) -> Vec<Vec<Color>> {
+        let camera = Camera::new(
+            width,
+            height,
+            Vec3::zero(),
+            Vec3::new(0.0, 0.0, 1.0),
+            90.0,
+            0.0,
+        )
+        .unwrap();
+        let raytracer = Raytracer::new(camera, 1);
+
+        let white = Color::new(255, 255, 255);
+        let world = [Object::new(
+            Primitive::Triangle(Triangle::new(
+                Vec3::new(-10.0, -10.0, 5.0),
+                Vec3::new(10.0, -10.0, 5.0),
+                Vec3::new(10.0, 10.0, 5.0),
+            )),
+            Material {
+                color: white,
+                ambient: white,
+                ..Default::default()
+            },
+        )];
+
+        raytracer.raycast(&world, &[])
+    }
+
+    fn count_blended(image: &[Vec<Color>]) -> usize {
+        let white: Color = Color::new(255, 255, 255);
+        image
+            .iter()
+            .flatten()
+            .filter(|&&c| c != white && c != Color::zero())
+            .count()
+    }
+
+    #[test]
+    fn exposure_stops() {
+        let gray = Color::new_f(0.18, 0.18, 0.18);
+
+        let mut image = vec![vec![gray]];
+        exposure(&mut image, 1.0);
+        assert_eq!(image[0][0], Color::new_f(0.36, 0.36, 0.36));
+
+        let mut image = vec![vec![gray]];
+        exposure(&mut image, -1.0);
+        assert_eq!(image[0][0], Color::new_f(0.09, 0.09, 0.09));
+
+        let mut image = vec![vec![gray]];
+        exposure(&mut image, 3.0);
+        clamp(&mut image);
+        assert_eq!(image[0][0], Color::new_f(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn tone_map_keeps_bright_pixels_apart() {
+        let bright = Color::new_f(4.0, 4.0, 4.0);
+        let brighter = Color::new_f(8.0, 8.0, 8.0);
+
+        let mut image = vec![vec![Color::zero(), bright, brighter]];
+        tone_map(&mut image, 1.0);
+        assert_eq!(image[0][0], Color::zero());
+        assert_eq!(image[0][1], Color::new_f(0.8, 0.8, 0.8));
+        assert_eq!(image[0][2], Color::new_f(8.0 / 9.0, 8.0 / 9.0, 8.0 / 9.0));
+
+        // Exposure is applied before the curve
+        let mut image = vec![vec![bright]];
+        tone_map(&mut image, 0.25);
+        assert_eq!(image[0][0], Color::new_f(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn downsample_averages_blocks() {
+        let white = Color::new(255, 255, 255);
+        let black = Color::zero();
+        let image = vec![vec![white, black], vec![black, white]];
+
+        let out = downsample(&image, 2);
+        assert_eq!(out, vec![vec![Color::new_f(0.5, 0.5, 0.5)]]);
+    }
+
+    #[test]
+    fn coverage_of_blocks() {
+        let mask = vec![
+            vec![true, false, true, true],
+            vec![false, false, true, true],
+        ];
+        assert_eq!(coverage(&mask, 2), vec![vec![0.25, 1.0]]);
+        assert_eq!(coverage(&mask, 1)[1], vec![0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn supersampled_edge_is_smoother() {
+        let aliased = render_edge(16, 16);
+        assert_eq!(count_blended(&aliased), 0);
+
+        let supersampled = downsample(&render_edge(32, 32), 2);
+        assert_eq!(supersampled.len(), 16);
+        assert_eq!(supersampled[0].len(), 16);
+        assert!(count_blended(&supersampled) > 0);
+    }
+}
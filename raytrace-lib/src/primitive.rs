@@ -1,23 +1,63 @@
-use crate::{ray::Ray, vec3::Vec3, FLOAT_EPS};
+use crate::{aabb::Aabb, ray::Ray, transform::Transform, vec3::Vec3, DISTANCE_EPS, FLOAT_EPS};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Intersection {
     /// The position of the intersection.
     pub pos: Vec3,
     /// The normal at the intersection point.
+    /// Always points against the direction of the ray.
     pub normal: Vec3,
+    /// True if the ray hit the side of the surface that the outward normal points to.
+    pub front_face: bool,
+    /// Texture coordinates at the intersection, if the surface has any.
+    pub uv: Option<(f64, f64)>,
+}
+
+impl Intersection {
+    /// Create an intersection where `outward_normal` is the geometric normal
+    /// of the surface. The stored normal is flipped to face the ray if needed.
+    pub fn new(ray: &Ray, pos: Vec3, outward_normal: Vec3) -> Self {
+        let front_face = ray.direction().dot(outward_normal) < 0.0;
+        let normal = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+
+        Self {
+            pos,
+            normal,
+            front_face,
+            uv: None,
+        }
+    }
 }
 
 pub trait Intersectable {
     /// Check if the ray intersects the intersectable.
     fn intersection(&self, ray: &Ray) -> Option<Intersection>;
+
+    /// Returns every intersection in front of the ray origin,
+    /// sorted by distance from the origin.
+    ///
+    /// Closed surfaces return where the ray enters and exits them.
+    /// By default only the closest intersection is returned.
+    fn all_intersections(&self, ray: &Ray) -> Vec<Intersection> {
+        self.intersection(ray).into_iter().collect()
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+// Triangles are kept inline, boxing them would cost an allocation per triangle
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Primitive {
     Sphere(Sphere),
     Triangle(Triangle),
     Plane(Plane),
+    Mesh(Mesh),
+    Cuboid(Cuboid),
+    Disk(Disk),
 }
 
 impl Intersectable for Primitive {
@@ -26,12 +66,152 @@ impl Intersectable for Primitive {
             Self::Sphere(s) => s.intersection(ray),
             Self::Triangle(s) => s.intersection(ray),
             Self::Plane(s) => s.intersection(ray),
+            Self::Mesh(s) => s.intersection(ray),
+            Self::Cuboid(s) => s.intersection(ray),
+            Self::Disk(s) => s.intersection(ray),
+        }
+    }
+
+    fn all_intersections(&self, ray: &Ray) -> Vec<Intersection> {
+        match self {
+            Self::Sphere(s) => s.all_intersections(ray),
+            Self::Triangle(s) => s.all_intersections(ray),
+            Self::Plane(s) => s.all_intersections(ray),
+            Self::Mesh(s) => s.all_intersections(ray),
+            Self::Cuboid(s) => s.all_intersections(ray),
+            Self::Disk(s) => s.all_intersections(ray),
+        }
+    }
+}
+
+impl Primitive {
+    /// Relative cost of one intersection test against the primitive.
+    ///
+    /// Used as a cost model when building acceleration structures.
+    pub fn intersection_cost(&self) -> f64 {
+        match self {
+            Self::Plane(_) => 0.5,
+            Self::Disk(_) => 0.75,
+            Self::Sphere(_) | Self::Cuboid(_) => 1.0,
+            Self::Triangle(_) => 1.5,
+            // The bounding sphere is always tested, the triangles only on a hit
+            Self::Mesh(m) => 1.0 + 1.5 * m.triangles.len() as f64,
+        }
+    }
+
+    /// Same as [`Intersectable::intersection`], adding the number of primitives
+    /// tested to `tested`. Meshes count every triangle they test.
+    pub fn counted_intersection(&self, ray: &Ray, tested: &mut usize) -> Option<Intersection> {
+        match self {
+            Self::Mesh(m) => m.closest_intersection(ray, tested),
+            _ => {
+                *tested += 1;
+                self.intersection(ray)
+            }
+        }
+    }
+
+    /// Returns a sphere enclosing the primitive, or `None` if it is unbounded.
+    pub fn bounding_sphere(&self) -> Option<Sphere> {
+        match self {
+            Self::Sphere(s) => Some(*s),
+            Self::Triangle(t) => Some(Sphere::enclosing(&[t.t1, t.t2, t.t3])),
+            Self::Plane(_) => None,
+            Self::Mesh(m) => Some(m.bounds),
+            Self::Cuboid(c) => Some(Sphere::new(c.center(), (c.max - c.min).length() / 2.0)),
+            Self::Disk(d) => Some(Sphere::new(d.center, d.radius)),
+        }
+    }
+
+    /// Returns an axis-aligned box enclosing the primitive, or `None` if it is unbounded.
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        match self {
+            Self::Sphere(s) => Some(s.bounding_box()),
+            Self::Triangle(t) => Some(Aabb::enclosing(&[t.t1, t.t2, t.t3])),
+            Self::Plane(_) => None,
+            // Looser than the triangles' box, but doesn't visit every vertex
+            Self::Mesh(m) => Some(m.bounds.bounding_box()),
+            Self::Cuboid(c) => Some(Aabb::new(c.min, c.max)),
+            Self::Disk(d) => Some(d.bounding_box()),
+        }
+    }
+
+    /// Returns true if `point` lies inside the volume enclosed by the primitive.
+    ///
+    /// A plane encloses the half-space opposite of its normal.
+    /// Meshes are assumed to be closed, see [`Mesh::contains`].
+    /// Triangles and disks don't enclose any volume.
+    pub fn contains(&self, point: Vec3) -> bool {
+        match self {
+            Self::Sphere(s) => (point - s.center).length_squared() < s.radius * s.radius,
+            Self::Plane(p) => (point - p.point).dot(p.normal) < 0.0,
+            Self::Cuboid(c) => {
+                (0..3).all(|axis| c.min[axis] < point[axis] && point[axis] < c.max[axis])
+            }
+            Self::Mesh(m) => m.contains(point),
+            Self::Triangle(_) | Self::Disk(_) => false,
+        }
+    }
+
+    /// Returns the primitive uniformly scaled by `factor` around the origin.
+    pub fn scaled(&self, factor: f64) -> Self {
+        match self {
+            Self::Sphere(s) => Sphere::new(s.center * factor, s.radius * factor).into(),
+            Self::Triangle(t) => t.scaled(factor).into(),
+            Self::Plane(p) => Plane::new(p.point * factor, p.normal).into(),
+            Self::Mesh(m) => {
+                Mesh::new(m.triangles.iter().map(|t| t.scaled(factor)).collect()).into()
+            }
+            Self::Cuboid(c) => Cuboid::new(c.min * factor, c.max * factor).into(),
+            Self::Disk(d) => Disk::new(d.center * factor, d.normal, d.radius * factor.abs()).into(),
+        }
+    }
+
+    /// Returns the primitive moved by `offset`.
+    pub fn translated(&self, offset: Vec3) -> Self {
+        match self {
+            Self::Sphere(s) => Sphere::new(s.center + offset, s.radius).into(),
+            Self::Triangle(t) => t.translated(offset).into(),
+            Self::Plane(p) => Plane::new(p.point + offset, p.normal).into(),
+            Self::Mesh(m) => {
+                Mesh::new(m.triangles.iter().map(|t| t.translated(offset)).collect()).into()
+            }
+            Self::Cuboid(c) => Cuboid::new(c.min + offset, c.max + offset).into(),
+            Self::Disk(d) => Disk::new(d.center + offset, d.normal, d.radius).into(),
+        }
+    }
+
+    /// Returns the primitive with `transform` baked into its coordinates.
+    ///
+    /// For static geometry this is cheaper than transforming every ray.
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        match self {
+            Self::Sphere(s) => s.transformed(transform).into(),
+            Self::Triangle(t) => t.transformed(transform).into(),
+            Self::Plane(p) => p.transformed(transform).into(),
+            Self::Disk(d) => d.transformed(transform).into(),
+            Self::Mesh(m) => Mesh::new(
+                m.triangles
+                    .iter()
+                    .map(|t| t.transformed(transform))
+                    .collect(),
+            )
+            .into(),
+            // Rotated boxes are no longer axis-aligned
+            Self::Cuboid(c) => Mesh::new(
+                c.triangles()
+                    .iter()
+                    .map(|t| t.transformed(transform))
+                    .collect(),
+            )
+            .into(),
         }
     }
 }
 
 /// An infinite plane described by a point and a normal.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Plane {
     point: Vec3,
     normal: Vec3,
@@ -51,6 +231,34 @@ impl Plane {
         // z = - d / c
         Self::new(Vec3::new(0.0, 0.0, -d / c), Vec3::new(a, b, c))
     }
+
+    /// Returns the plane with `transform` applied to its point and normal.
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        Self::new(
+            transform.apply_point(self.point),
+            transform.apply_vector(self.normal),
+        )
+    }
+
+    /// Returns the texture coordinates of `point` projected onto the plane.
+    ///
+    /// `u` and `v` are the distances from the point of the plane along two
+    /// perpendicular directions in it, so textures repeat every unit.
+    /// Planes facing up have `u` along x and `v` along z.
+    pub fn uv_at(&self, point: Vec3) -> (f64, f64) {
+        let n = self.normal;
+        // The axis least aligned with the normal gives the most stable direction
+        let axis = if n.x.abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let u_axis = (axis - n * n.dot(axis)).normalize();
+        let v_axis = u_axis.cross(n);
+
+        let offset = point - self.point;
+        (offset.dot(u_axis), offset.dot(v_axis))
+    }
 }
 
 impl From<Plane> for Primitive {
@@ -59,8 +267,11 @@ impl From<Plane> for Primitive {
     }
 }
 
-impl Intersectable for Plane {
-    fn intersection(&self, ray: &Ray) -> Option<Intersection> {
+impl Plane {
+    /// Same as [`Intersectable::intersection`], ignoring hits closer than `min_dist`
+    /// to the ray origin. The rounding error of the hit distance is added on top,
+    /// so rays starting on the plane never hit it again.
+    pub fn intersection_beyond(&self, ray: &Ray, min_dist: f64) -> Option<Intersection> {
         // Implemented from the wikipedia page about line-plane intersections.
         // <https://en.wikipedia.org/wiki/Line%E2%80%93plane_intersection#Algebraic_form>
 
@@ -79,32 +290,116 @@ impl Intersectable for Plane {
 
         let d = (p0 - l0).dot(n) / ln;
 
-        // Intersection behind the ray origin
-        if d < FLOAT_EPS {
+        // The rounding error of `d` grows with the magnitude of the coordinates
+        // and is amplified for grazing rays where `ln` is small.
+        let tolerance = min_dist + 8.0 * f64::EPSILON * (p0.length() + l0.length()) / ln.abs();
+
+        // Intersection behind or too close to the ray origin
+        if d < tolerance {
             return None;
         }
 
+        let pos = l0 + (l * d);
         Some(Intersection {
-            pos: l0 + (l * d),
-            normal: n,
+            uv: Some(self.uv_at(pos)),
+            ..Intersection::new(ray, pos, n)
         })
     }
 }
 
+impl Intersectable for Plane {
+    fn intersection(&self, ray: &Ray) -> Option<Intersection> {
+        self.intersection_beyond(ray, ray.min_dist().max(DISTANCE_EPS))
+    }
+}
+
+/// A flat circle described by its center, normal and radius.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Disk {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub radius: f64,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64) -> Self {
+        Self {
+            center,
+            normal: normal.normalize(),
+            radius,
+        }
+    }
+
+    /// Returns the disk with `transform` applied to its center, normal and radius.
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        Self::new(
+            transform.apply_point(self.center),
+            transform.apply_vector(self.normal),
+            self.radius * transform.scale.abs(),
+        )
+    }
+
+    /// Returns the axis-aligned box that the disk fits in.
+    /// Along each axis the disk reaches `radius` times the sine of its angle to the normal.
+    pub fn bounding_box(&self) -> Aabb {
+        let extent = |n: f64| self.radius * (1.0 - n * n).max(0.0).sqrt();
+        let half = Vec3::new(
+            extent(self.normal.x),
+            extent(self.normal.y),
+            extent(self.normal.z),
+        );
+        Aabb::enclosing(&[self.center - half, self.center + half])
+    }
+}
+
+impl From<Disk> for Primitive {
+    fn from(value: Disk) -> Self {
+        Self::Disk(value)
+    }
+}
+
+impl Intersectable for Disk {
+    fn intersection(&self, ray: &Ray) -> Option<Intersection> {
+        Plane::new(self.center, self.normal)
+            .intersection(ray)
+            .filter(|hit| (hit.pos - self.center).length_squared() <= self.radius * self.radius)
+    }
+}
+
+/// The algorithm used to intersect rays with a triangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriangleIntersection {
+    /// Fast, but rays can slip through the edge shared by two triangles.
+    #[default]
+    MollerTrumbore,
+    /// Never misses both triangles that share an edge, at a small extra cost.
+    Watertight,
+}
+
 /// A triangle in 3d-space.
 ///
 /// The three vectors makes up each corner of the triangle.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Triangle {
     pub t1: Vec3,
     pub t2: Vec3,
     pub t3: Vec3,
-    // The normal of the triangles plane.
+    // The unit normal of the triangles plane.
     pub normal: Vec3,
     // Line from `t1` to `t2`.
     pub l12: Vec3,
     // Line from `t1` to `t3`.
     pub l13: Vec3,
+    /// Texture coordinates at `t1`, `t2` and `t3`.
+    pub uvs: Option<[(f64, f64); 3]>,
+    /// Unit normals at `t1`, `t2` and `t3`, interpolated across the face
+    /// so meshes look smooth. Without them `normal` is used everywhere.
+    pub normals: Option<[Vec3; 3]>,
+    /// The algorithm used by [`Intersectable::intersection`].
+    pub intersection_mode: TriangleIntersection,
 }
 
 impl Triangle {
@@ -120,6 +415,103 @@ impl Triangle {
             normal,
             l12,
             l13,
+            uvs: None,
+            normals: None,
+            intersection_mode: TriangleIntersection::default(),
+        }
+    }
+
+    /// Returns the triangle uniformly scaled by `factor` around the origin.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            uvs: self.uvs,
+            normals: self.normals,
+            intersection_mode: self.intersection_mode,
+            ..Self::new(self.t1 * factor, self.t2 * factor, self.t3 * factor)
+        }
+    }
+
+    /// Returns the triangle moved by `offset`.
+    pub fn translated(&self, offset: Vec3) -> Self {
+        Self {
+            uvs: self.uvs,
+            normals: self.normals,
+            intersection_mode: self.intersection_mode,
+            ..Self::new(self.t1 + offset, self.t2 + offset, self.t3 + offset)
+        }
+    }
+
+    /// Returns the triangle with `transform` applied to its corners.
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        Self {
+            uvs: self.uvs,
+            normals: self
+                .normals
+                .map(|normals| normals.map(|n| n.rotate(&transform.rotation))),
+            intersection_mode: self.intersection_mode,
+            ..Self::new(
+                transform.apply_point(self.t1),
+                transform.apply_point(self.t2),
+                transform.apply_point(self.t3),
+            )
+        }
+    }
+
+    pub fn with_intersection_mode(mut self, mode: TriangleIntersection) -> Self {
+        self.intersection_mode = mode;
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Set the normals at the corners, they are normalized.
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals.map(Vec3::normalize));
+        self
+    }
+
+    /// Interpolate the normal at the barycentric coordinates `u`, `v`,
+    /// which are the weights of `t2` and `t3`.
+    ///
+    /// Without vertex normals the normal of the face is returned.
+    pub fn normal_at(&self, u: f64, v: f64) -> Vec3 {
+        match self.normals {
+            Some([a, b, c]) => ((1.0 - u - v) * a + u * b + v * c).normalize(),
+            None => self.normal,
+        }
+    }
+
+    /// Returns the intersection at the barycentric coordinates `u`, `v`.
+    /// Which side was hit is decided by the face normal,
+    /// the interpolated normal is then flipped to the same side.
+    fn intersection_at(&self, ray: &Ray, pos: Vec3, u: f64, v: f64) -> Intersection {
+        let mut intersection = Intersection::new(ray, pos, self.normal);
+        if self.normals.is_some() {
+            let normal = self.normal_at(u, v);
+            intersection.normal = if intersection.front_face {
+                normal
+            } else {
+                -normal
+            };
+        }
+        intersection.uv = Some(self.uv_at(u, v));
+        intersection
+    }
+
+    /// Interpolate the texture coordinates at the barycentric coordinates `u`, `v`,
+    /// which are the weights of `t2` and `t3`.
+    ///
+    /// Without texture coordinates the barycentric coordinates are used directly.
+    pub fn uv_at(&self, u: f64, v: f64) -> (f64, f64) {
+        match self.uvs {
+            Some([a, b, c]) => {
+                let w = 1.0 - u - v;
+                (w * a.0 + u * b.0 + v * c.0, w * a.1 + u * b.1 + v * c.1)
+            }
+            None => (u, v),
         }
     }
 }
@@ -130,8 +522,75 @@ impl From<Triangle> for Primitive {
     }
 }
 
+impl Triangle {
+    /// Watertight ray-triangle intersection.
+    ///
+    /// The triangle is transformed into a space where the ray points along
+    /// the z axis, the edge tests then only compare signs of 2d cross products
+    /// which are computed the same way for both triangles sharing an edge.
+    /// <https://jcgt.org/published/0002/01/05/>
+    fn watertight_intersection(&self, ray: &Ray) -> Option<Intersection> {
+        let axis = |v: Vec3, k: usize| [v.x, v.y, v.z][k];
+        let dir = ray.direction();
+
+        // Use the largest component of the direction as z
+        let kz = (0..3)
+            .max_by(|&a, &b| axis(dir, a).abs().total_cmp(&axis(dir, b).abs()))
+            .unwrap_or(2);
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        // Keep the winding of the triangle
+        if axis(dir, kz) < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        let sx = axis(dir, kx) / axis(dir, kz);
+        let sy = axis(dir, ky) / axis(dir, kz);
+        let sz = 1.0 / axis(dir, kz);
+
+        // Vertices relative to the ray origin, sheared so the ray is the z axis
+        let shear = |p: Vec3| {
+            let p = p - ray.origin;
+            (
+                axis(p, kx) - sx * axis(p, kz),
+                axis(p, ky) - sy * axis(p, kz),
+                sz * axis(p, kz),
+            )
+        };
+        let (ax, ay, az) = shear(self.t1);
+        let (bx, by, bz) = shear(self.t2);
+        let (cx, cy, cz) = shear(self.t3);
+
+        // Scaled barycentric coordinates of the ray
+        let u = cx * by - cy * bx;
+        let v = ax * cy - ay * cx;
+        let w = bx * ay - by * ax;
+
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+            return None;
+        }
+
+        let det = u + v + w;
+        if det == 0.0 {
+            return None;
+        }
+
+        // Intersection behind or too close to the ray origin
+        let distance = (u * az + v * bz + w * cz) / det;
+        if distance < ray.min_dist().max(FLOAT_EPS) {
+            return None;
+        }
+
+        let pos = ray.origin + dir * distance;
+        Some(self.intersection_at(ray, pos, v / det, w / det))
+    }
+}
+
 impl Intersectable for Triangle {
     fn intersection(&self, ray: &Ray) -> Option<Intersection> {
+        if self.intersection_mode == TriangleIntersection::Watertight {
+            return self.watertight_intersection(ray);
+        }
+
         // The Möller–Trumbore intersection algorithm.
         // <https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm>
         let ray_dir = ray.direction();
@@ -163,23 +622,21 @@ impl Intersectable for Triangle {
         // Distance along the ray travelled
         let distance = f * self.l13.dot(q);
 
-        // Intersection behind ray origin
-        if distance < FLOAT_EPS {
+        // Intersection behind or too close to the ray origin
+        if distance < ray.min_dist().max(FLOAT_EPS) {
             return None;
         }
 
         let out_intersection_point = ray_origin + ray_dir * distance;
-        Some(Intersection {
-            pos: out_intersection_point,
-            normal: self.normal,
-        })
+        Some(self.intersection_at(ray, out_intersection_point, u, v))
     }
 }
 
 /// A triangle in 3d-space.
 ///
 /// The three vectors makes up each corner of the triangle.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f64,
@@ -189,6 +646,50 @@ impl Sphere {
     pub fn new(center: Vec3, radius: f64) -> Self {
         Self { center, radius }
     }
+
+    /// Returns a sphere centered at the mean of `points` that contains all of them.
+    /// Not the smallest such sphere, but cheap to compute.
+    pub fn enclosing(points: &[Vec3]) -> Self {
+        if points.is_empty() {
+            return Self::new(Vec3::zero(), 0.0);
+        }
+
+        let sum = points.iter().fold(Vec3::zero(), |sum, &p| sum + p);
+        let center = sum * (1.0 / points.len() as f64);
+        let radius = points
+            .iter()
+            .map(|&p| (p - center).length())
+            .fold(0.0, f64::max);
+
+        // Keep points on the surface inside despite rounding
+        Self::new(center, radius * (1.0 + 1e-9) + FLOAT_EPS)
+    }
+
+    /// Returns the sphere with its center transformed and its radius scaled.
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        Self::new(
+            transform.apply_point(self.center),
+            self.radius * transform.scale.abs(),
+        )
+    }
+
+    /// Returns the axis-aligned box that the sphere fits in.
+    pub fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - r, self.center + r)
+    }
+
+    /// Returns the texture coordinates of `point` on the surface.
+    ///
+    /// `u` follows the longitude around the y axis and `v` the latitude
+    /// from the bottom pole to the top pole, both in `[0, 1]`.
+    /// <https://en.wikipedia.org/wiki/UV_mapping#Finding_UV_on_a_sphere>
+    pub fn uv_at(&self, point: Vec3) -> (f64, f64) {
+        let Vec3 { x, y, z } = (point - self.center).normalize();
+        let u = 0.5 + z.atan2(x) / (2.0 * std::f64::consts::PI);
+        let v = 0.5 + y.clamp(-1.0, 1.0).asin() / std::f64::consts::PI;
+        (u, v)
+    }
 }
 
 impl From<Sphere> for Primitive {
@@ -197,8 +698,10 @@ impl From<Sphere> for Primitive {
     }
 }
 
-impl Intersectable for Sphere {
-    fn intersection(&self, ray: &Ray) -> Option<Intersection> {
+impl Sphere {
+    /// Returns the distances along the ray to where it crosses the sphere,
+    /// the smallest first. Either may be behind the ray origin.
+    fn ray_distances(&self, ray: &Ray) -> Option<(f64, f64)> {
         // From: <https://www.scratchapixel.com/lessons/3d-basic-rendering/minimal-ray-tracer-rendering-simple-shapes/ray-sphere-intersection.html>
         // Where the direction of the ray is a unit vector.
 
@@ -243,20 +746,233 @@ impl Intersectable for Sphere {
             }
         };
 
-        // Minimum but not negative
-        let t = match (t0 < 0.0, t1 < 0.0) {
+        Some((t0.min(t1), t0.max(t1)))
+    }
+
+    /// Returns the intersection at distance `t` along the ray.
+    fn intersection_at(&self, ray: &Ray, t: f64) -> Intersection {
+        let pos = ray.origin + ray.direction() * t;
+        let normal = (pos - self.center).normalize();
+
+        Intersection {
+            uv: Some(self.uv_at(pos)),
+            ..Intersection::new(ray, pos, normal)
+        }
+    }
+}
+
+impl Intersectable for Sphere {
+    fn intersection(&self, ray: &Ray) -> Option<Intersection> {
+        let (t0, t1) = self.ray_distances(ray)?;
+
+        // Minimum but not behind or too close to the ray origin
+        let min = ray.min_dist();
+        let t = match (t0 < min, t1 < min) {
             (true, true) => {
                 return None;
             }
             (true, _) => t1,
-            (_, true) => t0,
-            _ => t0.min(t1),
+            _ => t0,
         };
 
-        let pos = ray.origin + dir * t;
-        let normal = (pos - self.center).normalize();
+        Some(self.intersection_at(ray, t))
+    }
 
-        Some(Intersection { pos, normal })
+    fn all_intersections(&self, ray: &Ray) -> Vec<Intersection> {
+        let Some((t0, t1)) = self.ray_distances(ray) else {
+            return vec![];
+        };
+
+        // A ray grazing the sphere only touches it once
+        let distances: &[f64] = if t0 == t1 { &[t0] } else { &[t0, t1] };
+        distances
+            .iter()
+            .filter(|&&t| t >= ray.min_dist())
+            .map(|&t| self.intersection_at(ray, t))
+            .collect()
+    }
+}
+
+/// A collection of triangles tested as one primitive.
+///
+/// Rays that miss the bounding sphere skip the triangles entirely.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mesh {
+    triangles: Vec<Triangle>,
+    bounds: Sphere,
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        let vertices: Vec<Vec3> = triangles.iter().flat_map(|t| [t.t1, t.t2, t.t3]).collect();
+
+        Self {
+            bounds: Sphere::enclosing(&vertices),
+            triangles,
+        }
+    }
+
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.triangles
+    }
+
+    /// Returns true if `point` lies inside the closed surface made by the triangles.
+    ///
+    /// A ray from inside crosses the surface an odd number of times.
+    /// The direction of the ray is skewed so it doesn't run along the edges of
+    /// axis-aligned meshes, where it could cross two triangles at once.
+    /// <https://en.wikipedia.org/wiki/Point_in_polygon#Ray_casting_algorithm>
+    pub fn contains(&self, point: Vec3) -> bool {
+        Ray::new(point, Vec3::new(0.267, 0.534, 0.802))
+            .is_ok_and(|ray| self.all_intersections(&ray).len() % 2 == 1)
+    }
+
+    /// Find the closest triangle hit, adding the number of triangles tested to `tested`.
+    fn closest_intersection(&self, ray: &Ray, tested: &mut usize) -> Option<Intersection> {
+        // Sphere intersections also return a hit when the ray starts inside
+        self.bounds.intersection(ray)?;
+
+        *tested += self.triangles.len();
+        self.triangles
+            .iter()
+            .filter_map(|t| t.intersection(ray))
+            .min_by(|a, b| {
+                let a = (a.pos - ray.origin).length_squared();
+                let b = (b.pos - ray.origin).length_squared();
+                a.total_cmp(&b)
+            })
+    }
+}
+
+impl From<Mesh> for Primitive {
+    fn from(value: Mesh) -> Self {
+        Self::Mesh(value)
+    }
+}
+
+impl Intersectable for Mesh {
+    fn intersection(&self, ray: &Ray) -> Option<Intersection> {
+        self.closest_intersection(ray, &mut 0)
+    }
+
+    fn all_intersections(&self, ray: &Ray) -> Vec<Intersection> {
+        if self.bounds.intersection(ray).is_none() {
+            return vec![];
+        }
+
+        let mut hits: Vec<Intersection> = self
+            .triangles
+            .iter()
+            .filter_map(|t| t.intersection(ray))
+            .collect();
+        hits.sort_by(|a, b| {
+            let a = (a.pos - ray.origin).length_squared();
+            let b = (b.pos - ray.origin).length_squared();
+            a.total_cmp(&b)
+        });
+        hits
+    }
+}
+
+/// An axis-aligned box described by its two extreme corners.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cuboid {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Cuboid {
+    /// Create a box from two opposite corners, in any order.
+    pub fn new(a: Vec3, b: Vec3) -> Self {
+        let Aabb { min, max } = Aabb::new(a, b);
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Returns the outward normal of the face closest to `point`.
+    fn normal_at(&self, point: Vec3) -> Vec3 {
+        let mut normal = Vec3::zero();
+        let mut closest = f64::INFINITY;
+        for axis in 0..3 {
+            for (face, sign) in [(self.min[axis], -1.0), (self.max[axis], 1.0)] {
+                let dist = (point[axis] - face).abs();
+                if dist < closest {
+                    closest = dist;
+                    normal = Vec3::new(
+                        if axis == 0 { sign } else { 0.0 },
+                        if axis == 1 { sign } else { 0.0 },
+                        if axis == 2 { sign } else { 0.0 },
+                    );
+                }
+            }
+        }
+        normal
+    }
+
+    /// Returns the 12 triangles making up the faces of the box.
+    pub fn triangles(&self) -> Vec<Triangle> {
+        let corner = |x: bool, y: bool, z: bool| {
+            Vec3::new(
+                if x { self.max.x } else { self.min.x },
+                if y { self.max.y } else { self.min.y },
+                if z { self.max.z } else { self.min.z },
+            )
+        };
+        // Each face as four corners counter-clockwise seen from outside
+        let faces = [
+            [(0, 0, 0), (0, 0, 1), (0, 1, 1), (0, 1, 0)],
+            [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)],
+            [(0, 0, 0), (1, 0, 0), (1, 0, 1), (0, 0, 1)],
+            [(0, 1, 0), (0, 1, 1), (1, 1, 1), (1, 1, 0)],
+            [(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 0, 0)],
+            [(0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1)],
+        ];
+        faces
+            .iter()
+            .flat_map(|face| {
+                let [a, b, c, d] = face.map(|(x, y, z)| corner(x == 1, y == 1, z == 1));
+                [Triangle::new(a, b, c), Triangle::new(a, c, d)]
+            })
+            .collect()
+    }
+}
+
+impl From<Cuboid> for Primitive {
+    fn from(value: Cuboid) -> Self {
+        Self::Cuboid(value)
+    }
+}
+
+impl Intersectable for Cuboid {
+    fn intersection(&self, ray: &Ray) -> Option<Intersection> {
+        self.all_intersections(ray).into_iter().next()
+    }
+
+    /// Uses the slab method, the entry distance is `0.0` when the ray starts inside.
+    fn all_intersections(&self, ray: &Ray) -> Vec<Intersection> {
+        let Some((near, far)) = Aabb::new(self.min, self.max).hit_interval(ray) else {
+            return vec![];
+        };
+
+        let min = ray.min_dist();
+        let distances: &[f64] = if near > min && near < far {
+            &[near, far]
+        } else {
+            &[far]
+        };
+        distances
+            .iter()
+            .filter(|&&t| t > min)
+            .map(|&t| {
+                let pos = ray.origin + ray.direction() * t;
+                Intersection::new(ray, pos, self.normal_at(pos))
+            })
+            .collect()
     }
 }
 
@@ -265,9 +981,104 @@ mod tests {
     use super::*;
     use crate::vec3::Vec3;
 
+    fn unit_quad_mesh(z: f64) -> Mesh {
+        let [a, b, c, d] = [
+            Vec3::new(0.0, 0.0, z),
+            Vec3::new(1.0, 0.0, z),
+            Vec3::new(1.0, 1.0, z),
+            Vec3::new(0.0, 1.0, z),
+        ];
+        Mesh::new(vec![Triangle::new(a, b, c), Triangle::new(a, c, d)])
+    }
+
+    #[test]
+    fn sphere_entry_and_exit() {
+        let sphere = Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0);
+        let dir = Vec3::new(0.0, 0.0, 1.0);
+
+        let ray = Ray::new(Vec3::zero(), dir).unwrap();
+        let hits = sphere.all_intersections(&ray);
+        assert_eq!(hits.len(), 2);
+        assert!((hits[0].pos - Vec3::new(0.0, 0.0, 4.0)).length() < 1e-9);
+        assert!((hits[1].pos - Vec3::new(0.0, 0.0, 6.0)).length() < 1e-9);
+        assert!(hits[0].front_face && !hits[1].front_face);
+        assert_eq!(hits[0], sphere.intersection(&ray).unwrap());
+
+        // Starting inside only the exit is in front of the ray
+        let inside = Ray::new(Vec3::new(0.0, 0.0, 5.0), dir).unwrap();
+        assert_eq!(sphere.all_intersections(&inside).len(), 1);
+
+        let plane = Plane::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(plane.all_intersections(&ray).len(), 1);
+        let through_quad = Ray::new(Vec3::new(0.75, 0.25, 0.0), dir).unwrap();
+        assert_eq!(
+            unit_quad_mesh(5.0).all_intersections(&through_quad).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn mesh_bounding_sphere_reject() {
+        let mesh = unit_quad_mesh(5.0);
+        let bounds = Primitive::from(mesh.clone()).bounding_sphere().unwrap();
+        assert!(mesh
+            .triangles()
+            .iter()
+            .flat_map(|t| [t.t1, t.t2, t.t3])
+            .all(|p| (p - bounds.center).length() <= bounds.radius));
+
+        let mut tested = 0;
+        let miss = Ray::new(Vec3::new(10.0, 10.0, 0.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert_eq!(mesh.closest_intersection(&miss, &mut tested), None);
+        assert_eq!(tested, 0);
+
+        let hit = Ray::new(Vec3::new(0.25, 0.75, 0.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        let intersection = mesh.closest_intersection(&hit, &mut tested).unwrap();
+        assert_eq!(tested, 2);
+        assert!((intersection.pos - Vec3::new(0.25, 0.75, 5.0)).length() < 1e-9);
+
+        // Scaling moves the bounds along with the triangles
+        let scaled = Primitive::from(mesh).scaled(2.0);
+        let hit = Ray::new(Vec3::new(1.5, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((scaled.intersection(&hit).unwrap().pos.z - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn intersection_cost() {
+        let sphere: Primitive = Sphere::new(Vec3::zero(), 1.0).into();
+        let triangle: Primitive = Triangle::new(
+            Vec3::zero(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .into();
+
+        assert!(sphere.intersection_cost() < triangle.intersection_cost());
+        assert!(sphere.intersection_cost() > 0.0);
+    }
+
+    #[test]
+    fn contains_point() {
+        let sphere: Primitive = Sphere::new(Vec3::new(1.0, 2.0, 3.0), 2.0).into();
+        assert!(sphere.contains(Vec3::new(1.0, 3.0, 3.5)));
+        assert!(!sphere.contains(Vec3::new(1.0, 5.0, 3.0)));
+
+        let plane: Primitive = Plane::new(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0)).into();
+        assert!(plane.contains(Vec3::new(4.0, -1.0, 2.0)));
+        assert!(!plane.contains(Vec3::new(4.0, 1.0, 2.0)));
+
+        let cuboid = Cuboid::new(Vec3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 2.0, 3.0));
+        let mesh: Primitive = Mesh::new(cuboid.triangles()).into();
+        assert!(mesh.contains(Vec3::new(0.0, 1.0, 2.0)));
+        assert!(mesh.contains(Vec3::new(0.9, 0.1, 2.9)));
+        // Outside, but within the bounding sphere of the mesh
+        assert!(!mesh.contains(Vec3::new(1.2, 1.9, 2.0)));
+        assert!(!mesh.contains(Vec3::new(0.0, 5.0, 2.0)));
+    }
+
     #[test]
     fn triangle_intersect() {
-        let ray = Ray::new(Vec3::new(-1.5, -0.5, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Vec3::new(-1.5, -0.5, -1.0), Vec3::new(1.0, 1.0, 1.0)).unwrap();
 
         let tri = Triangle::new(
             Vec3::new(-3.0, -2.0, 1.0),
@@ -287,10 +1098,181 @@ mod tests {
         assert!(tri.intersection(&ray).is_none());
     }
 
+    #[test]
+    fn watertight_shared_edge() {
+        let watertight = TriangleIntersection::Watertight;
+        let [a, b] = [Vec3::new(0.1, 0.7, 3.3), Vec3::new(2.9, -1.3, 4.1)];
+        let left =
+            Triangle::new(a, b, Vec3::new(0.3, -2.1, 3.7)).with_intersection_mode(watertight);
+        let right =
+            Triangle::new(b, a, Vec3::new(2.7, 1.9, 3.9)).with_intersection_mode(watertight);
+
+        // Same results as Möller–Trumbore away from the edges
+        let ray = Ray::new(Vec3::new(-1.5, -0.5, -1.0), Vec3::new(1.0, 1.0, 1.0)).unwrap();
+        let tri = Triangle::new(
+            Vec3::new(-3.0, -2.0, 1.0),
+            Vec3::new(3.0, 2.0, 1.0),
+            Vec3::new(-3.0, 2.0, -2.0),
+        );
+        let hit = tri
+            .with_intersection_mode(watertight)
+            .intersection(&ray)
+            .unwrap();
+        assert_eq!(hit.pos, tri.intersection(&ray).unwrap().pos);
+        assert_eq!(hit.uv, tri.intersection(&ray).unwrap().uv);
+
+        let origin = Vec3::new(0.37, 0.11, -0.93);
+        for i in 0..=1000 {
+            let t = f64::from(i) / 1000.0;
+            let ray = Ray::new(origin, a + (b - a) * t - origin).unwrap();
+            assert!(
+                left.intersection(&ray).is_some() || right.intersection(&ray).is_some(),
+                "Ray {i} slipped through the shared edge"
+            );
+        }
+    }
+
+    #[test]
+    fn triangle_normal_is_unit() {
+        let tri = Triangle::new(
+            Vec3::new(-3.0, -2.0, 1.0),
+            Vec3::new(3.0, 2.0, 1.0),
+            Vec3::new(-3.0, 2.0, -2.0),
+        );
+        assert!((tri.normal.length() - 1.0).abs() < FLOAT_EPS);
+
+        let ray = Ray::new(Vec3::new(-1.5, -0.5, -1.0), Vec3::new(1.0, 1.0, 1.0)).unwrap();
+        let normal = tri.intersection(&ray).unwrap().normal;
+        assert!((normal.length() - 1.0).abs() < FLOAT_EPS);
+    }
+
+    #[test]
+    fn triangle_smooth_normals() {
+        let flat = Triangle::new(
+            Vec3::zero(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let normals = [
+            Vec3::new(-1.0, -1.0, 1.0).normalize(),
+            Vec3::new(1.0, 0.0, 1.0).normalize(),
+            Vec3::new(0.0, 1.0, 1.0).normalize(),
+        ];
+        let smooth = flat.with_normals(normals);
+        let watertight = smooth.with_intersection_mode(TriangleIntersection::Watertight);
+
+        // The centroid and a point closer to `t2`
+        for (u, v) in [(1.0 / 3.0, 1.0 / 3.0), (0.6, 0.2)] {
+            let [a, b, c] = normals;
+            let expected = ((1.0 - u - v) * a + u * b + v * c).normalize();
+            let above = Ray::new(Vec3::new(u, v, 2.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+            let below = Ray::new(Vec3::new(u, v, -2.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+
+            assert_eq!(flat.intersection(&above).unwrap().normal, flat.normal);
+            for tri in [smooth, watertight] {
+                let hit = tri.intersection(&above).unwrap();
+                assert!(hit.front_face);
+                assert_eq!(hit.normal, expected);
+                assert_ne!(hit.normal, flat.normal);
+
+                // Flipped towards the ray like the face normal
+                let hit = tri.intersection(&below).unwrap();
+                assert!(!hit.front_face);
+                assert_eq!(hit.normal, -expected);
+            }
+        }
+    }
+
+    #[test]
+    fn triangle_uv_at_vertices() {
+        let tri = Triangle::new(
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(3.0, -1.0, 0.0),
+            Vec3::new(-1.0, 2.0, 0.0),
+        );
+        let textured = tri.with_uvs([(0.25, 0.5), (1.0, 0.0), (0.5, 1.0)]);
+        let watertight = tri.with_intersection_mode(TriangleIntersection::Watertight);
+
+        let corners = [tri.t1, tri.t2, tri.t3];
+        let canonical = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
+        for i in 0..3 {
+            let ray = Ray::new(
+                corners[i] + Vec3::new(0.0, 0.0, -1.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            )
+            .unwrap();
+            assert_eq!(tri.intersection(&ray).unwrap().uv, Some(canonical[i]));
+            assert_eq!(
+                watertight.intersection(&ray).unwrap().uv,
+                Some(canonical[i])
+            );
+            assert_eq!(
+                textured.intersection(&ray).unwrap().uv,
+                textured.uvs.map(|uvs| uvs[i])
+            );
+        }
+    }
+
+    #[test]
+    fn sphere_uv_at_equator_seam() {
+        let sphere = Sphere::new(Vec3::new(1.0, 2.0, 3.0), 2.0);
+        let uv_from = |origin: Vec3| {
+            let ray = Ray::new(origin, sphere.center - origin).unwrap();
+            sphere.intersection(&ray).unwrap().uv.unwrap()
+        };
+        let close = |(u, v): (f64, f64), (eu, ev): (f64, f64)| {
+            (u - eu).abs() < 1e-6 && (v - ev).abs() < 1e-6
+        };
+
+        // The seam is on the -x side, `u` wraps from 1 back to 0 across it
+        let seam = sphere.center + Vec3::new(-10.0, 0.0, 0.0);
+        assert!(close(uv_from(seam + Vec3::new(0.0, 0.0, 1e-9)), (1.0, 0.5)));
+        assert!(close(uv_from(seam - Vec3::new(0.0, 0.0, 1e-9)), (0.0, 0.5)));
+
+        // Around the equator
+        assert!(close(
+            uv_from(sphere.center + Vec3::new(10.0, 0.0, 0.0)),
+            (0.5, 0.5)
+        ));
+        assert!(close(
+            uv_from(sphere.center + Vec3::new(0.0, 0.0, 10.0)),
+            (0.75, 0.5)
+        ));
+        assert!(close(
+            uv_from(sphere.center + Vec3::new(0.0, 0.0, -10.0)),
+            (0.25, 0.5)
+        ));
+
+        // Poles, where `u` is arbitrary
+        let top = uv_from(sphere.center + Vec3::new(0.0, 10.0, 0.0));
+        let bottom = uv_from(sphere.center + Vec3::new(0.0, -10.0, 0.0));
+        assert!((top.1 - 1.0).abs() < 1e-6 && bottom.1.abs() < 1e-6);
+    }
+
+    #[test]
+    fn plane_uv_projection() {
+        let floor = Plane::new(Vec3::new(1.0, -2.0, 1.0), Vec3::new(0.0, 1.0, 0.0));
+        let ray = Ray::new(Vec3::new(3.5, 4.0, -0.25), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        let (u, v) = floor.intersection(&ray).unwrap().uv.unwrap();
+        assert!((u - 2.5).abs() < 1e-9 && (v + 1.25).abs() < 1e-9);
+
+        // Coordinates move one unit per unit along the plane, whatever its normal
+        let tilted = Plane::new(Vec3::new(0.3, 0.1, -2.0), Vec3::new(1.0, 0.2, -0.4));
+        let hit = |x: f64| {
+            let ray = Ray::new(Vec3::new(x, 5.0, 5.0), Vec3::new(-1.0, -0.5, -1.0)).unwrap();
+            tilted.intersection(&ray).unwrap()
+        };
+        let (a, b) = (hit(5.0), hit(6.0));
+        let (ua, va) = a.uv.unwrap();
+        let (ub, vb) = b.uv.unwrap();
+        let uv_dist = ((ua - ub).powi(2) + (va - vb).powi(2)).sqrt();
+        assert!((uv_dist - (a.pos - b.pos).length()).abs() < 1e-9);
+    }
+
     #[test]
     fn sphere_intersect() {
         let sphere = Sphere::new(Vec3::new(-7.04, 5.16, 2.0), 1.5);
-        let ray = Ray::new(Vec3::new(-0.19, 1.82, 1.0), Vec3::new(-2.0, 1.31, 0.48));
+        let ray = Ray::new(Vec3::new(-0.19, 1.82, 1.0), Vec3::new(-2.0, 1.31, 0.48)).unwrap();
 
         assert_eq!(
             sphere.intersection(&ray).unwrap().pos,
@@ -298,67 +1280,289 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sphere_front_face() {
+        let sphere = Sphere::new(Vec3::zero(), 1.0);
+
+        // From outside
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        let hit = sphere.intersection(&ray).unwrap();
+        assert!(hit.front_face);
+        assert_eq!(hit.pos, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, -1.0));
+
+        // From inside
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        let hit = sphere.intersection(&ray).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.pos, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
     #[test]
     fn plane_parallel() {
         let p = Plane::from_cartesian(-3.0, -2.0, 1.0, -4.0);
-        let ray = Ray::new(Vec3::new(2.0, -3.0, 4.0), Vec3::new(2.0, -4.0, -2.0));
+        let ray = Ray::new(Vec3::new(2.0, -3.0, 4.0), Vec3::new(2.0, -4.0, -2.0)).unwrap();
         assert_eq!(p.intersection(&ray), None);
 
         let p = Plane::from_cartesian(2.0, -3.0, 5.0, -10.0);
-        let ray = Ray::new(Vec3::new(-1.0, 7.0, 4.0), Vec3::new(1.0, -7.0, -4.6));
+        let ray = Ray::new(Vec3::new(-1.0, 7.0, 4.0), Vec3::new(1.0, -7.0, -4.6)).unwrap();
         assert_eq!(p.intersection(&ray), None);
     }
 
+    #[test]
+    fn plane_grazing_rays_from_surface() {
+        // Rays that start on a distant plane should never hit it again,
+        // even when they leave at a grazing angle.
+        let normal = Vec3::new(0.1, 1.0, 0.3).normalize();
+        let p = Plane::new(Vec3::new(1.0e6, 2.0e5, -3.0e6), normal);
+
+        let tangent = normal.cross(Vec3::new(1.0, 0.0, 0.0)).normalize();
+        let bitangent = normal.cross(tangent);
+
+        for i in 0..200 {
+            let offset = (i as f64 - 100.0) * 1234.5;
+            let origin = p.point + tangent * offset + bitangent * (offset * 0.7);
+
+            for k in 1..50 {
+                let angle = k as f64 * 1e-5;
+                for side in [1.0, -1.0] {
+                    let dir = tangent + normal * (side * angle);
+                    let ray = Ray::new(origin, dir).unwrap();
+                    assert_eq!(p.intersection(&ray), None, "offset {offset}, angle {angle}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reflected_ray_leaves_plane() {
+        let normal = Vec3::new(0.2, 1.0, -0.1).normalize();
+        let p = Plane::new(Vec3::new(3.0, -2.0, 7.0), normal);
+
+        for i in 0..100 {
+            let i = i as f64;
+            let incoming = Vec3::new(i.sin(), -1.0 - i * 0.1, i.cos()).normalize();
+            let ray = Ray::new(Vec3::new(i, 5.0, i * 0.5), incoming).unwrap();
+            let hit = p.intersection(&ray).unwrap();
+
+            // Starts exactly on the plane without any offset
+            let reflected = Ray::new(hit.pos, incoming.reflect(hit.normal)).unwrap();
+            assert_eq!(p.intersection(&reflected), None, "{i}");
+        }
+
+        // Hits closer than the minimum distance are skipped
+        let ray = Ray::new(p.point + normal * 0.5, -normal).unwrap();
+        assert!(p.intersection_beyond(&ray, 0.4).is_some());
+        assert_eq!(p.intersection_beyond(&ray, 0.6), None);
+    }
+
     #[test]
     fn plane_intersect() {
         let p = Plane::from_cartesian(2.0, 1.0, -1.0, -45.0);
-        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.0, 3.0, 4.0));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.0, 3.0, 4.0)).unwrap();
+        // Texture coordinates are tested in `plane_uv_projection`
         assert_eq!(
-            p.intersection(&ray),
+            p.intersection(&ray)
+                .map(|hit| Intersection { uv: None, ..hit }),
             Some(Intersection {
                 pos: Vec3::new(27.0, 27.0, 36.0),
-                normal: Vec3::new(2.0, 1.0, -1.0).normalize()
+                normal: -Vec3::new(2.0, 1.0, -1.0).normalize(),
+                front_face: false,
+                uv: None,
             })
         );
 
         let p = Plane::from_cartesian(-2.0, 6.0, -3.0, -35.0);
-        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(8.0, 8.0, 4.0));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(8.0, 8.0, 4.0)).unwrap();
         assert_eq!(
-            p.intersection(&ray),
+            p.intersection(&ray)
+                .map(|hit| Intersection { uv: None, ..hit }),
             Some(Intersection {
                 pos: Vec3::new(14.0, 14.0, 7.0),
-                normal: Vec3::new(-2.0, 6.0, -3.0).normalize()
+                normal: -Vec3::new(-2.0, 6.0, -3.0).normalize(),
+                front_face: false,
+                uv: None,
             })
         );
 
         let p = Plane::from_cartesian(2.0, -1.0, 3.0, -15.0);
-        let ray = Ray::new(Vec3::new(4.0, -1.0, 3.0), Vec3::new(1.0, 8.0, -2.0));
+        let ray = Ray::new(Vec3::new(4.0, -1.0, 3.0), Vec3::new(1.0, 8.0, -2.0)).unwrap();
         assert_eq!(
-            p.intersection(&ray),
+            p.intersection(&ray)
+                .map(|hit| Intersection { uv: None, ..hit }),
             Some(Intersection {
                 pos: Vec3::new(4.25, 1.0, 2.5),
-                normal: Vec3::new(2.0, -1.0, 3.0).normalize()
+                normal: Vec3::new(2.0, -1.0, 3.0).normalize(),
+                front_face: true,
+                uv: None,
             })
         );
 
         let p = Plane::from_cartesian(2.0, -3.0, 1.0, -14.0);
-        let ray = Ray::new(Vec3::new(1.0, 0.0, -1.0), Vec3::new(2.0, -3.0, 0.0));
+        let ray = Ray::new(Vec3::new(1.0, 0.0, -1.0), Vec3::new(2.0, -3.0, 0.0)).unwrap();
         assert_eq!(
-            p.intersection(&ray),
+            p.intersection(&ray)
+                .map(|hit| Intersection { uv: None, ..hit }),
             Some(Intersection {
                 pos: Vec3::new(3.0, -3.0, -1.0),
-                normal: Vec3::new(2.0, -3.0, 1.0).normalize()
+                normal: -Vec3::new(2.0, -3.0, 1.0).normalize(),
+                front_face: false,
+                uv: None,
             })
         );
 
         let p = Plane::from_cartesian(-5.0, 4.0, -1.0, 4.0);
-        let ray = Ray::new(Vec3::new(1.0, -2.0, 1.0), Vec3::new(-3.0, 3.0, 3.0));
+        let ray = Ray::new(Vec3::new(1.0, -2.0, 1.0), Vec3::new(-3.0, 3.0, 3.0)).unwrap();
         assert_eq!(
-            p.intersection(&ray),
+            p.intersection(&ray)
+                .map(|hit| Intersection { uv: None, ..hit }),
             Some(Intersection {
                 pos: Vec3::new(-0.25, -0.75, 2.25),
-                normal: Vec3::new(-5.0, 4.0, -1.0).normalize()
+                normal: -Vec3::new(-5.0, 4.0, -1.0).normalize(),
+                front_face: false,
+                uv: None,
             })
         );
     }
+
+    #[test]
+    fn baked_transform_matches_ray_transform() {
+        let transform = Transform {
+            scale: 2.5,
+            rotation: crate::rotation::Rotation::from_axis_angle(Vec3::new(1.0, 2.0, 0.5), 0.7),
+            translation: Vec3::new(3.0, -1.0, 4.0),
+        };
+        let inverse = transform.inverse();
+
+        let triangle = Triangle::new(
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let primitives: [Primitive; 3] = [
+            triangle.into(),
+            Sphere::new(Vec3::new(0.2, 0.1, 0.0), 0.5).into(),
+            Plane::new(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0)).into(),
+        ];
+
+        // Aim at a point of the object so every ray hits
+        let target = transform.apply_point(Vec3::new(0.1, 0.0, 0.0));
+        let origin = Vec3::new(-2.0, 6.0, -9.0);
+        let ray = Ray::new(origin, target - origin).unwrap();
+        let local_ray = Ray::new(
+            inverse.apply_point(ray.origin),
+            inverse.apply_vector(ray.direction()),
+        )
+        .unwrap();
+
+        for primitive in primitives {
+            let baked = primitive
+                .transformed(&transform)
+                .intersection(&ray)
+                .unwrap();
+            let local = primitive.intersection(&local_ray).unwrap();
+
+            let pos = transform.apply_point(local.pos);
+            let normal = transform.apply_vector(local.normal).normalize();
+            assert!((baked.pos - pos).length() < 1e-9, "{primitive:?}");
+            assert!((baked.normal - normal).length() < 1e-9, "{primitive:?}");
+        }
+    }
+
+    #[test]
+    fn cuboid_faces_have_outward_normals() {
+        let cuboid = Cuboid::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(-1.0, 0.0, 1.0));
+        let center = cuboid.center();
+        let mesh: Primitive = Mesh::new(cuboid.triangles()).into();
+
+        for axis in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ] {
+            for outward in [axis, -axis] {
+                // Slightly off center so the ray doesn't pass through an edge of the mesh
+                let origin = center + outward * 5.0 + Vec3::new(0.1, 0.2, 0.3);
+                let ray = Ray::new(origin, -outward).unwrap();
+
+                let hit = cuboid.intersection(&ray).unwrap();
+                assert_eq!(hit.normal, outward);
+                assert!(hit.front_face);
+                assert!(((hit.pos - center).dot(outward) - 1.0).abs() < 1e-9);
+
+                let mesh_hit = mesh.intersection(&ray).unwrap();
+                assert_eq!(mesh_hit.pos, hit.pos);
+                assert_eq!(mesh_hit.normal, hit.normal);
+
+                // From inside the ray exits through the opposite face
+                let inside = Ray::new(center, -outward).unwrap();
+                let exit = cuboid.intersection(&inside).unwrap();
+                assert_eq!(exit.normal, outward);
+                assert!(!exit.front_face);
+                assert_eq!(cuboid.all_intersections(&ray).len(), 2);
+            }
+        }
+
+        let miss = Ray::new(Vec3::new(5.0, 5.0, 5.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!(cuboid.intersection(&miss).is_none());
+        assert!(Primitive::from(cuboid).contains(center));
+    }
+
+    #[test]
+    fn disk_rejects_hits_outside_radius() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let disk = Disk::new(Vec3::new(0.0, 1.0, 5.0), normal * 3.0, 2.0);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+
+        let inside = Ray::new(Vec3::new(1.5, 4.0, 5.0), down).unwrap();
+        let hit = disk.intersection(&inside).unwrap();
+        assert_eq!(hit.pos, Vec3::new(1.5, 1.0, 5.0));
+        assert_eq!(hit.normal, normal);
+        assert!(hit.front_face);
+
+        // Hits the plane of the disk, but outside the radius
+        let outside = Ray::new(Vec3::new(1.5, 4.0, 6.5), down).unwrap();
+        assert!(Plane::new(disk.center, disk.normal)
+            .intersection(&outside)
+            .is_some());
+        assert!(disk.intersection(&outside).is_none());
+
+        let bounds = disk.bounding_box();
+        assert!(bounds.contains(Vec3::new(2.0, 1.0, 5.0)));
+        assert!(bounds.contains(Vec3::new(0.0, 1.0, 3.0)));
+        assert!((bounds.max.y - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn min_dist_skips_near_hits() {
+        let dir = Vec3::new(0.0, 0.0, 1.0);
+        let ray = Ray::new(Vec3::new(0.25, 0.25, 0.0), dir).unwrap();
+        let near = ray.with_min_dist(1.0);
+        let far = ray.with_min_dist(3.0);
+
+        let plane: Primitive = Plane::new(Vec3::new(0.0, 0.0, 0.5), -dir).into();
+        let triangle: Primitive = Triangle::new(
+            Vec3::new(0.0, 0.0, 0.5),
+            Vec3::new(1.0, 0.0, 0.5),
+            Vec3::new(0.0, 1.0, 0.5),
+        )
+        .into();
+        let cuboid: Primitive =
+            Cuboid::new(Vec3::new(0.0, 0.0, 0.5), Vec3::new(1.0, 1.0, 2.0)).into();
+        let sphere: Primitive = Sphere::new(Vec3::new(0.25, 0.25, 1.5), 1.0).into();
+
+        for flat in [plane, triangle] {
+            assert!(flat.intersection(&ray).is_some(), "{flat:?}");
+            assert!(flat.intersection(&near).is_none(), "{flat:?}");
+        }
+        // Closed surfaces are hit where the ray exits them instead
+        for closed in [cuboid, sphere] {
+            assert!((closed.intersection(&ray).unwrap().pos.z - 0.5).abs() < 1e-9);
+            let exit = closed.intersection(&near).unwrap();
+            assert!(!exit.front_face, "{closed:?}");
+            assert_eq!(closed.all_intersections(&near).len(), 1);
+            assert!(closed.intersection(&far).is_none(), "{closed:?}");
+        }
+    }
 }
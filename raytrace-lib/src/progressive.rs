@@ -0,0 +1,101 @@
+//! Rendering in passes where every pass refines the image.
+
+use crate::Color;
+
+/// The running average of the images of all passes rendered so far.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Accumulator {
+    image: Vec<Vec<Color>>,
+    passes: u32,
+}
+
+impl Accumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of passes added so far.
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Add the image of one pass.
+    /// All passes must have the same dimensions.
+    pub fn add_pass(&mut self, image: &[Vec<Color>]) {
+        self.passes += 1;
+
+        if self.passes == 1 {
+            self.image = image.to_vec();
+            return;
+        }
+
+        let weight = 1.0 / f64::from(self.passes);
+        for (row, new_row) in self.image.iter_mut().zip(image) {
+            for (color, &new) in row.iter_mut().zip(new_row) {
+                *color = color.lerp(new, weight);
+            }
+        }
+    }
+
+    /// Returns the average of all passes, linear and not clamped.
+    pub fn image(&self) -> &[Vec<Color>] {
+        &self.image
+    }
+
+    /// Returns the width and height of the passes, `(0, 0)` before the first pass.
+    pub fn dimensions(&self) -> (usize, usize) {
+        let width = self.image.first().map_or(0, Vec::len);
+        (width, self.image.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Material, primitive::Sphere, Camera, Object, Raytracer, Vec3};
+
+    #[test]
+    fn passes_average() {
+        let camera =
+            Camera::new(32, 16, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+        let material = Material {
+            color: Color::new_f(1.0, 1.0, 1.0),
+            ambient: Color::new_f(1.0, 1.0, 1.0),
+            ..crate::material::MaterialTemplate::Red.get_material(Color::zero())
+        };
+        let world = [
+            Object::new(Sphere::new(Vec3::new(0.0, 0.0, 5.0), 0.3).into(), material)
+                .with_velocity(Vec3::new(0.5, 0.0, 0.0)),
+        ];
+
+        let mut raytracer = Raytracer::new(camera, 1);
+        raytracer.set_shutter(1.0);
+        raytracer.set_time_samples(2);
+
+        let mut accumulator = Accumulator::new();
+        let passes: Vec<_> = (0..2)
+            .map(|pass| {
+                raytracer.set_seed(pass);
+                let image = raytracer.raycast(&world, &[]);
+                accumulator.add_pass(&image);
+                image
+            })
+            .collect();
+
+        assert_ne!(passes[0], passes[1]);
+        assert_eq!(accumulator.passes(), 2);
+
+        let expected: Vec<Vec<Color>> = passes[0]
+            .iter()
+            .zip(&passes[1])
+            .map(|(a, b)| {
+                a.iter()
+                    .zip(b)
+                    .map(|(&a, &b)| Color::average(&[a, b]))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(accumulator.image(), expected.as_slice());
+    }
+}
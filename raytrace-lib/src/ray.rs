@@ -1,9 +1,4 @@
-use crate::{
-    color::Color,
-    object::Object,
-    primitive::{Intersectable, Intersection},
-    vec3::Vec3,
-};
+use crate::{color::Color, object::Object, primitive::Intersectable, vec3::Vec3};
 
 /// A line that start from `origin` and moves in the direction of `dir`.
 #[derive(Debug, Clone, Copy)]
@@ -13,6 +8,8 @@ pub struct Ray {
     /// Direction of the ray.
     /// Will always be a unit vector.
     dir: Vec3,
+    /// Hits closer than this to the origin are ignored.
+    min_dist: f64,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -23,14 +20,57 @@ pub struct RayHit {
     pub intersection: Vec3,
     /// The normal of the reflection.
     pub normal: Vec3,
+    /// True if the outside of the surface was hit.
+    pub front_face: bool,
+    /// Texture coordinates at the intersection, if the surface has any.
+    pub uv: Option<(f64, f64)>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayNewError {
+    NonFinite,
+    DirectionZero,
+}
+
+impl std::fmt::Display for RayNewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NonFinite => write!(f, "Ray origin and direction must be finite"),
+            Self::DirectionZero => write!(f, "Ray direction must be non-zero"),
+        }
+    }
+}
+
+impl std::error::Error for RayNewError {}
+
 impl Ray {
-    pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Self {
+    /// Create a new ray, normalizing `direction`.
+    ///
+    /// Fails if `origin` or `direction` contains NaN or infinity,
+    /// or if `direction` is the zero vector.
+    pub fn new(origin: Vec3, direction: Vec3) -> Result<Self, RayNewError> {
+        if !origin.is_finite() || !direction.is_finite() {
+            return Err(RayNewError::NonFinite);
+        }
+        if direction.length_squared() == 0.0 {
+            return Err(RayNewError::DirectionZero);
+        }
+
+        Ok(Self {
             origin,
             dir: direction.normalize(),
-        }
+            min_dist: 0.0,
+        })
+    }
+
+    /// Ignore hits closer than `min_dist` to the origin,
+    /// e.g. the surface a secondary ray starts on.
+    pub fn with_min_dist(self, min_dist: f64) -> Self {
+        Self { min_dist, ..self }
+    }
+
+    pub fn min_dist(&self) -> f64 {
+        self.min_dist
     }
 
     pub fn direction(&self) -> Vec3 {
@@ -38,12 +78,12 @@ impl Ray {
     }
 
     pub fn trace(&self, object: &Object) -> Option<RayHit> {
-        object
-            .intersection(self)
-            .map(|Intersection { pos, normal }| RayHit {
-                color: object.material.color,
-                intersection: pos,
-                normal,
-            })
+        object.intersection(self).map(|hit| RayHit {
+            color: object.material.color,
+            intersection: hit.pos,
+            normal: hit.normal,
+            front_face: hit.front_face,
+            uv: hit.uv,
+        })
     }
 }
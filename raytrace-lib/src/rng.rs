@@ -0,0 +1,50 @@
+/// Returns a seed for stream number `stream` derived from `seed`.
+///
+/// Uses a splitmix64 step so neighbouring streams get unrelated seeds.
+/// <https://prng.di.unimi.it/splitmix64.c>
+pub fn stream_seed(seed: u64, stream: u64) -> u64 {
+    let mut z = seed.wrapping_add(stream.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A small, fast and deterministic pseudo random number generator.
+///
+/// Not suitable for anything but spreading samples.
+/// <https://en.wikipedia.org/wiki/Xorshift>
+#[derive(Debug, Clone)]
+pub struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    /// A seed of zero would only ever produce zeros and is replaced by a constant.
+    pub fn new(seed: u64) -> Self {
+        let state = if seed == 0 {
+            0x9e37_79b9_7f4a_7c15
+        } else {
+            seed
+        };
+        Self { state }
+    }
+
+    /// Create the generator for stream number `stream`, e.g. a pixel index.
+    /// The result only depends on `seed` and `stream`, not on the order
+    /// that streams are created in.
+    pub fn for_stream(seed: u64, stream: u64) -> Self {
+        Self::new(stream_seed(seed, stream))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
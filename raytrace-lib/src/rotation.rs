@@ -1,15 +1,40 @@
 use crate::{vec3::Vec3, UP_DIRECTION};
 
+/// Squared sine of the angle below which a direction counts as parallel to up.
+const PARALLEL_EPS: f64 = 1e-12;
+
 /// A 3d rotation matrix
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rotation {
     pub(crate) matrix: [[f64; 3]; 3],
 }
 
+/// Same as [`Rotation::look_along`] with the default up direction.
 impl From<Vec3> for Rotation {
     fn from(v: Vec3) -> Self {
+        Self::look_along(v, UP_DIRECTION)
+    }
+}
+
+impl Rotation {
+    /// The rotation that turns the z axis to `v` and keeps the y axis
+    /// as close to `up` as possible. Neither needs to be normalized.
+    ///
+    /// When `v` is (nearly) parallel to `up` there is no such rotation,
+    /// the y axis is then kept close to the z axis, or the x axis if `v` is along z.
+    pub fn look_along(v: Vec3, up: Vec3) -> Self {
         let v = v.normalize();
-        let x_axis = UP_DIRECTION.cross(v).normalize();
+        let mut x_axis = up.cross(v);
+        if x_axis.length_squared() < PARALLEL_EPS * up.length_squared() {
+            let up = if v.z.abs() < 0.9 {
+                Vec3::new(0.0, 0.0, 1.0)
+            } else {
+                Vec3::new(1.0, 0.0, 0.0)
+            };
+            x_axis = up.cross(v);
+        }
+        let x_axis = x_axis.normalize();
         let Vec3 {
             x: yx,
             y: yy,
@@ -27,8 +52,113 @@ impl From<Vec3> for Rotation {
     }
 }
 
+impl Rotation {
+    /// The rotation that leaves every vector unchanged.
+    pub const fn identity() -> Self {
+        Self {
+            matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// Counter-clockwise rotation of `angle` radians around `axis`.
+    ///
+    /// <https://en.wikipedia.org/wiki/Rotation_matrix#Rotation_matrix_from_axis_and_angle>
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Self {
+        let Vec3 { x, y, z } = axis.normalize();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+
+        Self {
+            matrix: [
+                [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y],
+                [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x],
+                [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos],
+            ],
+        }
+    }
+}
+
+impl Rotation {
+    /// Returns the rotation that undoes `self`, the transpose of the matrix.
+    pub fn inverse(&self) -> Self {
+        let mut matrix = [[0.0; 3]; 3];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = self.matrix[j][i];
+            }
+        }
+        Self { matrix }
+    }
+}
+
+/// `a * b` rotates by `b` first and then by `a`.
+impl std::ops::Mul for Rotation {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut matrix = [[0.0; 3]; 3];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = (0..3).map(|k| self.matrix[i][k] * rhs.matrix[k][j]).sum();
+            }
+        }
+        Self { matrix }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn from_vec() {}
+
+    #[test]
+    fn looking_straight_up_or_down() {
+        let is_orthonormal = |rotation: Rotation| {
+            let m = rotation.matrix;
+            let column = |j: usize| Vec3::new(m[0][j], m[1][j], m[2][j]);
+            let finite = m.iter().flatten().all(|c| c.is_finite());
+            let unit = (0..3).all(|j| (column(j).length() - 1.0).abs() < 1e-9);
+            let perpendicular = (0..3).all(|j| column(j).dot(column((j + 1) % 3)).abs() < 1e-9);
+            finite && unit && perpendicular
+        };
+
+        for v in [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, -5.0, 0.0),
+            Vec3::new(1e-9, 1.0, 0.0),
+        ] {
+            let rotation = Rotation::from(v);
+            assert!(is_orthonormal(rotation), "{v:?}");
+            assert_eq!(Vec3::new(0.0, 0.0, 1.0).rotate(&rotation), v.normalize());
+        }
+
+        // Up along z falls back to x
+        let rotation = Rotation::look_along(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(is_orthonormal(rotation));
+        assert_eq!(
+            Vec3::new(0.0, 1.0, 0.0).rotate(&rotation),
+            Vec3::new(1.0, 0.0, 0.0)
+        );
+
+        // Nothing changes away from the poles
+        let v = Vec3::new(0.3, -0.8, 0.5);
+        assert!(is_orthonormal(Rotation::from(v)));
+        assert!(Vec3::new(0.0, 1.0, 0.0).rotate(&Rotation::from(v)).y > 0.0);
+    }
+
+    #[test]
+    fn compose_rotations() {
+        let quarter =
+            Rotation::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), std::f64::consts::FRAC_PI_2);
+        let half = quarter * quarter;
+
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        let expected = Vec3::new(-1.0, -2.0, 3.0);
+        assert!((v.rotate(&half) - expected).length() < 1e-9);
+        assert!((v.rotate(&quarter).rotate(&quarter) - expected).length() < 1e-9);
+        assert_eq!(quarter * Rotation::identity(), quarter);
+    }
 }
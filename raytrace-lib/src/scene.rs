@@ -0,0 +1,234 @@
+use crate::{Camera, Color, Light, Object, Raytracer};
+
+/// A difference between two scenes.
+///
+/// Objects and lights are matched by their index in the scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneChange {
+    ObjectAdded(usize),
+    ObjectRemoved(usize),
+    ObjectModified(usize),
+    LightAdded(usize),
+    LightRemoved(usize),
+    LightModified(usize),
+    CameraModified,
+}
+
+impl std::fmt::Display for SceneChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ObjectAdded(i) => write!(f, "+ object {i}"),
+            Self::ObjectRemoved(i) => write!(f, "- object {i}"),
+            Self::ObjectModified(i) => write!(f, "~ object {i}"),
+            Self::LightAdded(i) => write!(f, "+ light {i}"),
+            Self::LightRemoved(i) => write!(f, "- light {i}"),
+            Self::LightModified(i) => write!(f, "~ light {i}"),
+            Self::CameraModified => write!(f, "~ camera"),
+        }
+    }
+}
+
+/// Compare two lists element by element and push the differences to `changes`.
+fn diff_slices<T: PartialEq>(
+    changes: &mut Vec<SceneChange>,
+    old: &[T],
+    new: &[T],
+    [added, removed, modified]: [fn(usize) -> SceneChange; 3],
+) {
+    let common = old.len().min(new.len());
+
+    changes.extend((0..common).filter(|&i| old[i] != new[i]).map(modified));
+    changes.extend((common..old.len()).map(removed));
+    changes.extend((common..new.len()).map(added));
+}
+
+/// A raytracer together with the world and lights that it renders.
+///
+/// The scene is kept between renders so objects and lights can be
+/// modified in place, e.g. between the frames of an animation.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scene {
+    raytracer: Raytracer,
+    objects: Vec<Object>,
+    lights: Vec<Light>,
+}
+
+impl Scene {
+    pub fn new(raytracer: Raytracer, objects: Vec<Object>, lights: Vec<Light>) -> Self {
+        Self {
+            raytracer,
+            objects,
+            lights,
+        }
+    }
+
+    pub fn raytracer(&self) -> &Raytracer {
+        &self.raytracer
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn camera(&self) -> &Camera {
+        self.raytracer.camera()
+    }
+
+    pub fn raytracer_mut(&mut self) -> &mut Raytracer {
+        &mut self.raytracer
+    }
+
+    pub fn objects_mut(&mut self) -> &mut Vec<Object> {
+        &mut self.objects
+    }
+
+    pub fn lights_mut(&mut self) -> &mut Vec<Light> {
+        &mut self.lights
+    }
+
+    /// Returns the objects, lights and raytracer in the order the scene parser
+    /// returns them.
+    pub fn into_parts(self) -> (Vec<Object>, Vec<Light>, Raytracer) {
+        (self.objects, self.lights, self.raytracer)
+    }
+
+    /// Returns the changes needed to turn `self` into `other`.
+    pub fn diff(&self, other: &Self) -> Vec<SceneChange> {
+        use SceneChange::*;
+
+        let mut changes = vec![];
+        if self.raytracer.camera() != other.raytracer.camera() {
+            changes.push(CameraModified);
+        }
+
+        diff_slices(
+            &mut changes,
+            &self.objects,
+            &other.objects,
+            [ObjectAdded, ObjectRemoved, ObjectModified],
+        );
+        diff_slices(
+            &mut changes,
+            &self.lights,
+            &other.lights,
+            [LightAdded, LightRemoved, LightModified],
+        );
+
+        changes
+    }
+
+    /// Render the scene with [`Raytracer::raycast`].
+    pub fn render(&self) -> Vec<Vec<Color>> {
+        self.raytracer.raycast(&self.objects, &self.lights)
+    }
+
+    /// Render the scene with [`Raytracer::par_raycast`].
+    pub fn par_render(&self) -> Vec<Vec<Color>> {
+        self.raytracer.par_raycast(&self.objects, &self.lights)
+    }
+}
+
+impl From<(Vec<Object>, Vec<Light>, Raytracer)> for Scene {
+    fn from((objects, lights, raytracer): (Vec<Object>, Vec<Light>, Raytracer)) -> Self {
+        Self::new(raytracer, objects, lights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::MaterialTemplate,
+        primitive::{Primitive, Sphere},
+        Material, Vec3,
+    };
+
+    #[test]
+    fn diff_moved_sphere() {
+        let scene = || {
+            let camera =
+                Camera::new(16, 16, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+            let material = MaterialTemplate::Bronze.get_material(Color::new(255, 0, 0));
+            Scene::new(
+                Raytracer::new(camera, 1),
+                vec![
+                    Object::new(Sphere::new(Vec3::new(-1.0, 0.0, 5.0), 1.0).into(), material),
+                    Object::new(Sphere::new(Vec3::new(1.0, 0.0, 5.0), 1.0).into(), material),
+                ],
+                vec![Light::new(Vec3::zero(), 1.0)],
+            )
+        };
+
+        let old = scene();
+        let mut new = scene();
+        assert!(old.diff(&new).is_empty());
+
+        if let Primitive::Sphere(s) = &mut new.objects_mut()[1].primitive {
+            s.center = Vec3::new(2.0, 0.0, 5.0);
+        }
+        assert_eq!(old.diff(&new), vec![SceneChange::ObjectModified(1)]);
+
+        new.lights_mut().clear();
+        assert_eq!(
+            old.diff(&new),
+            vec![SceneChange::ObjectModified(1), SceneChange::LightRemoved(0)]
+        );
+        assert_eq!(
+            new.diff(&old),
+            vec![SceneChange::ObjectModified(1), SceneChange::LightAdded(0)]
+        );
+    }
+
+    #[test]
+    fn accessors_reflect_inputs() {
+        let camera =
+            Camera::new(16, 16, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+        let material = MaterialTemplate::Red.get_material(Color::new(255, 0, 0));
+        let objects = vec![Object::new(Sphere::new(Vec3::zero(), 1.0).into(), material)];
+        let lights = vec![Light::new(Vec3::new(0.0, 5.0, 0.0), 0.5)];
+
+        let scene = Scene::new(
+            Raytracer::new(camera.clone(), 1),
+            objects.clone(),
+            lights.clone(),
+        );
+
+        assert_eq!(scene.objects(), objects.as_slice());
+        assert_eq!(scene.lights(), lights.as_slice());
+        assert_eq!(scene.camera(), &camera);
+        assert_eq!(scene.raytracer().camera(), &camera);
+    }
+
+    #[test]
+    fn move_object_between_renders() {
+        let camera =
+            Camera::new(16, 16, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+        let material = Material {
+            color: Color::new_f(1.0, 1.0, 1.0),
+            ambient: Color::new_f(1.0, 1.0, 1.0),
+            ..Default::default()
+        };
+        let sphere = Sphere::new(Vec3::new(-1.0, 0.0, 5.0), 1.0);
+
+        let mut scene = Scene::new(
+            Raytracer::new(camera, 1),
+            vec![Object::new(sphere.into(), material)],
+            vec![],
+        );
+
+        let first = scene.render();
+
+        if let Primitive::Sphere(s) = &mut scene.objects_mut()[0].primitive {
+            s.center = Vec3::new(1.0, 0.0, 5.0);
+        }
+
+        let second = scene.render();
+        assert_ne!(first, second);
+        assert_eq!(second, scene.par_render());
+    }
+}
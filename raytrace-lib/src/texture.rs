@@ -0,0 +1,107 @@
+use crate::Color;
+
+/// Width of grid lines as a fraction of the space between two lines.
+const GRID_LINE_WIDTH: f64 = 0.1;
+
+/// A color that varies over the surface of an object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Texture {
+    /// Lines at every multiple of `1 / divisions` in both `u` and `v`.
+    /// Useful for checking the texture coordinates of a surface.
+    LatLongGrid {
+        line_color: Color,
+        bg_color: Color,
+        divisions: u32,
+    },
+    /// Squares of `a` and `b` that alternate every `scale` in both `u` and `v`.
+    /// On planes the texture coordinates are in world units.
+    Checker { a: Color, b: Color, scale: f64 },
+}
+
+impl Texture {
+    /// Returns the color at the texture coordinates `(u, v)`.
+    pub fn sample(&self, (u, v): (f64, f64)) -> Color {
+        match *self {
+            Self::LatLongGrid {
+                line_color,
+                bg_color,
+                divisions,
+            } => {
+                let divisions = f64::from(divisions.max(1));
+                let on_line = |t: f64| {
+                    let offset = (t * divisions).rem_euclid(1.0);
+                    offset.min(1.0 - offset) < GRID_LINE_WIDTH / 2.0
+                };
+
+                if on_line(u) || on_line(v) {
+                    line_color
+                } else {
+                    bg_color
+                }
+            }
+            Self::Checker { a, b, scale } => {
+                let cell = (u / scale).floor() + (v / scale).floor();
+                if cell.rem_euclid(2.0) < 1.0 {
+                    a
+                } else {
+                    b
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        primitive::{Intersectable, Plane},
+        ray::Ray,
+        Vec3,
+    };
+
+    #[test]
+    fn grid_lines() {
+        let line = Color::new_f(1.0, 1.0, 1.0);
+        let bg = Color::new_f(0.0, 0.0, 0.5);
+        let grid = Texture::LatLongGrid {
+            line_color: line,
+            bg_color: bg,
+            divisions: 4,
+        };
+
+        assert_eq!(grid.sample((0.25, 0.4)), line);
+        assert_eq!(grid.sample((0.4, 0.749)), line);
+        assert_eq!(grid.sample((0.999, 0.1)), line);
+        assert_eq!(grid.sample((0.1, 0.1)), bg);
+        assert_eq!(grid.sample((0.375, 0.625)), bg);
+    }
+
+    #[test]
+    fn checker_alternates() {
+        let a = Color::new_f(1.0, 1.0, 1.0);
+        let b = Color::new_f(0.0, 0.0, 0.0);
+        let checker = Texture::Checker { a, b, scale: 0.5 };
+
+        assert_eq!(checker.sample((0.1, 0.1)), a);
+        assert_eq!(checker.sample((0.6, 0.1)), b);
+        assert_eq!(checker.sample((0.6, 0.6)), a);
+        // Cells continue past zero
+        assert_eq!(checker.sample((-0.1, 0.1)), b);
+        assert_eq!(checker.sample((-0.1, -0.1)), a);
+
+        // Adjacent cells on a floor
+        let floor = Plane::new(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0));
+        let color_at = |x: f64, z: f64| {
+            let ray = Ray::new(Vec3::new(x, 3.0, z), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+            checker.sample(floor.intersection(&ray).unwrap().uv.unwrap())
+        };
+        for i in -4..4 {
+            let x = f64::from(i) * 0.5 + 0.25;
+            assert_ne!(color_at(x, 0.25), color_at(x + 0.5, 0.25));
+            assert_ne!(color_at(x, 0.25), color_at(x, 0.75));
+            assert_eq!(color_at(x, 0.25), color_at(x + 0.5, 0.75));
+        }
+    }
+}
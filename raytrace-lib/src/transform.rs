@@ -0,0 +1,98 @@
+use crate::{rotation::Rotation, vec3::Vec3};
+
+/// A uniform scale followed by a rotation and a translation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub scale: f64,
+    pub rotation: Rotation,
+    pub translation: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            rotation: Rotation::identity(),
+            translation: Vec3::zero(),
+        }
+    }
+}
+
+impl Transform {
+    /// Apply the transform to a point: scale, then rotate, then translate.
+    pub fn apply_point(&self, point: Vec3) -> Vec3 {
+        (point * self.scale).rotate(&self.rotation) + self.translation
+    }
+
+    /// Apply the transform to a direction, which is not translated.
+    pub fn apply_vector(&self, vector: Vec3) -> Vec3 {
+        (vector * self.scale).rotate(&self.rotation)
+    }
+
+    /// Returns the transform that undoes `self`.
+    /// The scale must not be zero.
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.inverse();
+        Self {
+            scale: 1.0 / self.scale,
+            rotation,
+            translation: -self.translation.rotate(&rotation) * (1.0 / self.scale),
+        }
+    }
+
+    /// Returns the transform that applies `self` and then `other`,
+    /// e.g. an object's own transform followed by the transform of its group.
+    pub fn then(&self, other: &Self) -> Self {
+        Self {
+            scale: self.scale * other.scale,
+            rotation: other.rotation * self.rotation,
+            translation: other.apply_point(self.translation),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotate_then_translate() {
+        let rotate = Transform {
+            rotation: Rotation::from_axis_angle(
+                Vec3::new(0.0, 1.0, 0.0),
+                std::f64::consts::FRAC_PI_2,
+            ),
+            ..Default::default()
+        };
+        let translate = Transform {
+            translation: Vec3::new(1.0, 2.0, 3.0),
+            ..Default::default()
+        };
+        let scale = Transform {
+            scale: 2.0,
+            ..Default::default()
+        };
+
+        let p = Vec3::new(1.0, 0.0, 0.0);
+        let manual = translate.apply_point(rotate.apply_point(p));
+        // (1, 0, 0) rotated a quarter turn around y is (0, 0, -1)
+        assert!(
+            (manual - Vec3::new(1.0, 2.0, 2.0)).length() < 1e-9,
+            "{manual:?}"
+        );
+
+        let composed = rotate.then(&translate);
+        assert!((composed.apply_point(p) - manual).length() < 1e-9);
+
+        let stacked = scale.then(&rotate).then(&translate);
+        let manual = translate.apply_point(rotate.apply_point(scale.apply_point(p)));
+        assert!((stacked.apply_point(p) - manual).length() < 1e-9);
+
+        // Translation is applied after scaling, not scaled itself
+        let moved_then_scaled = translate.then(&scale);
+        assert!(
+            (moved_then_scaled.apply_point(Vec3::zero()) - Vec3::new(2.0, 4.0, 6.0)).length()
+                < 1e-9
+        );
+    }
+}
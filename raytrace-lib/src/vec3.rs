@@ -2,6 +2,7 @@ use crate::{rotation::Rotation, FLOAT_EPS};
 
 /// Vector in 3d-space.
 #[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec3 {
     /// The x-component of the vector.
     pub x: f64,
@@ -96,12 +97,46 @@ impl Vec3 {
         self - 2.0 * normal * normal.dot(self)
     }
 
+    /// Refract `self` through a surface with Snell's law, where `eta` is the ratio of
+    /// the refractive index on the incoming side to the one on the outgoing side.
+    /// The normal must point against `self` and both must be normalized.
+    /// Returns `None` on total internal reflection.
+    ///
+    /// <https://en.wikipedia.org/wiki/Snell%27s_law#Vector_form>
+    #[must_use]
+    pub fn refract(self, normal: Self, eta: f64) -> Option<Self> {
+        debug_assert!(self.is_unit() && normal.is_unit());
+        let cos_i = -normal.dot(self);
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return None;
+        }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(eta * self + (eta * cos_i - cos_t) * normal)
+    }
+
+    /// Returns the angle in radians between `self` and `other`.
+    /// Neither vector needs to be normalized.
+    #[must_use]
+    pub fn angle_between(self, other: Self) -> f64 {
+        // Rounding can push the dot product slightly outside of [-1, 1]
+        let cos = self.normalize().dot(other.normalize()).clamp(-1.0, 1.0);
+        cos.acos()
+    }
+
     /// Returns true if `self` as a unit vector.
     #[must_use]
     pub fn is_unit(self) -> bool {
         self.length() - 1.0 < FLOAT_EPS
     }
 
+    /// Returns true if no component is NaN or infinite.
+    #[must_use]
+    pub fn is_finite(self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+
     /// Rotates the vector with the given rotation matrix.
     #[must_use]
     pub fn rotate(self, rot: &Rotation) -> Self {
@@ -175,6 +210,38 @@ impl std::ops::Add for Vec3 {
     }
 }
 
+impl std::ops::AddAssign for Vec3 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::SubAssign for Vec3 {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::ops::MulAssign<f64> for Vec3 {
+    fn mul_assign(&mut self, rhs: f64) {
+        *self = *self * rhs;
+    }
+}
+
+/// Component by axis, 0, 1 and 2 for x, y and z.
+impl std::ops::Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 has no axis {axis}"),
+        }
+    }
+}
+
 impl PartialEq for Vec3 {
     fn eq(&self, other: &Self) -> bool {
         (self.x - other.x).abs() < FLOAT_EPS
@@ -182,3 +249,59 @@ impl PartialEq for Vec3 {
             && (self.z - other.z).abs() < FLOAT_EPS
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    #[test]
+    fn angle_between() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 3.0, 0.0);
+        let v = Vec3::new(0.3, -1.7, 2.9);
+
+        // acos is badly conditioned close to ±1
+        let eps = 1e-6;
+        assert!((x.angle_between(y) - FRAC_PI_2).abs() < eps);
+        assert!(v.angle_between(v).abs() < eps);
+        assert!((v.angle_between(-v) - PI).abs() < eps);
+        assert!(!v.angle_between(v * 2.0).is_nan());
+    }
+
+    #[test]
+    fn assign_operators() {
+        let vs = [
+            Vec3::new(0.3, -1.7, 2.9),
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(-4.5, 0.25, 8.0),
+        ];
+
+        let mut acc = Vec3::zero();
+        for &v in &vs {
+            acc += v;
+        }
+        acc -= vs[1];
+        acc *= 0.5;
+
+        assert_eq!(acc, (vs[0] + vs[1] + vs[2] - vs[1]) * 0.5);
+    }
+
+    #[test]
+    fn refract_snell() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let incoming = Vec3::new(1.0, -1.0, 0.0).normalize();
+        let eta = 1.0 / 1.5;
+
+        let refracted = incoming.refract(normal, eta).unwrap();
+        let sin_i = incoming.angle_between(-normal).sin();
+        let sin_t = refracted.angle_between(-normal).sin();
+        assert!(refracted.is_unit());
+        assert!((sin_i * eta - sin_t).abs() < 1e-9);
+
+        // Straight through when the indices match
+        assert_eq!(incoming.refract(normal, 1.0), Some(incoming));
+        // Total internal reflection leaving glass at a grazing angle
+        assert_eq!(incoming.refract(normal, 1.5), None);
+    }
+}
@@ -1,15 +1,36 @@
-use clap::Parser;
+mod profile;
+
+use clap::{Parser, ValueEnum};
 use path_absolutize::Absolutize;
+use profile::Profile;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
-use image::RgbImage;
+use image::{imageops::FilterType, DynamicImage, ImageBuffer, Pixel, Rgb, RgbImage, Rgba};
+use raytrace_lib::material::MaterialTemplate;
+use raytrace_lib::primitive::{Plane, Sphere};
+use raytrace_lib::progressive::Accumulator;
+use raytrace_lib::rng::{self, XorShift64};
+use raytrace_lib::texture::Texture;
+use raytrace_lib::{postprocess, Color, FovAxis, Light, Material, Object, Raytracer, Scene, Vec3};
+use std::str::FromStr;
 
 /// The default path when saving images.
 const DEFAULT_FILE_NAME: &str = "./raytraced.png";
 
+/// The base color used when overriding materials.
+const CLAY_COLOR: Color = Color::new_f(0.8, 0.8, 0.8);
+
+/// The color of the squares of the `--add-floor` plane that aren't [`CLAY_COLOR`].
+const FLOOR_COLOR: Color = Color::new_f(0.4, 0.4, 0.4);
+
+/// Radius of the spheres shown by `--show-lights` for lights without a shadow radius.
+const LIGHT_MARKER_RADIUS: f64 = 0.1;
+
 #[derive(Parser)]
 #[command(author, version, about)]
 struct Args {
+    /// The scene file to render, or `-` to read it from stdin
     #[arg(short, long)]
     file: String,
     #[arg(short, long)]
@@ -18,10 +39,116 @@ struct Args {
     width: Option<u32>,
     #[arg(long)]
     height: Option<u32>,
+    /// Whether the camera's fov spans the width or the height of the image
+    #[arg(long, value_name = "horizontal|vertical")]
+    fov_axis: Option<FovAxis>,
     #[arg(short, long)]
     recurse_depth: Option<u32>,
     #[arg(short, long)]
     parallel: bool,
+    /// Save the scene with all defaults and overrides resolved as JSON
+    #[arg(long, value_name = "FILE")]
+    save_scene: Option<String>,
+    /// Print what the primary ray of pixel (X, Y) hits instead of rendering
+    #[arg(long, num_args = 2, value_names = ["X", "Y"])]
+    debug_pixel: Option<Vec<u32>>,
+    /// Render at FACTOR times the resolution and downsample the result
+    #[arg(long = "ss", value_name = "FACTOR")]
+    supersample: Option<u32>,
+    /// Image shown behind the scene wherever a primary ray hits nothing
+    #[arg(long, conflicts_with = "transparent")]
+    backplate: Option<String>,
+    /// Save an RGBA image where pixels that hit nothing are transparent
+    #[arg(long)]
+    transparent: bool,
+    /// Add a checkered ground plane at height Y below the scene
+    #[arg(long, value_name = "Y", allow_negative_numbers = true)]
+    add_floor: Option<f64>,
+    /// Show every light as a small glowing sphere
+    #[arg(long)]
+    show_lights: bool,
+    /// Replace every material with a matte gray one
+    #[arg(long, conflicts_with = "material_override")]
+    clay: bool,
+    /// Replace every material with the named material template
+    #[arg(long, value_name = "TEMPLATE")]
+    material_override: Option<String>,
+    /// Print the differences to another scene file instead of rendering
+    #[arg(long, value_name = "OTHER")]
+    diff: Option<String>,
+    /// Clamp the luminance of every sample to L to suppress fireflies
+    #[arg(long, value_name = "L")]
+    clamp_luminance: Option<f64>,
+    /// Exposure compensation in stops applied before clamping
+    #[arg(long, value_name = "STOPS", allow_negative_numbers = true)]
+    exposure: Option<f64>,
+    /// Compress bright colors with Reinhard tone mapping instead of clamping them
+    #[arg(long)]
+    tonemap: bool,
+    /// Print how long each stage of the render took: parse, build, trace, post and encode
+    #[arg(long)]
+    profile: bool,
+    /// Print N randomly chosen primary rays as CSV instead of rendering
+    #[arg(long, value_name = "N")]
+    dump_rays: Option<usize>,
+    /// Render a side-by-side stereo image with the eyes SEP apart
+    #[arg(long, value_name = "SEP", conflicts_with_all = ["progressive", "backplate", "transparent"])]
+    stereo: Option<f64>,
+    /// Save the ambient, diffuse and specular terms as separate images instead of the render,
+    /// named like `out_ambient.png`
+    #[arg(long, conflicts_with_all = ["progressive", "stereo"])]
+    split_channels: bool,
+    /// Move the camera to the origin before tracing, for scenes far from the origin
+    #[arg(long)]
+    camera_relative: bool,
+    /// Print the node count, depth and leaf sizes of the BVH, and how many primitives
+    /// the primary rays test, instead of rendering
+    #[arg(long, alias = "traversal-stats")]
+    bvh_stats: bool,
+    /// Index of the frame when rendering an animation
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    frame: u64,
+    /// Use a different random seed for every frame instead of the same one
+    #[arg(long)]
+    seed_per_frame: bool,
+    /// Bits per channel in the saved image
+    #[arg(long, value_enum, default_value = "8")]
+    bit_depth: BitDepth,
+    /// Send each primary ray through a random point of its pixel,
+    /// the passes of a progressive render then antialias the image
+    #[arg(long)]
+    jitter: bool,
+    /// Number of jittered rays averaged into each pixel, antialiasing a single render.
+    /// Overrides the samples of the scene
+    #[arg(long, value_name = "N")]
+    samples: Option<u32>,
+    /// Shade with one light per point, chosen by its intensity, instead of every light.
+    /// Noisier, but faster in scenes with many lights
+    #[arg(long)]
+    sample_lights: bool,
+    /// Render in passes, saving the averaged image so far every --pass-interval passes
+    #[arg(long)]
+    progressive: bool,
+    /// Number of passes of a progressive render
+    #[arg(long, value_name = "N", default_value_t = 8, requires = "progressive")]
+    passes: u32,
+    /// Save an intermediate image every N passes of a progressive render
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "progressive")]
+    pass_interval: u32,
+    /// Overwrite the output image after each pass instead of saving separate images
+    #[arg(long, requires = "progressive")]
+    overwrite_passes: bool,
+    /// Continue a progressive render from the `.accum.json` file saved next to its image
+    #[arg(long, value_name = "FILE", requires = "progressive")]
+    resume_from: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BitDepth {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
 }
 
 fn main() {
@@ -37,10 +164,20 @@ fn main() {
 }
 
 fn run_raytracer(args: Args) -> Result<String, String> {
+    let mut profile = Profile::default();
     let buf = read_file(args.file)?;
 
-    let (world, lights, mut raytracer) =
-        scene_parser::parse_string(&buf).map_err(|e| format!("Unable to parse file:\n {e}"))?;
+    let ((mut world, mut lights, mut raytracer), warnings) = profile
+        .time("parse", || scene_parser::parse_string_with_warnings(&buf))
+        .map_err(|e| format!("Unable to parse file:\n {e}"))?;
+
+    for warning in warnings {
+        eprintln!("{warning}");
+    }
+
+    if let Some(other) = args.diff {
+        return diff_scenes(Scene::new(raytracer, world, lights), &read_file(other)?);
+    }
 
     if let Some(w) = args.width {
         raytracer.set_width(w);
@@ -50,31 +187,115 @@ fn run_raytracer(args: Args) -> Result<String, String> {
         raytracer.set_height(h);
     }
 
+    if let Some(fov_axis) = args.fov_axis {
+        raytracer.set_fov_axis(fov_axis);
+    }
+
     if let Some(depth) = args.recurse_depth {
         raytracer.set_recurse_depth(depth);
     }
 
-    let out = if args.parallel {
-        raytracer.par_raycast(world.into(), lights.into())
+    raytracer.set_luminance_clamp(args.clamp_luminance);
+    raytracer.set_pixel_jitter(args.jitter);
+    if let Some(samples) = args.samples {
+        raytracer.set_samples_per_pixel(samples);
+    }
+    raytracer.set_light_sampling(args.sample_lights);
+
+    if let Some(y) = args.add_floor {
+        world.push(floor_object(y));
+    }
+
+    if let Some(name) = args.material_override {
+        let template = MaterialTemplate::from_str(&name)?;
+        override_materials(&mut world, template.get_material(CLAY_COLOR));
+    } else if args.clay {
+        override_materials(&mut world, clay_material());
+    }
+
+    // After the material overrides so the lights keep glowing
+    if args.show_lights {
+        world.extend(light_markers(&lights));
+    }
+
+    if let Some(path) = args.save_scene {
+        let scene = Scene::new(raytracer, world, lights);
+        save_scene(&scene, Path::new(&path))?;
+        (world, lights, raytracer) = scene.into_parts();
+    }
+
+    let offset = if args.camera_relative {
+        raytracer.make_camera_relative(&mut world, &mut lights)
     } else {
-        raytracer.raycast(&world, &lights)
+        Vec3::zero()
     };
 
-    let width = out[0].len() as u32;
-    let height = out.len() as u32;
+    if let Some(pixel) = args.debug_pixel {
+        let (x, y) = (pixel[0], pixel[1]);
+        let (width, height) = raytracer.pixels();
+        if x >= width || y >= height {
+            return Err(format!(
+                "Pixel ({x}, {y}) is outside the {width}x{height} image"
+            ));
+        }
 
-    let mut img = RgbImage::new(width, height);
+        // Image rows are flipped vertically compared to the raycast output
+        return Ok(
+            match raytracer.debug_pixel(&world, &lights, height - 1 - y, x) {
+                Some(mut debug) => {
+                    debug.intersection -= offset;
+                    format!("Pixel ({x}, {y}) hit {debug}")
+                }
+                None => format!("Pixel ({x}, {y}) hit nothing"),
+            },
+        );
+    }
 
-    for (y, row) in out.iter().enumerate() {
-        // Flip image vertically
-        let y = height - 1 - y as u32;
+    let seed = frame_seed(args.frame, args.seed_per_frame);
+    raytracer.set_seed(seed);
 
-        for (x, color) in row.iter().enumerate() {
-            let x = x as u32;
-            img.put_pixel(x, y, image::Rgb((*color).into()));
-        }
+    if let Some(n) = args.dump_rays {
+        return Ok(dump_rays(&raytracer, n, seed));
+    }
+
+    if args.bvh_stats {
+        return Ok(raytracer.traversal_stats(&world).to_string());
     }
 
+    let factor = args.supersample.unwrap_or(1).max(1);
+    if factor > 1 {
+        let (w, h) = raytracer.pixels();
+        raytracer.set_width(w * factor);
+        raytracer.set_height(h * factor);
+    }
+
+    let backplate = match args.backplate {
+        Some(path) => {
+            let backplate = image::open(&path)
+                .map_err(|e| format!("Could not read backplate '{path}'!\n{e}"))?
+                .to_rgb8();
+            let (w, h) = raytracer.pixels();
+            let backplate = image::imageops::resize(&backplate, w, h, FilterType::Triangle);
+            Some((backplate, raytracer.hit_mask(&world)))
+        }
+        None => None,
+    };
+
+    let coverage = if args.transparent {
+        Some(postprocess::coverage(&raytracer.hit_mask(&world), factor))
+    } else {
+        None
+    };
+
+    let develop = Develop {
+        factor,
+        backplate,
+        coverage,
+        exposure: args.exposure,
+        tonemap: args.tonemap,
+        bit_depth: args.bit_depth,
+    };
+
     let out_file = if let Some(f) = args.out_file {
         Path::new(&f)
             .absolutize()
@@ -84,17 +305,432 @@ fn run_raytracer(args: Args) -> Result<String, String> {
         find_unique_file_name()?
     };
 
-    create_empty_file(&out_file)?;
+    let render = |raytracer: &Raytracer| {
+        if args.parallel {
+            raytracer.par_raycast_timed(&world, &lights)
+        } else {
+            raytracer.raycast_timed(&world, &lights)
+        }
+    };
+
+    if args.split_channels {
+        let terms = raytracer.term_raytracers();
+        let mut saved = vec![];
+        for (term, raytracer) in ["ambient", "diffuse", "specular"].into_iter().zip(terms) {
+            let image = profile.render(|| raytracer.par_raycast_timed(&world, &lights));
+            let path = term_file_name(&out_file, term);
+            let img = profile.time("post", || develop.image(image));
+            profile.time("encode", || save_image(&img, &path))?;
+            saved.push(format!("Saved {term} to {}", path.to_string_lossy()));
+        }
+        return Ok(saved.join("\n"));
+    }
+
+    let out = if let Some(separation) = args.stereo {
+        let (left, right) = raytracer.stereo_pair(separation);
+        let left = profile.render(|| left.par_raycast_timed(&world, &lights));
+        let right = profile.render(|| right.par_raycast_timed(&world, &lights));
+        side_by_side(left, right)
+    } else if args.progressive {
+        let passes = args.passes.max(1);
+        let mut accumulator = match args.resume_from {
+            Some(path) => load_accumulation(Path::new(&path), raytracer.pixels())?,
+            None => Accumulator::new(),
+        };
+        let accumulation_file = accumulation_file_name(&out_file);
+
+        for pass in accumulator.passes()..passes {
+            // Every pass of every frame gets its own noise
+            raytracer.set_seed(rng::stream_seed(seed, u64::from(pass)));
+            let image = profile.render(|| render(&raytracer));
+            accumulator.add_pass(&image);
+
+            let done = accumulator.passes();
+            if done < passes && done.is_multiple_of(args.pass_interval.max(1)) {
+                let path = if args.overwrite_passes {
+                    out_file.clone()
+                } else {
+                    pass_file_name(&out_file, done)
+                };
+                let img = profile.time("post", || develop.image(accumulator.image().to_vec()));
+                profile.time("encode", || save_image(&img, &path))?;
+                save_accumulation(&accumulator, &accumulation_file)?;
+                eprintln!("Saved pass {done}/{passes} to {}", path.to_string_lossy());
+            }
+        }
+        save_accumulation(&accumulator, &accumulation_file)?;
+
+        accumulator.image().to_vec()
+    } else {
+        profile.render(|| render(&raytracer))
+    };
+
+    let img = profile.time("post", || develop.image(out));
+    profile.time("encode", || save_image(&img, &out_file))?;
+
+    let saved = format!("Saved image to {}", out_file.to_string_lossy());
+    if args.profile {
+        Ok(format!("{saved}\n{profile}"))
+    } else {
+        Ok(saved)
+    }
+}
+
+/// Post-processing that turns the raw render into the saved image.
+struct Develop {
+    /// Supersampling factor to downsample by.
+    factor: u32,
+    /// Image shown where the hit mask has no hit, at the render resolution.
+    backplate: Option<(RgbImage, Vec<Vec<bool>>)>,
+    /// Alpha of each output pixel, saves an RGBA image if set.
+    coverage: Option<Vec<Vec<f64>>>,
+    exposure: Option<f64>,
+    /// Tone map after the exposure instead of only clamping.
+    tonemap: bool,
+    bit_depth: BitDepth,
+}
+
+impl Develop {
+    fn image(&self, mut out: Vec<Vec<Color>>) -> DynamicImage {
+        if let Some((backplate, hit_mask)) = &self.backplate {
+            fill_misses(&mut out, hit_mask, backplate);
+        }
+
+        let mut out = if self.factor > 1 {
+            postprocess::downsample(&out, self.factor)
+        } else {
+            out
+        };
+
+        if let Some(stops) = self.exposure {
+            postprocess::exposure(&mut out, stops);
+        }
+        if self.tonemap {
+            postprocess::tone_map(&mut out, 1.0);
+        }
+        postprocess::clamp(&mut out);
+
+        match (self.bit_depth, &self.coverage) {
+            (BitDepth::Eight, Some(coverage)) => to_rgba_image::<u8>(&out, coverage).into(),
+            (BitDepth::Eight, None) => to_rgb_image::<u8>(&out).into(),
+            (BitDepth::Sixteen, Some(coverage)) => to_rgba_image::<u16>(&out, coverage).into(),
+            (BitDepth::Sixteen, None) => to_rgb_image::<u16>(&out).into(),
+        }
+    }
+}
+
+fn save_scene(scene: &Scene, path: &Path) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(scene)
+        .map_err(|e| format!("Could not serialize scene!\n{e}"))?;
+    std::fs::write(path, json).map_err(|e| format!("Could not save scene!\n{e}"))
+}
+
+fn save_image(img: &DynamicImage, path: &Path) -> Result<(), String> {
+    create_empty_file(path)?;
+    img.save(path)
+        .map_err(|e| format!("Could not save image!\n{e}"))
+}
+
+/// Returns the path of the intermediate image saved after `pass`,
+/// e.g. `out.pass3.png` for `out.png`.
+fn pass_file_name(out_file: &Path, pass: u32) -> PathBuf {
+    let stem = out_file.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match out_file.extension() {
+        Some(ext) => format!("{stem}.pass{pass}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.pass{pass}"),
+    };
+    out_file.with_file_name(name)
+}
+
+/// Returns the path of the image of a single shading term,
+/// e.g. `out_diffuse.png` for `out.png`.
+fn term_file_name(out_file: &Path, term: &str) -> PathBuf {
+    let stem = out_file.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match out_file.extension() {
+        Some(ext) => format!("{stem}_{term}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_{term}"),
+    };
+    out_file.with_file_name(name)
+}
+
+/// Join two images of the same height, with `left` on the left.
+fn side_by_side(left: Vec<Vec<Color>>, right: Vec<Vec<Color>>) -> Vec<Vec<Color>> {
+    left.into_iter()
+        .zip(right)
+        .map(|(mut row, right)| {
+            row.extend(right);
+            row
+        })
+        .collect()
+}
+
+/// Returns the path that the unclamped buffer of a progressive render is saved to,
+/// e.g. `out.accum.json` for `out.png`.
+fn accumulation_file_name(out_file: &Path) -> PathBuf {
+    let stem = out_file.file_stem().unwrap_or_default().to_string_lossy();
+    out_file.with_file_name(format!("{stem}.accum.json"))
+}
+
+/// Save the averaged buffer and the number of passes in it,
+/// so that `--resume-from` can continue the render.
+fn save_accumulation(accumulator: &Accumulator, path: &Path) -> Result<(), String> {
+    let json = serde_json::to_string(accumulator)
+        .map_err(|e| format!("Could not serialize accumulated passes!\n{e}"))?;
+    std::fs::write(path, json).map_err(|e| format!("Could not save accumulated passes!\n{e}"))
+}
+
+/// Load the passes saved by [`save_accumulation`], checking that they were rendered
+/// at the `(width, height)` of the current render.
+fn load_accumulation(path: &Path, (width, height): (u32, u32)) -> Result<Accumulator, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| {
+        format!(
+            "Could not read accumulated passes '{}'!\n{e}",
+            path.to_string_lossy()
+        )
+    })?;
+    let accumulator: Accumulator = serde_json::from_str(&json)
+        .map_err(|e| format!("Could not parse accumulated passes!\n{e}"))?;
+
+    let (w, h) = accumulator.dimensions();
+    if accumulator.passes() > 0 && (w, h) != (width as usize, height as usize) {
+        return Err(format!(
+            "Accumulated passes are {w}x{h}, but the render is {width}x{height}"
+        ));
+    }
+
+    Ok(accumulator)
+}
+
+/// The seed shared by every frame without `--seed-per-frame`.
+const BASE_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Returns the random seed for `frame`.
+///
+/// With a fixed seed the noise stays in place between frames, varying it
+/// per frame makes the noise move instead.
+fn frame_seed(frame: u64, per_frame: bool) -> u64 {
+    if !per_frame {
+        return BASE_SEED;
+    }
+
+    rng::stream_seed(BASE_SEED, frame)
+}
+
+/// Returns `n` primary rays from pixels picked at random as CSV.
+///
+/// The pixels are picked from `seed` so the output is reproducible.
+fn dump_rays(raytracer: &Raytracer, n: usize, seed: u64) -> String {
+    let (width, height) = raytracer.pixels();
+    let mut csv = "x,y,origin_x,origin_y,origin_z,dir_x,dir_y,dir_z".to_string();
+
+    if width == 0 || height == 0 {
+        return csv;
+    }
+
+    let mut rng = XorShift64::new(seed);
+    let mut next = |bound: u32| (rng.next_u64() % u64::from(bound)) as u32;
+
+    for _ in 0..n {
+        let (x, y) = (next(width), next(height));
+        // Image rows are flipped vertically compared to the raycast output
+        if let Some(ray) = raytracer.pixel_ray(height - 1 - y, x) {
+            let (o, d) = (ray.origin, ray.direction());
+            csv += &format!("\n{x},{y},{},{},{},{},{},{}", o.x, o.y, o.z, d.x, d.y, d.z);
+        }
+    }
+
+    csv
+}
+
+/// Describe the changes needed to turn `scene` into the scene in `other_source`.
+fn diff_scenes(scene: Scene, other_source: &str) -> Result<String, String> {
+    let other: Scene = scene_parser::parse_string(other_source)
+        .map_err(|e| format!("Unable to parse other file:\n {e}"))?
+        .into();
+
+    let changes = scene.diff(&other);
+    if changes.is_empty() {
+        return Ok("No changes".to_string());
+    }
+
+    Ok(changes
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Returns a matte gray material used for clay renders.
+fn clay_material() -> Material {
+    Material {
+        color: CLAY_COLOR,
+        lambert: Color::new_f(1.0, 1.0, 1.0),
+        ambient: Color::new_f(0.1, 0.1, 0.1),
+        ..Default::default()
+    }
+}
+
+/// Returns a horizontal plane at height `y` facing up,
+/// checkered in squares of one world unit.
+fn floor_object(y: f64) -> Object {
+    let material = Material {
+        texture: Some(Texture::Checker {
+            a: CLAY_COLOR,
+            b: FLOOR_COLOR,
+            scale: 1.0,
+        }),
+        ..clay_material()
+    };
+    Object::new(
+        Plane::new(Vec3::new(0.0, y, 0.0), Vec3::new(0.0, 1.0, 0.0)).into(),
+        material,
+    )
+    .with_name("floor")
+}
+
+/// Returns an emissive sphere at the position of each light, sized by its shadow radius.
+/// The spheres don't cast shadows, otherwise they would block the light inside them.
+fn light_markers(lights: &[Light]) -> Vec<Object> {
+    lights
+        .iter()
+        .map(|light| {
+            let radius = if light.shadow_radius > 0.0 {
+                light.shadow_radius
+            } else {
+                LIGHT_MARKER_RADIUS
+            };
+            let material = Material {
+                color: Color::zero(),
+                lambert: Color::zero(),
+                ambient: Color::zero(),
+                emission: Color::new_f(1.0, 1.0, 1.0),
+                ..clay_material()
+            };
+
+            Object::new(Sphere::new(light.pos, radius).into(), material)
+                .with_name("light")
+                .with_casts_shadows(false)
+        })
+        .collect()
+}
+
+/// Replace the material of every object while keeping the geometry.
+fn override_materials(world: &mut [Object], material: Material) {
+    for object in world.iter_mut() {
+        object.material = material;
+    }
+}
+
+/// A channel type that clamped colors can be encoded to.
+trait Channel: image::Primitive {
+    fn from_color(color: Color) -> [Self; 3];
+    fn from_alpha(alpha: f64) -> Self;
+}
+
+impl Channel for u8 {
+    fn from_color(color: Color) -> [Self; 3] {
+        color.to_srgb_bytes()
+    }
+
+    fn from_alpha(alpha: f64) -> Self {
+        (alpha * 255.0).round() as u8
+    }
+}
+
+impl Channel for u16 {
+    fn from_color(color: Color) -> [Self; 3] {
+        color.to_srgb_u16_array()
+    }
+
+    fn from_alpha(alpha: f64) -> Self {
+        (alpha * 65535.0).round() as u16
+    }
+}
+
+/// Convert the output of the raytracer to an image.
+fn to_rgb_image<T: Channel>(out: &[Vec<Color>]) -> ImageBuffer<Rgb<T>, Vec<T>>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    let width = out[0].len() as u32;
+    let height = out.len() as u32;
+
+    let mut img = ImageBuffer::new(width, height);
+
+    for (y, row) in out.iter().enumerate() {
+        // Flip image vertically
+        let y = height - 1 - y as u32;
+
+        for (x, color) in row.iter().enumerate() {
+            let x = x as u32;
+            img.put_pixel(x, y, Rgb(T::from_color(*color)));
+        }
+    }
+
+    img
+}
+
+/// Convert the output of the raytracer to an image with an alpha channel
+/// taken from `coverage`.
+fn to_rgba_image<T: Channel>(
+    out: &[Vec<Color>],
+    coverage: &[Vec<f64>],
+) -> ImageBuffer<Rgba<T>, Vec<T>>
+where
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    let width = out[0].len() as u32;
+    let height = out.len() as u32;
+
+    let mut img = ImageBuffer::new(width, height);
+
+    for (y, (row, alphas)) in out.iter().zip(coverage).enumerate() {
+        // Flip image vertically
+        let y = height - 1 - y as u32;
 
-    match img.save(&out_file) {
-        Ok(_) => Ok(format!("Saved image to {}", out_file.to_string_lossy())),
-        Err(e) => Err(format!("Could not save image!\n{e}")),
+        for (x, (color, alpha)) in row.iter().zip(alphas).enumerate() {
+            let [r, g, b] = T::from_color(*color);
+            img.put_pixel(x as u32, y, Rgba([r, g, b, T::from_alpha(*alpha)]));
+        }
     }
+
+    img
 }
 
+/// Replace every pixel whose primary ray missed with the matching backplate pixel.
+///
+/// `backplate` must have the same dimensions as `image`.
+fn fill_misses(image: &mut [Vec<Color>], hit_mask: &[Vec<bool>], backplate: &RgbImage) {
+    let height = image.len() as u32;
+
+    for (y, (row, hits)) in image.iter_mut().zip(hit_mask).enumerate() {
+        // The image is flipped vertically compared to the raycast output
+        let y = height - 1 - y as u32;
+
+        for (x, (color, &hit)) in row.iter_mut().zip(hits).enumerate() {
+            if !hit {
+                *color = Color::from_srgb_bytes(backplate.get_pixel(x as u32, y).0);
+            }
+        }
+    }
+}
+
+/// Read the scene source from `file_name`, or from stdin if it is `-`.
 fn read_file(file_name: String) -> Result<String, String> {
-    match std::fs::read_to_string(file_name) {
-        Ok(s) => Ok(s),
+    if file_name == "-" {
+        return read_source(std::io::stdin().lock());
+    }
+
+    match std::fs::File::open(file_name) {
+        Ok(file) => read_source(file),
+        Err(e) => Err(format!("Could not read input file!\n{e}")),
+    }
+}
+
+fn read_source<R: Read>(mut reader: R) -> Result<String, String> {
+    let mut source = String::new();
+    match reader.read_to_string(&mut source) {
+        Ok(_) => Ok(source),
         Err(e) => Err(format!("Could not read input file!\n{e}")),
     }
 }
@@ -140,3 +776,313 @@ fn find_unique_file_name() -> Result<PathBuf, String> {
     }
     Ok(name.to_string().into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raytrace_lib::{
+        primitive::{Primitive, Sphere},
+        Camera,
+    };
+
+    #[test]
+    fn backplate_fills_misses() {
+        let camera = Camera::new(8, 8, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+        let raytracer = Raytracer::new(camera, 1);
+        let white = Color::new(255, 255, 255);
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0).into(),
+            Material {
+                color: white,
+                ambient: white,
+                ..Default::default()
+            },
+        )];
+
+        let mut image = raytracer.raycast(&world, &[]);
+        let mask = raytracer.hit_mask(&world);
+        let backplate = RgbImage::from_fn(8, 8, |x, y| image::Rgb([x as u8 * 10, y as u8 * 10, 7]));
+
+        fill_misses(&mut image, &mask, &backplate);
+
+        // Bottom left corner of the picture is the first row of the raycast output
+        assert!(!mask[0][0]);
+        // Decoded from sRGB so it is saved with the same bytes
+        assert_eq!(image[0][0], Color::from_srgb_bytes([0, 70, 7]));
+        assert_eq!(image[0][0].to_srgb_bytes(), [0, 70, 7]);
+        // The sphere in the center is kept
+        assert!(mask[4][4]);
+        assert_eq!(image[4][4], white);
+    }
+
+    #[test]
+    fn material_override_replaces_all() {
+        let bronze = MaterialTemplate::Bronze.get_material(Color::new(255, 0, 0));
+        let mut world = vec![
+            Object::new(Sphere::new(Vec3::zero(), 1.0).into(), bronze),
+            Object::new(
+                Plane::new(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0)).into(),
+                bronze,
+            ),
+        ];
+
+        override_materials(&mut world, clay_material());
+        assert!(world.iter().all(|o| o.material == clay_material()));
+
+        let green = MaterialTemplate::Green.get_material(CLAY_COLOR);
+        override_materials(&mut world, green);
+        assert!(world.iter().all(|o| o.material == green));
+    }
+
+    #[test]
+    fn show_lights_adds_markers() {
+        let mut soft = Light::new(Vec3::new(3.0, 4.0, 0.0), 1.0);
+        soft.shadow_radius = 0.5;
+        let lights = [Light::new(Vec3::new(0.0, 5.0, 0.0), 1.0), soft];
+
+        let mut world = vec![floor_object(0.0)];
+        world.extend(light_markers(&lights));
+        assert_eq!(world.len(), 1 + lights.len());
+
+        for (marker, light) in world[1..].iter().zip(&lights) {
+            assert!(marker.material.is_emissive());
+            assert!(marker.primitive.contains(light.pos));
+        }
+        assert_eq!(
+            world[2].primitive.bounding_sphere(),
+            Some(Sphere::new(soft.pos, 0.5))
+        );
+
+        // The markers don't block the lights they surround
+        let camera = Camera::new(8, 8, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+        let raytracer = Raytracer::new(camera, 1);
+        for light in &lights {
+            assert_eq!(raytracer.light_visibility(&world, Vec3::zero(), light), 1.0);
+        }
+    }
+
+    #[test]
+    fn transparent_misses() {
+        let camera = Camera::new(8, 8, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+        let raytracer = Raytracer::new(camera, 1);
+        let world = [Object::new(
+            Sphere::new(Vec3::new(0.0, 0.0, 20.0), 1.0).into(),
+            clay_material(),
+        )];
+
+        let out = raytracer.raycast(&world, &[]);
+        let coverage = postprocess::coverage(&raytracer.hit_mask(&world), 1);
+        let img = to_rgba_image::<u8>(&out, &coverage);
+
+        assert_eq!(img.get_pixel(0, 0).0[3], 0);
+        assert_eq!(img.get_pixel(4, 3).0[3], 255);
+    }
+
+    #[test]
+    fn sixteen_bit_keeps_more_gradient_steps() {
+        let gradient = vec![(0..1024)
+            .map(|i| {
+                let v = 0.25 + 0.01 * f64::from(i) / 1023.0;
+                Color::new_f(v, v, v)
+            })
+            .collect::<Vec<_>>()];
+
+        let distinct = |values: Vec<u32>| {
+            let mut values = values;
+            values.dedup();
+            values.len()
+        };
+
+        let low = to_rgb_image::<u8>(&gradient);
+        let high = to_rgb_image::<u16>(&gradient);
+        let low = distinct(low.pixels().map(|p| u32::from(p.0[0])).collect());
+        let high = distinct(high.pixels().map(|p| u32::from(p.0[0])).collect());
+
+        assert!(low <= 4, "{low}");
+        assert!(high > 600, "{high}");
+    }
+
+    #[test]
+    fn add_floor_appends_one_plane() {
+        let source = include_str!("../../scenes/room.scene");
+        let (mut world, _, _) = scene_parser::parse_string(source).unwrap();
+        let planes = |world: &[Object]| {
+            world
+                .iter()
+                .filter(|o| matches!(o.primitive, Primitive::Plane(_)))
+                .count()
+        };
+        let before = (world.len(), planes(&world));
+
+        world.push(floor_object(-1.0));
+        assert_eq!(world.len(), before.0 + 1);
+        assert_eq!(planes(&world), before.1 + 1);
+        assert!(world[before.0]
+            .primitive
+            .contains(Vec3::new(3.0, -2.0, 5.0)));
+        assert!(!world[before.0].primitive.contains(Vec3::new(3.0, 0.0, 5.0)));
+
+        // Neighbouring squares alternate between the two colors
+        let floor = &world[before.0];
+        let Primitive::Plane(plane) = &floor.primitive else {
+            panic!("floor is not a plane");
+        };
+        let color = |x, z| {
+            floor
+                .material
+                .color_at(Some(plane.uv_at(Vec3::new(x, -1.0, z))))
+        };
+        assert_ne!(color(0.5, 0.5), color(1.5, 0.5));
+        assert_ne!(color(0.5, 0.5), color(0.5, 1.5));
+        assert_eq!(color(0.5, 0.5), color(1.5, 1.5));
+    }
+
+    #[test]
+    fn dumped_rays_are_unit() {
+        let camera =
+            Camera::new(32, 16, Vec3::zero(), Vec3::new(1.0, 2.0, 3.0), 90.0, 17.0).unwrap();
+        let csv = dump_rays(&Raytracer::new(camera, 1), 100, BASE_SEED);
+
+        let rows: Vec<Vec<f64>> = csv
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(rows.len(), 100);
+
+        for row in rows {
+            assert!(row[0] < 32.0 && row[1] < 16.0);
+            let dir = Vec3::new(row[5], row[6], row[7]);
+            assert!((dir.length() - 1.0).abs() < 1e-9, "{dir:?}");
+        }
+    }
+
+    #[test]
+    fn seed_per_frame() {
+        let camera =
+            Camera::new(32, 16, Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 90.0, 0.0).unwrap();
+        let raytracer = Raytracer::new(camera, 1);
+        let frame = |frame, per_frame| dump_rays(&raytracer, 20, frame_seed(frame, per_frame));
+
+        assert_eq!(frame(0, false), frame(1, false));
+        assert_ne!(frame(0, true), frame(1, true));
+        assert_eq!(frame(3, true), frame(3, true));
+
+        let source = include_str!("../../scenes/room.scene");
+        let (world, lights, mut raytracer) = scene_parser::parse_string(source).unwrap();
+        raytracer.set_width(12);
+        raytracer.set_height(8);
+        raytracer.set_pixel_jitter(true);
+        let mut render = |frame, per_frame| {
+            raytracer.set_seed(frame_seed(frame, per_frame));
+            raytracer.raycast(&world, &lights)
+        };
+
+        // The jittered pixels are the only difference between the frames
+        assert_eq!(render(0, false), render(1, false));
+        assert_ne!(render(0, true), render(1, true));
+    }
+
+    #[test]
+    fn pass_file_names() {
+        assert_eq!(
+            pass_file_name(Path::new("/tmp/render.png"), 3),
+            PathBuf::from("/tmp/render.pass3.png")
+        );
+        assert_eq!(
+            pass_file_name(Path::new("render"), 12),
+            PathBuf::from("render.pass12")
+        );
+    }
+
+    #[test]
+    fn resumed_passes_match_uninterrupted() {
+        let source = include_str!("../../scenes/room.scene");
+        let (world, lights, mut raytracer) = scene_parser::parse_string(source).unwrap();
+        raytracer.set_width(12);
+        raytracer.set_height(8);
+        raytracer.set_pixel_jitter(true);
+
+        let mut pass = |accumulator: &mut Accumulator, seed| {
+            raytracer.set_seed(seed);
+            accumulator.add_pass(&raytracer.raycast(&world, &lights));
+        };
+
+        let mut uninterrupted = Accumulator::new();
+        pass(&mut uninterrupted, 0);
+        pass(&mut uninterrupted, 1);
+
+        let mut first = Accumulator::new();
+        pass(&mut first, 0);
+        let path = std::env::temp_dir().join("raytrace-rs-resume-test.accum.json");
+        save_accumulation(&first, &path).unwrap();
+
+        assert!(load_accumulation(&path, (8, 8)).is_err());
+        let mut resumed = load_accumulation(&path, (12, 8)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.passes(), 1);
+        pass(&mut resumed, 1);
+
+        assert_eq!(resumed.passes(), 2);
+        assert_eq!(resumed.image(), uninterrupted.image());
+        assert_eq!(
+            accumulation_file_name(Path::new("/tmp/render.png")),
+            PathBuf::from("/tmp/render.accum.json")
+        );
+    }
+
+    #[test]
+    fn term_file_names() {
+        assert_eq!(
+            term_file_name(Path::new("/tmp/render.png"), "specular"),
+            PathBuf::from("/tmp/render_specular.png")
+        );
+        assert_eq!(
+            term_file_name(Path::new("render"), "ambient"),
+            PathBuf::from("render_ambient")
+        );
+    }
+
+    #[test]
+    fn stereo_side_by_side() {
+        let left = vec![vec![Color::new(255, 0, 0); 2]; 3];
+        let right = vec![vec![Color::new(0, 0, 255); 4]; 3];
+        let joined = side_by_side(left, right);
+        assert_eq!(joined.len(), 3);
+        for row in joined {
+            assert_eq!(row.len(), 6);
+            assert_eq!(row[1], Color::new(255, 0, 0));
+            assert_eq!(row[2], Color::new(0, 0, 255));
+        }
+    }
+
+    #[test]
+    fn save_scene_round_trip() {
+        let source = include_str!("../../scenes/room.scene");
+        let (world, lights, mut raytracer) = scene_parser::parse_string(source).unwrap();
+        raytracer.set_width(24);
+        raytracer.set_height(16);
+        let scene = Scene::new(raytracer, world, lights);
+
+        let path = std::env::temp_dir().join("raytrace-rs-save-scene-test.json");
+        save_scene(&scene, &path).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let reloaded: Scene = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.objects(), scene.objects());
+        assert_eq!(reloaded.lights(), scene.lights());
+        assert_eq!(reloaded.render(), scene.render());
+    }
+
+    #[test]
+    fn read_scene_from_reader() {
+        let scene = include_str!("../../scenes/room.scene");
+        let source = read_source(std::io::Cursor::new(scene)).unwrap();
+        assert!(scene_parser::parse_string(&source).is_ok());
+
+        let broken = read_source("Camera {\n  pos: (0,0,0),\n  dir: ?\n}".as_bytes()).unwrap();
+        let err = scene_parser::parse_string(&broken).unwrap_err().to_string();
+        assert!(err.contains("3 |"), "{err}");
+    }
+}
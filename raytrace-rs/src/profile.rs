@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+/// Accumulated wall-clock time for each stage of a render.
+#[derive(Debug, Default)]
+pub struct Profile {
+    /// Stages in the order they were first timed.
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl Profile {
+    /// Run `f` and add the time it took to `stage`.
+    pub fn time<T, F: FnOnce() -> T>(&mut self, stage: &'static str, f: F) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add(stage, start.elapsed());
+        result
+    }
+
+    /// Run the render `f` and add the time it took to "trace", except for the time
+    /// it reports building bounding volume hierarchies, which goes to "build".
+    pub fn render<T, F: FnOnce() -> (T, Duration)>(&mut self, f: F) -> T {
+        let start = Instant::now();
+        let (result, build) = f();
+        let elapsed = start.elapsed();
+
+        self.add("build", build);
+        self.add("trace", elapsed.saturating_sub(build));
+        result
+    }
+
+    pub fn add(&mut self, stage: &'static str, duration: Duration) {
+        if let Some((_, total)) = self.stages.iter_mut().find(|(name, _)| *name == stage) {
+            *total += duration;
+        } else {
+            self.stages.push((stage, duration));
+        }
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total: Duration = self.stages.iter().map(|(_, d)| *d).sum();
+
+        for (name, duration) in &self.stages {
+            writeln!(f, "{name:>8}: {:>10.2} ms", duration.as_secs_f64() * 1000.0)?;
+        }
+        write!(
+            f,
+            "{:>8}: {:>10.2} ms",
+            "total",
+            total.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_stages() {
+        let mut profile = Profile::default();
+        profile.add("parse", Duration::from_millis(3));
+        profile.add("trace", Duration::from_millis(10));
+        profile.add("parse", Duration::from_millis(4));
+
+        assert_eq!(
+            profile.stages,
+            vec![
+                ("parse", Duration::from_millis(7)),
+                ("trace", Duration::from_millis(10)),
+            ]
+        );
+
+        let value = profile.time("encode", || 42);
+        assert_eq!(value, 42);
+        assert_eq!(profile.stages.len(), 3);
+        assert_eq!(profile.stages[2].0, "encode");
+
+        let report = profile.to_string();
+        assert!(report.find("parse").unwrap() < report.find("trace").unwrap());
+        assert!(report.contains("total"));
+    }
+
+    #[test]
+    fn render_separates_build() {
+        let mut profile = Profile::default();
+        let value = profile.render(|| (42, Duration::from_millis(3)));
+        assert_eq!(value, 42);
+
+        let names: Vec<_> = profile.stages.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["build", "trace"]);
+        assert_eq!(profile.stages[0].1, Duration::from_millis(3));
+    }
+}
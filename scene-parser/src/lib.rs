@@ -4,7 +4,8 @@ mod scene_builder;
 mod scene_object;
 
 use lalrpop_util::ParseError;
-use raytrace_lib::{Light, Object, Raytracer};
+use lit::Bindings;
+use raytrace_lib::{color::ColorNames, Light, Object, Raytracer};
 
 #[macro_use]
 extern crate lalrpop_util;
@@ -56,6 +57,17 @@ impl ParseStringError {
         start: &Location,
         end: Option<&Location>,
         message: String,
+    ) -> Self {
+        Self::annotate_with_level(source_lines, start, end, "error", message)
+    }
+
+    /// Annotate a diagnostic like cargo, prefixed with `level`.
+    fn annotate_with_level(
+        source_lines: &[&str],
+        start: &Location,
+        end: Option<&Location>,
+        level: &str,
+        message: String,
     ) -> Self {
         let line = match source_lines.get(start.line - 1) {
             Some(line) => line,
@@ -80,7 +92,7 @@ impl ParseStringError {
 
         Self::Annotated(format!(
             "
-error: {message}
+{level}: {message}
 {spaces} |
 {} | {line}
 {spaces} |{before}{under}
@@ -109,6 +121,12 @@ pub enum SceneParseError {
         name: String,
         end: usize,
     },
+    /// A name that no `let` before it binds.
+    UnknownIdentifier {
+        start: usize,
+        name: String,
+        end: usize,
+    },
     DuplicateKey {
         start: usize,
         key: String,
@@ -164,7 +182,22 @@ impl SceneParseError {
                     input_lines,
                     &start,
                     Some(&end),
-                    format!("Unknown color '{name}'"),
+                    match ColorNames::suggest(&name) {
+                        Some(suggestion) => {
+                            format!("Unknown color '{name}', did you mean '{suggestion}'?")
+                        }
+                        None => format!("Unknown color '{name}'"),
+                    },
+                )
+            }
+            Self::UnknownIdentifier { start, name, end } => {
+                let start = Location::new(start, input_string);
+                let end = Location::new(end, input_string);
+                ParseStringError::annotate(
+                    input_lines,
+                    &start,
+                    Some(&end),
+                    format!("Unknown identifier '{name}'"),
                 )
             }
             Self::DuplicateKey { start, key } => {
@@ -227,6 +260,40 @@ impl SceneParseError {
     }
 }
 
+/// A problem in a scene that does not stop it from being rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneWarning {
+    start: usize,
+    message: String,
+    end: Option<usize>,
+}
+
+impl SceneWarning {
+    pub fn new(start: usize, message: String, end: Option<usize>) -> Self {
+        Self {
+            start,
+            message,
+            end,
+        }
+    }
+
+    /// Annotate the warning against the source like cargo.
+    pub fn into_string(self, input_string: &str) -> String {
+        let input_lines = &input_string.lines().collect::<Vec<_>>();
+        let start = Location::new(self.start, input_string);
+        let end = self.end.map(|end| Location::new(end, input_string));
+
+        ParseStringError::annotate_with_level(
+            input_lines,
+            &start,
+            end.as_ref(),
+            "warning",
+            self.message,
+        )
+        .to_string()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Ident {
     start: usize,
@@ -268,12 +335,24 @@ impl Location {
     }
 }
 
-pub fn parse_string(s: &str) -> Result<(Vec<Object>, Vec<Light>, Raytracer), ParseStringError> {
+/// The world, lights and raytracer described by a scene file.
+pub type ParsedScene = (Vec<Object>, Vec<Light>, Raytracer);
+
+pub fn parse_string(s: &str) -> Result<ParsedScene, ParseStringError> {
+    parse_string_with_warnings(s).map(|(scene, _)| scene)
+}
+
+/// Like [`parse_string`] but also returns annotated warnings about
+/// problems that don't stop the scene from being rendered.
+pub fn parse_string_with_warnings(s: &str) -> Result<(ParsedScene, Vec<String>), ParseStringError> {
     let source_lines = &s.lines().collect::<Vec<_>>();
 
-    match scene::SceneParser::new().parse(s) {
+    match scene::SceneParser::new().parse(&Bindings::default(), s) {
         Ok(scene) => match scene {
-            Ok(raytracer) => Ok(raytracer),
+            Ok((objects, lights, raytracer, warnings)) => Ok((
+                (objects, lights, raytracer),
+                warnings.into_iter().map(|w| w.into_string(s)).collect(),
+            )),
             Err(scene_parse_error) => Err(ParseStringError::Many(
                 scene_parse_error
                     .into_iter()
@@ -315,9 +394,7 @@ pub fn parse_string(s: &str) -> Result<(Vec<Object>, Vec<Light>, Raytracer), Par
                 Some(&Location::new(r, s)),
                 t.to_string(),
             ),
-            ParseError::User { error } => ParseStringError::User {
-                error: error.to_string(),
-            },
+            ParseError::User { error } => error.into_parse_string_error(s),
         }),
     }
 }
@@ -325,6 +402,19 @@ pub fn parse_string(s: &str) -> Result<(Vec<Object>, Vec<Light>, Raytracer), Par
 #[cfg(test)]
 mod tests {
     use super::*;
+    use raytrace_lib::{
+        material::{MaterialTemplate, DEFAULT_IOR},
+        primitive::{Intersectable, Primitive},
+        ray::Ray,
+        texture::Texture,
+        Camera, Color, Vec3,
+    };
+
+    /// Returns a scene with `prelude` before an 8x8 camera at the origin
+    /// looking along the z-axis, and `body` after it.
+    fn scene_with(prelude: &str, body: &str) -> String {
+        format!("{prelude}\nCamera {{ width: 8, height: 8, pos: (0,0,0), dir: (0,0,1) }}\n{body}")
+    }
 
     #[test]
     fn test_parse() {
@@ -363,12 +453,922 @@ mod tests {
             }
         "#
         .trim();
-        let parsed = parse_string(&s);
-        if !parsed.is_ok() {
-            panic!("Expected Ok: {}", parsed.unwrap_err());
+        let parsed = parse_string(s);
+        if let Err(e) = parsed {
+            panic!("Expected Ok: {e}");
         }
     }
 
+    #[test]
+    fn object_name() {
+        let s = r#"
+            Camera {
+                width: 512,
+                height: 512,
+                pos: (0,0,0),
+                dir: (0,0,1),
+            }
+
+            Sphere {
+                name: "ball",
+                priority: -2,
+                pos: (0,0,5),
+                r: 1,
+                material: {
+                    color: (255, 0, 0),
+                    template: "bronze",
+                }
+            }
+        "#
+        .trim();
+
+        let (world, _, _) = parse_string(s).unwrap();
+        assert_eq!(world[0].name.as_deref(), Some("ball"));
+        assert_eq!(world[0].priority, -2);
+    }
+
+    #[test]
+    fn global_epsilon() {
+        let scene = |global: &str| scene_with(global, "Light { pos: (0,1,0), intensity: 1 }");
+
+        let (_, _, raytracer) = parse_string(&scene("")).unwrap();
+        assert_eq!(raytracer.epsilon(), raytrace_lib::DISTANCE_EPS);
+
+        let (_, _, raytracer) = parse_string(&scene("Global { epsilon: 0.01 }")).unwrap();
+        assert_eq!(raytracer.epsilon(), 0.01);
+
+        assert!(parse_string(&scene("Global { epsilon: 0 }")).is_err());
+    }
+
+    #[test]
+    fn global_background() {
+        let scene = |global: &str| scene_with(global, "Light { pos: (0,1,0), intensity: 1 }");
+        let blue = raytrace_lib::Color::new(0, 0, 255);
+
+        let (_, _, raytracer) = parse_string(&scene("")).unwrap();
+        assert_eq!(raytracer.background(), raytrace_lib::Color::zero());
+
+        let (_, _, raytracer) = parse_string(&scene("Global { background: (0,0,255) }")).unwrap();
+        assert_eq!(raytracer.background(), blue);
+
+        let (world, lights, raytracer) =
+            parse_string(&scene(r#"Global { background: "blue" }"#)).unwrap();
+        assert_eq!(raytracer.background(), blue);
+        let image = raytracer.raycast(&world, &lights);
+        assert!(image.iter().flatten().all(|&c| c == blue));
+
+        assert!(parse_string(&scene("Global { background: 3 }")).is_err());
+    }
+
+    #[test]
+    fn global_samples() {
+        let scene = |global: &str| scene_with(global, "Light { pos: (0,1,0), intensity: 1 }");
+
+        let (_, _, raytracer) = parse_string(&scene("")).unwrap();
+        assert_eq!(raytracer.samples_per_pixel(), 1);
+
+        let (_, _, raytracer) = parse_string(&scene("Global { samples: 4 }")).unwrap();
+        assert_eq!(raytracer.samples_per_pixel(), 4);
+
+        assert!(parse_string(&scene("Global { samples: 0 }")).is_err());
+        assert!(parse_string(&scene("Global { samples: 1.5 }")).is_err());
+    }
+
+    #[test]
+    fn light_radiant_power() {
+        let scene = |light: &str| scene_with("", &format!("Light {{ pos: (0,1,0), {light} }}"));
+
+        let (_, lights, _) = parse_string(&scene("intensity: 0.5")).unwrap();
+        assert_eq!(lights[0].radiant_power, None);
+
+        let (_, lights, _) = parse_string(&scene("radiant_power: 100")).unwrap();
+        assert_eq!(lights[0].radiant_power, Some(100.0));
+
+        let both = scene("intensity: 1, radiant_power: 1");
+        assert!(parse_string(&both).is_err());
+        assert!(parse_string(&scene("radiant_power: -1")).is_err());
+    }
+
+    #[test]
+    fn light_shadow_options() {
+        let scene = |light: &str| scene_with("", &format!("Light {{ pos: (0,1,0),\n{light} }}"));
+
+        let (_, lights, _) = parse_string(&scene("intensity: 1")).unwrap();
+        assert_eq!(lights[0].shadow_radius, 0.0);
+
+        let soft = "intensity: 1, shadow_samples: 16, shadow_radius: 0.5";
+        let (_, lights, _) = parse_string(&scene(soft)).unwrap();
+        assert_eq!(lights[0].shadow_samples, 16);
+        assert_eq!(lights[0].shadow_radius, 0.5);
+
+        assert!(parse_string(&scene("intensity: 1, shadow_radius: -1")).is_err());
+    }
+
+    #[test]
+    fn light_attenuation() {
+        let scene = |light: &str| scene_with("", &format!("Light {{ pos: (0,1,0),\n{light} }}"));
+
+        let (_, lights, _) = parse_string(&scene("intensity: 1")).unwrap();
+        assert_eq!(lights[0].attenuation, Light::INVERSE_SQUARE);
+
+        let source = scene("intensity: 1, attenuation: (1, 0.5, 0.25)");
+        let (_, lights, _) = parse_string(&source).unwrap();
+        assert_eq!(lights[0].attenuation, (1.0, 0.5, 0.25));
+
+        let err = |light| parse_string(&scene(light)).unwrap_err().to_string();
+        let zero = err("intensity: 1, attenuation: (0, 0, 0)");
+        assert!(zero.contains("not all zero"), "{zero}");
+        let power = err("radiant_power: 1, attenuation: (0, 0, 1)");
+        assert!(power.contains("distance squared"), "{power}");
+    }
+
+    #[test]
+    fn global_scale() {
+        let body = r#"
+            Sphere {
+                pos: (0,0,5),
+                r: 1,
+                material: {
+                    color: (255, 0, 0),
+                    template: "bronze",
+                }
+            }
+
+            Light { pos: (0,1,0), intensity: 1 }
+        "#;
+        let scene = |scale: &str| scene_with(&format!("Global {{ scale: {scale} }}"), body);
+
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        let hit_distance = |s: &str| {
+            let (world, lights, _) = parse_string(s).unwrap();
+            let dist = world[0].intersection(&ray).unwrap().pos.length();
+            (dist, lights[0].pos)
+        };
+
+        let (unscaled, light) = hit_distance(&scene("1"));
+        assert!((unscaled - 4.0).abs() < 1e-9);
+        assert_eq!(light, Vec3::new(0.0, 1.0, 0.0));
+
+        let (scaled, light) = hit_distance(&scene("2.0"));
+        assert!((scaled - 8.0).abs() < 1e-9);
+        assert_eq!(light, Vec3::new(0.0, 2.0, 0.0));
+
+        assert!(parse_string(&scene("-1")).is_err());
+
+        // Lengths of the camera scale along with its position
+        let s = r#"
+            Global { scale: 2 }
+            Camera {
+                width: 8, height: 8, pos: (0,1,0), dir: (0,0,1),
+                projection: "orthographic", ortho_scale: 1.5,
+                aperture: 0.1, focus_distance: 5,
+            }
+        "#;
+        let (_, _, raytracer) = parse_string(s).unwrap();
+        let camera = raytracer.camera();
+        assert_eq!(camera.position(), Vec3::new(0.0, 2.0, 0.0));
+        assert_eq!(camera.aperture(), 0.2);
+        assert_eq!(camera.focus_distance(), 10.0);
+        assert_eq!(
+            camera.projection(),
+            raytrace_lib::Projection::Orthographic { scale: 3.0 }
+        );
+    }
+
+    #[test]
+    fn camera_fov_axis() {
+        let scene = |axis: &str| {
+            format!(
+                r#"Camera {{ width: 8, height: 4, pos: (0,0,0), dir: (0,0,1), fov_axis: "{axis}" }}"#
+            )
+        };
+
+        let (_, _, raytracer) = parse_string(&scene("vertical")).unwrap();
+        let mut expected = raytracer.camera().clone();
+        expected.set_fov_axis(raytrace_lib::FovAxis::Vertical);
+        assert_eq!(raytracer.camera(), &expected);
+
+        let (_, _, raytracer) = parse_string(&scene("horizontal")).unwrap();
+        assert_ne!(raytracer.camera(), &expected);
+
+        assert!(parse_string(&scene("diagonal")).is_err());
+    }
+
+    #[test]
+    fn angle_suffixes() {
+        let camera = |angles: &str| {
+            let source = format!(
+                r#"
+                Camera {{
+                    width: 8,
+                    height: 8,
+                    pos: (0,0,0),
+                    dir: (0,0,1),
+                    {angles}
+                }}
+                "#
+            );
+            parse_string(&source).unwrap().2.camera().clone()
+        };
+
+        let corner_ray = |camera: Camera| camera.ray_from_pixel(3.0, 7.0).unwrap().direction();
+
+        let degrees = corner_ray(camera("fov: 90deg, roll: 30deg"));
+        let radians = corner_ray(camera("fov: 1.5708rad, roll: 0.5236rad"));
+        assert!((degrees - radians).length() < 1e-4);
+        assert_eq!(corner_ray(camera("fov: 90, roll: 30")), degrees);
+
+        let err = parse_string("Light { pos: (0,1,0), intensity: 2deg }")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Only angles can have"), "{err}");
+    }
+
+    #[test]
+    fn camera_look_at() {
+        let camera = |aim: &str| {
+            let source = format!(
+                r#"
+                Camera {{
+                    width: 8, height: 8, pos: (1,2,3),
+                    {aim}
+                }}
+                "#
+            );
+            parse_string(&source).map(|scene| scene.2.camera().clone())
+        };
+
+        let by_dir = camera("dir: (3,4,12)").unwrap();
+        assert_eq!(camera("look_at: (4,6,15)").unwrap(), by_dir);
+        assert_eq!(camera("look_at: (7,10,27), roll: 0").unwrap(), by_dir);
+
+        let err = camera("dir: (0,0,1), look_at: (0,0,5)")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("both dir and look_at"), "{err}");
+        assert!(camera("look_at: (1,2,3)").is_err());
+    }
+
+    #[test]
+    fn camera_up() {
+        let camera = |options: &str| {
+            let source = format!(
+                r#"
+                Camera {{
+                    width: 8, height: 8, pos: (0,0,0),
+                    {options}
+                }}
+                "#
+            );
+            parse_string(&source).map(|scene| scene.2.camera().clone())
+        };
+        let ray = |camera: Camera| camera.ray_from_pixel(1.0, 2.0).unwrap().direction();
+
+        let level = camera("dir: (0,0,1)").unwrap();
+        assert_eq!(camera("dir: (0,0,1), up: (0,2,0)").unwrap(), level);
+
+        let sideways = ray(camera("dir: (0,0,1), up: (1,0,0)").unwrap());
+        let rolled = ray(camera("dir: (0,0,1), roll: -90").unwrap());
+        assert!((sideways - rolled).length() < 1e-9);
+
+        // Looking straight down with north at the top of the image
+        let down = camera("look_at: (0,-5,0), up: (0,0,1)").unwrap();
+        assert_eq!(down.view_dir(), Vec3::new(0.0, -1.0, 0.0));
+        assert!(down.ray_from_pixel(0.0, 0.0).unwrap().direction().z > 0.0);
+
+        assert!(camera("dir: (0,0,1), up: (0,0,0)").is_err());
+    }
+
+    #[test]
+    fn camera_projection() {
+        let scene = |projection: &str| {
+            format!(
+                r#"
+                Camera {{
+                    width: 8,
+                    height: 8,
+                    pos: (0,0,0),
+                    dir: (0,0,1),
+                    fov: 200,
+                    projection: "{projection}"
+                }}
+                "#
+            )
+        };
+
+        let (_, _, raytracer) = parse_string(&scene("fisheye")).unwrap();
+        assert_eq!(
+            raytracer.camera().projection(),
+            raytrace_lib::Projection::Fisheye {
+                fov: 200f64.to_radians()
+            }
+        );
+
+        let (_, _, raytracer) = parse_string(&scene("pinhole")).unwrap();
+        assert_eq!(
+            raytracer.camera().projection(),
+            raytrace_lib::Projection::Pinhole
+        );
+
+        assert!(parse_string(&scene("panini")).is_err());
+    }
+
+    #[test]
+    fn camera_orthographic() {
+        let scene = |options: &str| {
+            format!(
+                r#"
+                Camera {{
+                    width: 8,
+                    height: 8,
+                    pos: (0,0,0),
+                    dir: (0,0,1),
+                    projection: "orthographic",
+                    {options}
+                }}
+                "#
+            )
+        };
+
+        let (_, _, raytracer) = parse_string(&scene("")).unwrap();
+        assert_eq!(
+            raytracer.camera().projection(),
+            raytrace_lib::Projection::Orthographic { scale: 1.0 }
+        );
+
+        let (_, _, raytracer) = parse_string(&scene("ortho_scale: 2.5")).unwrap();
+        assert_eq!(
+            raytracer.camera().projection(),
+            raytrace_lib::Projection::Orthographic { scale: 2.5 }
+        );
+
+        assert!(parse_string(&scene("ortho_scale: 0")).is_err());
+    }
+
+    #[test]
+    fn camera_depth_of_field() {
+        let scene = |lens: &str| {
+            format!(
+                r#"
+                Camera {{
+                    width: 8,
+                    height: 8,
+                    pos: (0,0,0),
+                    dir: (0,0,1),
+                    {lens}
+                }}
+                "#
+            )
+        };
+
+        let (_, _, raytracer) = parse_string(&scene("")).unwrap();
+        assert_eq!(raytracer.camera().aperture(), 0.0);
+
+        let lens = "aperture: 0.2, focus_distance: 5";
+        let (_, _, raytracer) = parse_string(&scene(lens)).unwrap();
+        assert_eq!(raytracer.camera().aperture(), 0.2);
+        assert_eq!(raytracer.camera().focus_distance(), 5.0);
+
+        assert!(parse_string(&scene("aperture: -1")).is_err());
+        assert!(parse_string(&scene("focus_distance: 0")).is_err());
+    }
+
+    #[test]
+    fn default_material() {
+        let body = r#"
+            Sphere { pos: (0,0,5), r: 1 }
+            Sphere {
+                pos: (0,3,5),
+                r: 1,
+                material: { color: (0,0,255), template: "blue" }
+            }
+            Light { pos: (0,1,0), intensity: 1 }
+        "#;
+        let scene = |global: &str| scene_with(global, body);
+
+        assert!(parse_string(&scene("")).is_err());
+
+        let (world, _, _) = parse_string(&scene(
+            r#"Global { default_material: { color: (255,0,0), template: "red" } }"#,
+        ))
+        .unwrap();
+
+        let red = MaterialTemplate::Red.get_material(raytrace_lib::Color::new(255, 0, 0));
+        let blue = MaterialTemplate::Blue.get_material(raytrace_lib::Color::new(0, 0, 255));
+        assert_eq!(world[0].material, red);
+        assert_eq!(world[1].material, blue);
+    }
+
+    #[test]
+    fn named_materials() {
+        let scene = |extra: &str| {
+            let body = format!(
+                r#"
+                Sphere {{ pos: (0,0,5), r: 1, material: "shiny" }}
+                Sphere {{ pos: (0,3,5), r: 1, material: "shiny" }}
+                Material shiny {{ color: (0,0,255), template: "blue" }}
+                Light {{ pos: (0,1,0), intensity: 1 }}
+                {extra}
+                "#
+            );
+            scene_with("", &body)
+        };
+
+        let (world, _, _) = parse_string(&scene("")).unwrap();
+        let blue = MaterialTemplate::Blue.get_material(raytrace_lib::Color::new(0, 0, 255));
+        assert_eq!(world[0].material, blue);
+        assert_eq!(world[1].material, blue);
+
+        let err = parse_string(&scene(r#"Sphere { pos: (0,0,9), r: 1, material: "x" }"#))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Unknown material 'x'"), "{err}");
+
+        let err = parse_string(&scene(
+            r#"Material shiny {
+                color: "red", template: "red"
+            }"#,
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("already defined"), "{err}");
+    }
+
+    #[test]
+    fn material_clearcoat() {
+        let scene = |material: &str| {
+            let body = format!(
+                r#"
+                Sphere {{ pos: (0,0,5), r: 1, material: {{ color: (255,0,0), {material} }} }}
+                Light {{ pos: (0,1,0), intensity: 1 }}
+                "#
+            );
+            scene_with("", &body)
+        };
+
+        let (world, _, _) = parse_string(&scene(
+            r#"template: "red", clearcoat: 0.8, clearcoat_roughness: 0.05"#,
+        ))
+        .unwrap();
+        assert_eq!(world[0].material.clearcoat, 0.8);
+        assert_eq!(world[0].material.clearcoat_roughness, 0.05);
+
+        let (world, _, _) = parse_string(&scene(
+            "lambert: (1,1,1), specular: (0,0,0), ambient: (0,0,0), clearcoat: 1",
+        ))
+        .unwrap();
+        assert_eq!(world[0].material.clearcoat, 1.0);
+
+        assert!(parse_string(&scene(r#"template: "red", clearcoat: 1.5"#)).is_err());
+        assert!(parse_string(&scene(r#"template: "red", clearcoat_roughness: 0"#)).is_err());
+    }
+
+    #[test]
+    fn material_opacity() {
+        let scene = |material: &str| {
+            let body = format!(
+                "Sphere {{ pos: (0,0,5), r: 1, material: {{ color: (255,0,0), {material} }} }}"
+            );
+            scene_with("", &body)
+        };
+
+        let (world, _, _) = parse_string(&scene(r#"template: "red""#)).unwrap();
+        assert_eq!(world[0].material.opacity, 1.0);
+
+        let (world, _, _) = parse_string(&scene(r#"template: "red", opacity: 0.5"#)).unwrap();
+        assert_eq!(world[0].material.opacity, 0.5);
+
+        assert!(parse_string(&scene(r#"template: "red", opacity: 2"#)).is_err());
+    }
+
+    #[test]
+    fn material_refraction() {
+        let scene = |material: &str| {
+            let body = format!(
+                "Sphere {{ pos: (0,0,5), r: 1, material: {{ color: (255,0,0), {material} }} }}"
+            );
+            scene_with("", &body)
+        };
+
+        let (world, _, _) = parse_string(&scene(r#"template: "red""#)).unwrap();
+        assert_eq!(world[0].material.refraction, 0.0);
+        assert_eq!(world[0].material.ior, DEFAULT_IOR);
+
+        let glass = r#"template: "red", refraction: 0.9, ior: 1.33"#;
+        let (world, _, _) = parse_string(&scene(glass)).unwrap();
+        assert_eq!(world[0].material.refraction, 0.9);
+        assert_eq!(world[0].material.ior, 1.33);
+
+        assert!(parse_string(&scene(r#"template: "red", refraction: 2"#)).is_err());
+        assert!(parse_string(&scene(r#"template: "red", ior: 0"#)).is_err());
+    }
+
+    #[test]
+    fn material_shininess() {
+        let scene = |material: &str| {
+            let body = format!(
+                "Sphere {{ pos: (0,0,5), r: 1, material: {{ color: (255,0,0), {material} }} }}"
+            );
+            scene_with("", &body)
+        };
+
+        let (world, _, _) = parse_string(&scene(r#"template: "red""#)).unwrap();
+        assert_eq!(world[0].material.shininess, 0.0);
+
+        let (world, _, _) = parse_string(&scene(r#"template: "red", shininess: 32"#)).unwrap();
+        assert_eq!(world[0].material.shininess, 32.0);
+
+        assert!(parse_string(&scene(r#"template: "red", shininess: -1"#)).is_err());
+    }
+
+    #[test]
+    fn material_texture() {
+        let scene = |texture: &str| {
+            let body = format!(
+                r#"
+                Sphere {{
+                    pos: (0,0,5),
+                    r: 1,
+                    material: {{
+                        color: (255,0,0),
+                        template: "red",
+                        texture: {{
+                            {texture}
+                        }}
+                    }}
+                }}
+                Light {{ pos: (0,1,0), intensity: 1 }}
+                "#
+            );
+            scene_with("", &body)
+        };
+
+        let (world, _, _) = parse_string(&scene(
+            r#"type: "lat_long_grid", line_color: (255,255,255), bg_color: (0,0,0), divisions: 8"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            world[0].material.texture,
+            Some(Texture::LatLongGrid {
+                line_color: raytrace_lib::Color::new(255, 255, 255),
+                bg_color: raytrace_lib::Color::new(0, 0, 0),
+                divisions: 8,
+            })
+        );
+
+        let err = parse_string(&scene(r#"type: "marble""#))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("No texture named 'marble'"), "{err}");
+        assert!(parse_string(&scene(r#"type: "lat_long_grid", divisions: 8"#)).is_err());
+    }
+
+    #[test]
+    fn material_checker_color() {
+        let scene = |color: &str| {
+            let body = format!(
+                r#"
+                Plane {{
+                    point: (0,-1,0),
+                    normal: (0,1,0),
+                    material: {{ color: {color}, template: "red" }}
+                }}
+                "#
+            );
+            scene_with("", &body)
+        };
+        let white = raytrace_lib::Color::new(255, 255, 255);
+        let black = raytrace_lib::Color::new(0, 0, 0);
+
+        let (world, _, _) =
+            parse_string(&scene(r#"{ checker: ("white","black"), scale: 0.5 }"#)).unwrap();
+        assert_eq!(world[0].material.color, white);
+        assert_eq!(
+            world[0].material.texture,
+            Some(Texture::Checker {
+                a: white,
+                b: black,
+                scale: 0.5
+            })
+        );
+
+        let (world, _, _) = parse_string(&scene(r#"{ checker: ((0,0,0),"white") }"#)).unwrap();
+        assert_eq!(
+            world[0].material.texture,
+            Some(Texture::Checker {
+                a: black,
+                b: white,
+                scale: 1.0
+            })
+        );
+
+        assert!(parse_string(&scene(r#"{ checker: ("white") }"#)).is_err());
+        assert!(parse_string(&scene(r#"{ checker: ("white","black"), scale: 0 }"#)).is_err());
+        assert!(parse_string(&scene(r#"{ scale: 1.0 }"#)).is_err());
+    }
+
+    #[test]
+    fn duplicate_global_blocks() {
+        let s = r#"
+            Global { recurse_depth: 2 }
+            Camera { width: 8, height: 8, pos: (0,0,0), dir: (0,0,1) }
+            Global { recurse_depth: 3 }
+        "#;
+
+        let err = parse_string(s).unwrap_err().to_string();
+        assert!(err.contains("Only one global block"), "{err}");
+        // The error points at the second block
+        assert!(err.contains("4 |"), "{err}");
+    }
+
+    #[test]
+    fn duplicate_lights_warn() {
+        let s = r#"
+            Camera { width: 8, height: 8, pos: (0,0,0), dir: (0,0,1) }
+            Light { pos: (0,1,0), intensity: 1 }
+            Light { pos: (0,1,0), intensity: 1 }
+        "#;
+
+        let (_, lights, _, warnings) = scene::SceneParser::new()
+            .parse(&Bindings::default(), s)
+            .unwrap()
+            .unwrap();
+        assert_eq!(lights.len(), 2);
+        assert_eq!(warnings.len(), 1);
+
+        let warning = warnings[0].clone().into_string(s);
+        assert!(warning.contains("warning: Duplicate light"), "{warning}");
+    }
+
+    #[test]
+    fn warnings_are_returned_with_scene() {
+        let s = "Camera { width: 8, height: 8, pos: (0,0,0), dir: (0,0,1) }";
+
+        let ((world, lights, _), warnings) = parse_string_with_warnings(s).unwrap();
+        assert!(world.is_empty() && lights.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].contains("warning: Scene has no lights"),
+            "{}",
+            warnings[0]
+        );
+        assert!(parse_string(s).is_ok());
+    }
+
+    #[test]
+    fn lightless_scene_warns() {
+        let scene = |emission: &str| {
+            let body = format!(
+                r#"
+                Sphere {{
+                    pos: (0,0,5),
+                    r: 1,
+                    material: {{ color: (255,255,255), template: "red", emission: {emission} }}
+                }}
+                "#
+            );
+            scene_with("", &body)
+        };
+
+        let warnings = |s: &str| {
+            scene::SceneParser::new()
+                .parse(&Bindings::default(), s)
+                .unwrap()
+                .unwrap()
+                .3
+        };
+
+        let source = scene("(0,0,0)");
+        let dark = warnings(&source);
+        assert_eq!(dark.len(), 1);
+        let warning = dark[0].clone().into_string(&source);
+        assert!(warning.contains("no lights"), "{warning}");
+
+        assert!(warnings(&scene("(255,255,255)")).is_empty());
+    }
+
+    #[test]
+    fn parse_box() {
+        let scene = |options: &str| {
+            let body = format!(
+                r#"
+                Box {{
+                    {options},
+                    material: {{ color: (255,0,0), template: "red" }}
+                }}
+                "#
+            );
+            scene_with("", &body)
+        };
+        let cuboid = |options: &str| match parse_string(&scene(options)).unwrap().0[0].primitive {
+            Primitive::Cuboid(c) => (c.min, c.max),
+            ref p => panic!("Expected a box, got {p:?}"),
+        };
+
+        let expected = (Vec3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 2.0, 2.0));
+        assert_eq!(cuboid("min: (1,2,2), max: (-1,0,1)"), expected);
+        assert_eq!(cuboid("pos: (0,1,1.5), size: (2,2,1)"), expected);
+
+        assert!(parse_string(&scene("min: (0,0,0)")).is_err());
+    }
+
+    #[test]
+    fn parse_disk() {
+        let s = r#"
+            Camera { width: 8, height: 8, pos: (0,0,-5), dir: (0,0,1) }
+            Disk {
+                pos: (0,1,2),
+                normal: (0,2,0),
+                r: 1.5,
+                material: { color: (255,0,0), template: "red" }
+            }
+            "#;
+        let (world, _, _) = parse_string(s).unwrap();
+        match world[0].primitive {
+            Primitive::Disk(d) => {
+                assert_eq!(d.center, Vec3::new(0.0, 1.0, 2.0));
+                assert_eq!(d.normal, Vec3::new(0.0, 1.0, 0.0));
+                assert_eq!(d.radius, 1.5);
+            }
+            ref p => panic!("Expected a disk, got {p:?}"),
+        }
+    }
+
+    #[test]
+    fn repeat_objects() {
+        let scene = |count: &str| {
+            let body = format!(
+                r#"
+                repeat {count} {{
+                    Sphere {{
+                        pos: ($i, 0, 0),
+                        r: 0.25,
+                        material: {{ color: (255,0,0), template: "red" }}
+                    }}
+                }}
+                Light {{ pos: (0,5,0), intensity: 1 }}
+                "#
+            );
+            scene_with("", &body)
+        };
+
+        let (world, lights, _) = parse_string(&scene("3")).unwrap();
+        assert_eq!(lights.len(), 1);
+        let centers: Vec<_> = world
+            .iter()
+            .map(|o| match &o.primitive {
+                Primitive::Sphere(s) => s.center,
+                p => panic!("Expected a sphere, got {p:?}"),
+            })
+            .collect();
+        assert_eq!(
+            centers,
+            [0.0, 1.0, 2.0].map(|x| Vec3::new(x, 0.0, 0.0)).to_vec()
+        );
+
+        assert!(parse_string(&scene("0")).unwrap().0.is_empty());
+        for count in ["100000", "3000000000", "99999999999999999999"] {
+            let err = parse_string(&scene(count)).unwrap_err().to_string();
+            assert!(err.contains("Repeat count must be"), "{err}");
+        }
+
+        // `$i` only has a value inside `repeat`
+        let s = "Camera { width: 8, height: 8, pos: $i, dir: (0,0,1) }";
+        let err = parse_string(s).unwrap_err().to_string();
+        assert!(err.contains("$i"), "{err}");
+    }
+
+    #[test]
+    fn arithmetic() {
+        let sphere = |options: &str| {
+            let source = format!(
+                r#"
+                Camera {{
+                    width: 8, height: 8,
+                    pos: (0,0,0), dir: (0,0,1)
+                }}
+                Sphere {{
+                    {options},
+                    material: {{ color: "red", template: "red" }}
+                }}
+                "#
+            );
+            parse_string(&source).map(|(world, _, _)| match &world[0].primitive {
+                Primitive::Sphere(s) => (s.center, s.radius),
+                p => panic!("Expected a sphere, got {p:?}"),
+            })
+        };
+
+        let (center, radius) = sphere("pos: (1.0 + 2.0, 2 * (3 - 4.5), -(1-2)), r: 7 / 2").unwrap();
+        assert_eq!(center, Vec3::new(3.0, -3.0, 1.0));
+        assert_eq!(radius, 3.5);
+        // Binary minus without spaces, and precedence
+        let (center, _) = sphere("pos: (1-2, -2*-3, 1 + 2 * 3 - 4 / 2), r: 1").unwrap();
+        assert_eq!(center, Vec3::new(-1.0, 6.0, 5.0));
+
+        let err = sphere("pos: (0, 0, 1 / (2 - 2)), r: 1")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Division by zero"), "{err}");
+        assert!(err.contains("7 |"), "{err}");
+
+        assert!(sphere(r#"pos: (0, 0, 1 + "a"), r: 1"#).is_err());
+
+        // Literals are read before the minus sign applies
+        let (center, _) = sphere("pos: (0, 0, -2147483648), r: 1").unwrap();
+        assert_eq!(center.z, -2147483648.0);
+        let err = sphere("pos: (0, 0, 99999999999999999999), r: 1")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Integer '99999999999999999999' is too large"),
+            "{err}"
+        );
+        assert!(err.contains("7 |"), "{err}");
+        assert!(sphere("pos: (0, 0, 1 + 1deg), r: 1").is_err());
+        let err = sphere("pos: (0, 0,\n(-9223372036854775807 - 1) / -1), r: 1")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Integer overflow"), "{err}");
+
+        // Colors stay integers when they divide evenly
+        let s = r#"
+            Camera { width: 8, height: 8, pos: (0,0,0), dir: (0,0,1) }
+            repeat 2 {
+                Sphere {
+                    pos: ($i * 2 + 1, 0, 5),
+                    r: 0.5,
+                    material: { color: (255 / 5, 0, 0), template: "red" }
+                }
+            }
+        "#;
+        let (world, _, _) = parse_string(s).unwrap();
+        assert_eq!(world[1].primitive.bounding_box().unwrap().center().x, 3.0);
+        assert_eq!(world[1].material.color, Color::new(51, 0, 0));
+    }
+
+    #[test]
+    fn let_bindings() {
+        let s = r#"
+            let radius = 2.0;
+            let center = (0, 0, radius * 5);
+            let red = { color: "red", template: "red" };
+            Camera { width: 8, height: 8, pos: (0,0,0), dir: (0,0,1) }
+            Sphere { pos: center, r: radius, material: red }
+            Sphere { pos: (0, 6, 10), r: radius / 2, material: red }
+        "#;
+        let (world, _, _) = parse_string(s).unwrap();
+        let radii: Vec<_> = world
+            .iter()
+            .map(|o| match &o.primitive {
+                Primitive::Sphere(s) => s.radius,
+                p => panic!("Expected a sphere, got {p:?}"),
+            })
+            .collect();
+        assert_eq!(radii, [2.0, 1.0]);
+        let aabb = world[0].primitive.bounding_box().unwrap();
+        assert_eq!(aabb.center(), Vec3::new(0.0, 0.0, 10.0));
+
+        // Names must be bound before they are used
+        let s = r#"
+            Camera { width: 8, height: 8, pos: (0,0,0), dir: (0,0,1) }
+            Sphere {
+                pos: (0, 0, 5), r: radius,
+                material: { color: "red", template: "red" }
+            }
+            let radius = 2.0;
+        "#;
+        let err = parse_string(s).unwrap_err().to_string();
+        assert!(err.contains("Unknown identifier 'radius'"), "{err}");
+        assert!(err.contains("4 |"), "{err}");
+    }
+
+    #[test]
+    fn named_colors() {
+        let scene = |color: &str| {
+            let body = format!(
+                r#"
+                Sphere {{
+                    pos: (0,0,5),
+                    r: 1,
+                    material: {{
+                        color: "{color}",
+                        template: "red"
+                    }}
+                }}
+                Light {{ pos: (0,5,0), intensity: 1 }}
+                "#
+            );
+            scene_with("", &body)
+        };
+
+        let (world, _, _) = parse_string(&scene("tomato")).unwrap();
+        assert_eq!(world[0].material.color, Color::new(255, 99, 71));
+
+        let err = parse_string(&scene("tomatoe")).unwrap_err().to_string();
+        assert!(err.contains("did you mean 'tomato'?"), "{err}");
+    }
+
     #[test]
     fn multiple_keys_error() {
         let s = r#"
@@ -380,7 +1380,7 @@ mod tests {
         "#
         .trim();
 
-        let parsed = parse_string(&s);
+        let parsed = parse_string(s);
         assert!(parsed.is_err(), "{:#?}", parsed);
     }
 
@@ -396,7 +1396,7 @@ mod tests {
         "#
         .trim();
 
-        let parsed = parse_string(&s);
+        let parsed = parse_string(s);
         assert!(parsed.is_err(), "{:#?}", parsed);
     }
 
@@ -418,7 +1418,7 @@ mod tests {
         "#
         .trim();
 
-        let parsed = parse_string(&s);
+        let parsed = parse_string(s);
         assert!(parsed.is_err(), "{:#?}", parsed);
     }
 }
@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use raytrace_lib::color::ColorNames;
@@ -17,32 +19,83 @@ impl SpannedLit {
     pub fn new(start: usize, lit: Lit, end: usize) -> Self {
         Self { start, lit, end }
     }
+
+    pub fn binary(start: usize, op: Op, a: Self, b: Self, end: usize) -> Self {
+        Self::new(start, Lit::Binary(op, Box::new(a), Box::new(b)), end)
+    }
+
+    pub fn negate(start: usize, a: Self, end: usize) -> Self {
+        Self::new(start, Lit::Neg(Box::new(a)), end)
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self.lit, Lit::Object(_))
+    }
+}
+
+/// Arithmetic operator between two numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+        };
+        write!(f, "{op}")
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Lit {
     String(String),
     Double(f64),
-    Int(i32),
+    /// A number with a `deg` or `rad` suffix, in radians.
+    Angle(f64),
+    /// Wider than the integers read from it, so `-2147483648` is `-(2147483648)`.
+    Int(i64),
+    /// A name starting with `$`, replaced by a number inside `repeat`.
+    Variable(String),
+    /// A name bound with `let`, replaced by its value when the object is parsed.
+    Name(String),
     Tuple(Vec<SpannedLit>),
     Object(Vec<(Ident, SpannedLit)>),
+    /// Arithmetic, evaluated when the number is read.
+    Binary(Op, Box<SpannedLit>, Box<SpannedLit>),
+    Neg(Box<SpannedLit>),
 }
 
 const TYPE_STRING: &str = "Str";
 const TYPE_DOUBLE: &str = "f64";
+const TYPE_ANGLE: &str = "angle";
 const TYPE_VEC3: &str = "( f64, f64, f64 )";
 const TYPE_COLOR: &str = "( u8, u8, u8 )";
+const TYPE_COLOR_PAIR: &str = "( color, color )";
 const TYPE_INT: &str = "int";
 const TYPE_U32: &str = "u32";
 const TYPE_U8: &str = "u8";
 const TYPE_OBJECT: &str = "{}";
+const TYPE_NUMBER: &str = "number";
 
 impl std::fmt::Display for SpannedLit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.lit {
             Lit::String(s) => write!(f, "{s}"),
             Lit::Double(d) => write!(f, "{d}"),
+            Lit::Angle(a) => write!(f, "{a}rad"),
             Lit::Int(d) => write!(f, "{d}"),
+            Lit::Variable(name) => write!(f, "${name}"),
+            Lit::Name(name) => write!(f, "{name}"),
+            Lit::Binary(op, a, b) => write!(f, "({a} {op} {b})"),
+            Lit::Neg(a) => write!(f, "-{a}"),
             Lit::Tuple(t) => write!(
                 f,
                 "( {} )",
@@ -68,7 +121,14 @@ impl SpannedLit {
         match &self.lit {
             Lit::String(_) => TYPE_STRING.to_string(),
             Lit::Double(_) => TYPE_DOUBLE.to_string(),
+            Lit::Angle(_) => TYPE_ANGLE.to_string(),
             Lit::Int(_) => TYPE_INT.to_string(),
+            Lit::Variable(name) => format!("${name}"),
+            Lit::Name(name) => name.clone(),
+            Lit::Binary(op, a, b) => {
+                format!("{} {op} {}", a.to_type_string(), b.to_type_string())
+            }
+            Lit::Neg(a) => format!("-{}", a.to_type_string()),
             Lit::Tuple(v) => format!(
                 "( {} )",
                 v.iter()
@@ -86,6 +146,95 @@ impl SpannedLit {
         }
     }
 
+    /// Returns the literal with every `$name` replaced by `value`.
+    pub fn bind(&self, name: &str, value: i64) -> Self {
+        let lit = match &self.lit {
+            Lit::Variable(v) if v == name => Lit::Int(value),
+            Lit::Tuple(t) => Lit::Tuple(t.iter().map(|l| l.bind(name, value)).collect()),
+            Lit::Binary(op, a, b) => Lit::Binary(
+                *op,
+                Box::new(a.bind(name, value)),
+                Box::new(b.bind(name, value)),
+            ),
+            Lit::Neg(a) => Lit::Neg(Box::new(a.bind(name, value))),
+            Lit::Object(os) => Lit::Object(
+                os.iter()
+                    .map(|(k, v)| (k.clone(), v.bind(name, value)))
+                    .collect(),
+            ),
+            lit => lit.clone(),
+        };
+        Self { lit, ..*self }
+    }
+
+    /// Returns the literal with its arithmetic evaluated.
+    ///
+    /// Integers stay integers unless divided unevenly. Angles can be added to
+    /// angles and scaled by numbers, which keeps them angles.
+    fn evaluate(&self) -> Result<Lit, SceneParseError> {
+        let error = |error: &str| SceneParseError::Custom {
+            start: self.start,
+            error: error.to_string(),
+            end: Some(self.end),
+        };
+        let overflow = || error("Integer overflow");
+
+        match &self.lit {
+            Lit::Neg(a) => match a.evaluate()? {
+                Lit::Int(i) => i.checked_neg().map(Lit::Int).ok_or_else(overflow),
+                Lit::Double(d) => Ok(Lit::Double(-d)),
+                Lit::Angle(a) => Ok(Lit::Angle(-a)),
+                _ => Err(a.wrong_type(TYPE_NUMBER)),
+            },
+            Lit::Binary(op, a, b) => {
+                let (x, y) = (a.evaluate()?, b.evaluate()?);
+                for (lit, operand) in [(&x, a), (&y, b)] {
+                    if !matches!(lit, Lit::Int(_) | Lit::Double(_) | Lit::Angle(_)) {
+                        return Err(operand.wrong_type(TYPE_NUMBER));
+                    }
+                }
+                let is_zero = matches!(y, Lit::Int(0)) || matches!(y, Lit::Double(d) if d == 0.0);
+                if *op == Op::Div && is_zero {
+                    return Err(error("Division by zero"));
+                }
+
+                match (x, y) {
+                    (Lit::Int(x), Lit::Int(y)) => match op {
+                        Op::Add => x.checked_add(y).map(Lit::Int).ok_or_else(overflow),
+                        Op::Sub => x.checked_sub(y).map(Lit::Int).ok_or_else(overflow),
+                        Op::Mul => x.checked_mul(y).map(Lit::Int).ok_or_else(overflow),
+                        Op::Div => match x.checked_rem(y).ok_or_else(overflow)? {
+                            0 => x.checked_div(y).map(Lit::Int).ok_or_else(overflow),
+                            _ => Ok(Lit::Double(x as f64 / y as f64)),
+                        },
+                    },
+                    (Lit::Angle(x), Lit::Angle(y)) if matches!(op, Op::Add | Op::Sub) => {
+                        Ok(Lit::Angle(apply(*op, x, y)))
+                    }
+                    (Lit::Angle(x), y) if matches!(op, Op::Mul | Op::Div) && !is_angle(&y) => {
+                        Ok(Lit::Angle(apply(*op, x, number(&y))))
+                    }
+                    (x, Lit::Angle(y)) if *op == Op::Mul && !is_angle(&x) => {
+                        Ok(Lit::Angle(apply(*op, number(&x), y)))
+                    }
+                    (Lit::Angle(_), _) | (_, Lit::Angle(_)) => Err(error(
+                        "Angles can only be added to angles and scaled by numbers",
+                    )),
+                    (x, y) => Ok(Lit::Double(apply(*op, number(&x), number(&y)))),
+                }
+            }
+            lit => Ok(lit.clone()),
+        }
+    }
+
+    fn wrong_type(&self, expected: &'static str) -> SceneParseError {
+        SceneParseError::WrongType {
+            start: self.start,
+            t: self.to_type_string(),
+            expected,
+            end: self.end,
+        }
+    }
 
     pub fn get_string(&self) -> Result<String, SceneParseError> {
         match &self.lit {
@@ -101,9 +250,14 @@ impl SpannedLit {
 
     // TODO: Use a macro for all get_{number}
     pub fn get_double(&self) -> Result<f64, SceneParseError> {
-        match self.lit {
+        match self.evaluate()? {
             Lit::Double(d) => Ok(d),
-            Lit::Int(d) => Ok(d.into()),
+            Lit::Int(d) => Ok(d as f64),
+            Lit::Angle(_) => Err(SceneParseError::Custom {
+                start: self.start,
+                error: "Only angles can have a 'deg' or 'rad' suffix".to_string(),
+                end: Some(self.end),
+            }),
             _ => Err(SceneParseError::WrongType {
                 start: self.start,
                 t: self.to_type_string(),
@@ -113,8 +267,24 @@ impl SpannedLit {
         }
     }
 
+    /// Returns the angle in radians.
+    /// Numbers without a `deg` or `rad` suffix are in degrees.
+    pub fn get_angle(&self) -> Result<f64, SceneParseError> {
+        match self.evaluate()? {
+            Lit::Angle(a) => Ok(a),
+            Lit::Double(d) => Ok(d.to_radians()),
+            Lit::Int(d) => Ok((d as f64).to_radians()),
+            _ => Err(SceneParseError::WrongType {
+                start: self.start,
+                t: self.to_type_string(),
+                expected: TYPE_ANGLE,
+                end: self.end,
+            }),
+        }
+    }
+
     pub fn get_u32(&self) -> Result<u32, SceneParseError> {
-        match self.lit {
+        match self.evaluate()? {
             Lit::Int(i) => {
                 match u32::try_from(i) {
                     Ok(o) => Ok(o),
@@ -135,9 +305,25 @@ impl SpannedLit {
         }
     }
 
+    pub fn get_i32(&self) -> Result<i32, SceneParseError> {
+        match self.evaluate()? {
+            Lit::Int(i) => i32::try_from(i).map_err(|err| SceneParseError::Custom {
+                start: self.start,
+                error: format!("{}", err),
+                end: Some(self.end),
+            }),
+            _ => Err(SceneParseError::WrongType {
+                start: self.start,
+                t: self.to_type_string(),
+                expected: TYPE_INT,
+                end: self.end,
+            }),
+        }
+    }
+
     pub fn get_u8(&self) -> Result<u8, SceneParseError> {
-        match &self.lit {
-            &Lit::Int(i) => match u8::try_from(i) {
+        match self.evaluate()? {
+            Lit::Int(i) => match u8::try_from(i) {
                 Ok(n) => Ok(n),
                 Err(err) => Err(SceneParseError::Custom {
                     start: self.start,
@@ -172,11 +358,12 @@ impl SpannedLit {
     pub fn get_color(&self) -> Result<Color, SceneParseError> {
         match &self.lit {
             // Either "red"
-            Lit::String(name) => {
+            Lit::String(_) => {
+                let name = self.get_string()?;
                 let color =
-                    ColorNames::from_str(name).map_err(|_| SceneParseError::UnknownColor {
+                    ColorNames::from_str(&name).map_err(|_| SceneParseError::UnknownColor {
                         start: self.start,
-                        name: name.clone(),
+                        name,
                         end: self.end,
                     })?;
                 return Ok(color.into());
@@ -197,6 +384,124 @@ impl SpannedLit {
             end: self.end,
         })
     }
+
+    /// Returns the two colors of a tuple like `("white", (0,0,0))`.
+    pub fn get_color_pair(&self) -> Result<(Color, Color), SceneParseError> {
+        if let Lit::Tuple(colors) = &self.lit {
+            if let [a, b] = colors.as_slice() {
+                return Ok((a.get_color()?, b.get_color()?));
+            }
+        }
+
+        Err(SceneParseError::WrongType {
+            start: self.start,
+            t: self.to_type_string(),
+            expected: TYPE_COLOR_PAIR,
+            end: self.end,
+        })
+    }
+}
+
+/// Parses the digits of an integer literal spanning `start` to `end`.
+pub fn parse_int(start: usize, s: &str, end: usize) -> Result<i64, SceneParseError> {
+    i64::from_str(s).map_err(|_| SceneParseError::Custom {
+        start,
+        error: format!("Integer '{s}' is too large"),
+        end: Some(end),
+    })
+}
+
+/// Parses the digits of a decimal literal spanning `start` to `end`.
+pub fn parse_float(start: usize, s: &str, end: usize) -> Result<f64, SceneParseError> {
+    f64::from_str(s)
+        .ok()
+        .filter(|d| d.is_finite())
+        .ok_or_else(|| SceneParseError::Custom {
+            start,
+            error: format!("Number '{s}' is too large"),
+            end: Some(end),
+        })
+}
+
+/// Values bound with `let`, filled in while the scene is parsed
+/// so names can only refer to earlier bindings.
+#[derive(Debug, Default)]
+pub struct Bindings(RefCell<HashMap<String, SpannedLit>>);
+
+impl Bindings {
+    /// Bind `name` to `value`, replacing any earlier value of the name.
+    pub fn bind(&self, name: Ident, value: SpannedLit) -> Result<(), SceneParseError> {
+        let value = self.resolve(&value)?;
+        self.0.borrow_mut().insert(name.name, value);
+        Ok(())
+    }
+
+    /// Returns the literal with every name replaced by its value.
+    /// The values take the span of the name, so errors point to where they are used.
+    pub fn resolve(&self, lit: &SpannedLit) -> Result<SpannedLit, SceneParseError> {
+        let resolved = match &lit.lit {
+            Lit::Name(name) => match self.0.borrow().get(name) {
+                Some(value) => value.lit.clone(),
+                None => {
+                    return Err(SceneParseError::UnknownIdentifier {
+                        start: lit.start,
+                        name: name.clone(),
+                        end: lit.end,
+                    })
+                }
+            },
+            Lit::Tuple(t) => Lit::Tuple(
+                t.iter()
+                    .map(|l| self.resolve(l))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Lit::Object(os) => Lit::Object(self.resolve_options(os.clone())?),
+            Lit::Binary(op, a, b) => {
+                Lit::Binary(*op, Box::new(self.resolve(a)?), Box::new(self.resolve(b)?))
+            }
+            Lit::Neg(a) => Lit::Neg(Box::new(self.resolve(a)?)),
+            lit => lit.clone(),
+        };
+        Ok(SpannedLit {
+            lit: resolved,
+            ..*lit
+        })
+    }
+
+    /// Resolves the names in the values of `options`.
+    pub fn resolve_options(
+        &self,
+        options: Vec<(Ident, SpannedLit)>,
+    ) -> Result<Vec<(Ident, SpannedLit)>, SceneParseError> {
+        options
+            .into_iter()
+            .map(|(k, v)| Ok((k, self.resolve(&v)?)))
+            .collect()
+    }
+}
+
+/// Applies `op` to two numbers.
+fn apply(op: Op, x: f64, y: f64) -> f64 {
+    match op {
+        Op::Add => x + y,
+        Op::Sub => x - y,
+        Op::Mul => x * y,
+        Op::Div => x / y,
+    }
+}
+
+/// Returns the value of an evaluated number, angles in radians.
+fn number(lit: &Lit) -> f64 {
+    match lit {
+        &Lit::Int(i) => i as f64,
+        &Lit::Double(d) | &Lit::Angle(d) => d,
+        // Operands are checked to be numbers before they are combined
+        _ => f64::NAN,
+    }
+}
+
+fn is_angle(lit: &Lit) -> bool {
+    matches!(lit, Lit::Angle(_))
 }
 
 impl TryFrom<SpannedLit> for Options {
@@ -1,32 +1,68 @@
-use crate::scene_object::{GlobalOptions, SceneObject};
-use crate::SceneParseError;
+use std::collections::HashMap;
 
-use raytrace_lib::{Light, Object, Raytracer};
+use crate::scene_object::{GlobalOptions, MaterialRef, SceneObject};
+use crate::{SceneParseError, SceneWarning};
+
+use raytrace_lib::{Light, Object, Projection, Raytracer};
+
+/// The world, lights and raytracer of a scene together with any warnings.
+pub type BuiltScene = (Vec<Object>, Vec<Light>, Raytracer, Vec<SceneWarning>);
 
 pub struct SceneBuilder;
 
 impl SceneBuilder {
     pub fn build(
         scene_objects: Vec<Result<SceneObject, SceneParseError>>,
-    ) -> Result<(Vec<Object>, Vec<Light>, Raytracer), Vec<SceneParseError>> {
+    ) -> Result<BuiltScene, Vec<SceneParseError>> {
         let mut cameras = vec![];
-        let mut objects = vec![];
-        let mut lights = vec![];
+        let mut unresolved_objects = vec![];
+        let mut lights: Vec<Light> = vec![];
         let mut errors = vec![];
-        let mut options = GlobalOptions::default();
+        let mut warnings = vec![];
+        let mut options: Option<GlobalOptions> = None;
+        let mut materials = HashMap::new();
 
         for object in scene_objects {
             match object {
                 Ok(object) => match object {
-                    SceneObject::GlobalOptions(o) => {
-                        options = o;
+                    SceneObject::GlobalOptions(o, ident) => {
+                        if options.is_some() {
+                            errors.push(SceneParseError::Custom {
+                                start: ident.start,
+                                error: "Only one global block is allowed in a scene".to_string(),
+                                end: Some(ident.end),
+                            });
+                        } else {
+                            options = Some(o);
+                        }
+                    }
+                    SceneObject::Camera(c, ident) => cameras.push((c, ident)),
+                    SceneObject::NamedMaterial(material, ident) => {
+                        if materials.insert(ident.name.clone(), material).is_some() {
+                            errors.push(SceneParseError::Custom {
+                                start: ident.start,
+                                error: format!("Material '{}' is already defined", ident.name),
+                                end: Some(ident.end),
+                            });
+                        }
+                    }
+                    SceneObject::Object {
+                        primitive,
+                        material,
+                        name,
+                        priority,
+                        ident,
+                    } => unresolved_objects.push((primitive, material, name, priority, ident)),
+                    SceneObject::Light(l, ident) => {
+                        if lights.contains(&l) {
+                            warnings.push(SceneWarning::new(
+                                ident.start,
+                                "Duplicate light, it will contribute twice".to_string(),
+                                Some(ident.end),
+                            ));
+                        }
+                        lights.push(l);
                     }
-                    SceneObject::Camera(c) => cameras.push(c),
-                    SceneObject::Object(p, m) => objects.push(Object {
-                        primitive: p,
-                        material: m,
-                    }),
-                    SceneObject::Light(l) => lights.push(l),
                 },
                 Err(obj_err) => {
                     errors.push(obj_err);
@@ -34,15 +70,47 @@ impl SceneBuilder {
             }
         }
 
+        // Materials may be defined after the objects that use them
+        let default_material = options.as_ref().and_then(|o| o.default_material);
+        let mut objects = vec![];
+        for (primitive, material, name, priority, ident) in unresolved_objects {
+            let material = match material {
+                Some(MaterialRef::Inline(material)) => Some(material),
+                Some(MaterialRef::Named(name, start, end)) => match materials.get(&name) {
+                    Some(&material) => Some(material),
+                    None => {
+                        errors.push(SceneParseError::UnknownMaterial { start, name, end });
+                        continue;
+                    }
+                },
+                None => default_material,
+            };
+            match material {
+                Some(material) => {
+                    let mut object = Object::new(*primitive, material);
+                    object.name = name;
+                    object.priority = priority;
+                    objects.push(object);
+                }
+                None => errors.push(SceneParseError::MissingOption {
+                    start: ident.start,
+                    name: "material".to_string(),
+                }),
+            }
+        }
+
         if cameras.len() != 1 {
+            // Point at the first superfluous camera if there is one
+            let (start, end) = cameras
+                .get(1)
+                .map_or((0, None), |(_, ident)| (ident.start, Some(ident.end)));
             errors.push(SceneParseError::Custom {
-                // TODO: Get location of (any) cameras
-                start: 0,
+                start,
                 error: format!(
                     "There must be exactly one camera in a scene, found {}",
                     cameras.len()
                 ),
-                end: None, // TODO: location
+                end,
             });
         }
 
@@ -50,13 +118,50 @@ impl SceneBuilder {
             return Err(errors);
         }
 
+        if lights.is_empty() && !objects.iter().any(|o| o.material.is_emissive()) {
+            warnings.push(SceneWarning::new(
+                0,
+                "Scene has no lights or emissive objects, only ambient light is visible"
+                    .to_string(),
+                None,
+            ));
+        }
+
+        let options = options.unwrap_or_default();
+
+        if options.scale != 1.0 {
+            let scale = options.scale;
+            for object in objects.iter_mut() {
+                object.primitive = object.primitive.scaled(scale);
+            }
+            for light in lights.iter_mut() {
+                light.pos *= scale;
+                light.shadow_radius *= scale;
+            }
+            for (camera, _) in cameras.iter_mut() {
+                camera.set_position(camera.position() * scale);
+                camera.set_aperture(camera.aperture() * scale);
+                camera.set_focus_distance(camera.focus_distance() * scale);
+                if let Projection::Orthographic { scale: width } = camera.projection() {
+                    camera.set_projection(Projection::Orthographic {
+                        scale: width * scale,
+                    });
+                }
+            }
+        }
+
         // Checked length above
-        if let Some(camera) = cameras.pop() {
-            Ok((
-                objects,
-                lights,
-                Raytracer::new(camera, options.recurse_depth),
-            ))
+        if let Some((camera, _)) = cameras.pop() {
+            let mut raytracer = Raytracer::new(camera, options.recurse_depth);
+            if let Some(epsilon) = options.epsilon {
+                raytracer.set_epsilon(epsilon);
+            }
+            if let Some(background) = options.background {
+                raytracer.set_background(background);
+            }
+            raytracer.set_samples_per_pixel(options.samples);
+
+            Ok((objects, lights, raytracer, warnings))
         } else {
             unreachable!()
         }
@@ -4,14 +4,38 @@ use crate::lit::SpannedLit;
 use crate::options::Options;
 use crate::{Ident, SceneParseError, DEFAULT_FOV};
 use raytrace_lib::material::MaterialTemplate;
-use raytrace_lib::primitive::{Plane, Primitive, Sphere, Triangle};
-use raytrace_lib::{Camera, Light, Material};
+use raytrace_lib::primitive::{Cuboid, Disk, Plane, Primitive, Sphere, Triangle};
+use raytrace_lib::texture::Texture;
+use raytrace_lib::{Camera, Color, FovAxis, Light, Material, Projection, Vec3};
+
+/// Largest number of copies a `repeat` block may create.
+const MAX_REPEAT: i64 = 10_000;
+
+/// The type and options of an object, before it is built.
+pub type RawObject = (Ident, Vec<(Ident, SpannedLit)>);
 
 pub enum SceneObject {
-    Camera(Camera),
-    Object(Primitive, Material),
-    Light(Light),
-    GlobalOptions(GlobalOptions),
+    Camera(Camera, Ident),
+    /// An object whose material may be left to the scene default.
+    Object {
+        primitive: Box<Primitive>,
+        material: Option<MaterialRef>,
+        name: Option<String>,
+        priority: i32,
+        ident: Ident,
+    },
+    Light(Light, Ident),
+    GlobalOptions(GlobalOptions, Ident),
+    /// A material defined with `Material name { ... }`, named by the ident.
+    NamedMaterial(Material, Ident),
+}
+
+/// The material of an object, either written out or the name of a material
+/// defined elsewhere in the scene.
+pub enum MaterialRef {
+    Inline(Material),
+    /// The name and the span of the string that refers to it.
+    Named(String, usize, usize),
 }
 
 impl SceneObject {
@@ -20,19 +44,136 @@ impl SceneObject {
         let width = options.get("width", s)?.1.get_u32()?;
         let height = options.get("height", s)?.1.get_u32()?;
         let position = options.get("pos", s)?.1.get_vec3()?;
-        let view_dir = options.get("dir", s)?.1.get_vec3()?;
+        // Either a direction or a point to look at
+        let view_dir = match options.get("look_at", s) {
+            Ok((look_at, _)) if options.get("dir", s).is_ok() => {
+                return Err(SceneParseError::Custom {
+                    start: look_at.start,
+                    error: "A camera can't have both dir and look_at".to_string(),
+                    end: Some(look_at.end),
+                })
+            }
+            Ok((_, target)) => target.get_vec3()? - position,
+            Err(_) => options.get("dir", s)?.1.get_vec3()?,
+        };
         let fov = if let Ok(fov) = options.get("fov", s) {
-            fov.1.get_double()?
+            fov.1.get_angle()?
+        } else {
+            DEFAULT_FOV.to_radians()
+        };
+        let roll = if let Ok(roll) = options.get("roll", s) {
+            roll.1.get_angle()?
+        } else {
+            0.0
+        };
+
+        let up = if let Ok((_, lit)) = options.get("up", s) {
+            let up = lit.get_vec3()?;
+            if up.length_squared() == 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Up direction must be non-zero".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+            Some(up)
+        } else {
+            None
+        };
+
+        let fov_axis = if let Ok((_, lit)) = options.get("fov_axis", s) {
+            FovAxis::from_str(&lit.get_string()?).map_err(|error| SceneParseError::Custom {
+                start: lit.start,
+                error,
+                end: Some(lit.end),
+            })?
+        } else {
+            FovAxis::default()
+        };
+
+        let projection = if let Ok((_, lit)) = options.get("projection", s) {
+            match lit.get_string()?.as_str() {
+                "pinhole" => Projection::Pinhole,
+                "fisheye" => Projection::Fisheye { fov },
+                "orthographic" => {
+                    let scale = match options.get("ortho_scale", s) {
+                        Ok((_, lit)) if lit.get_double()? <= 0.0 => {
+                            return Err(SceneParseError::Custom {
+                                start: lit.start,
+                                error: "Orthographic scale must be positive".to_string(),
+                                end: Some(lit.end),
+                            })
+                        }
+                        Ok((_, lit)) => lit.get_double()?,
+                        Err(_) => 1.0,
+                    };
+                    Projection::Orthographic { scale }
+                }
+                name => {
+                    return Err(SceneParseError::Custom {
+                        start: lit.start,
+                        error: format!(
+                            "No projection named '{name}', expected 'pinhole', 'fisheye' or 'orthographic'"
+                        ),
+                        end: Some(lit.end),
+                    })
+                }
+            }
         } else {
-            DEFAULT_FOV
+            Projection::default()
+        };
+
+        let aperture = if let Ok((_, lit)) = options.get("aperture", s) {
+            let aperture = lit.get_double()?;
+            if aperture < 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Aperture must not be negative".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+            aperture
+        } else {
+            0.0
+        };
+        let focus_distance = if let Ok((_, lit)) = options.get("focus_distance", s) {
+            let distance = lit.get_double()?;
+            if distance <= 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Focus distance must be positive".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+            Some(distance)
+        } else {
+            None
         };
 
         options.check_empty()?;
-        Camera::new(width, height, position, view_dir, fov).map_err(|e| SceneParseError::Custom {
+        let mut camera = Camera::new(
+            width,
+            height,
+            position,
+            view_dir,
+            fov.to_degrees(),
+            roll.to_degrees(),
+        )
+        .map_err(|e| SceneParseError::Custom {
             start: ident.start,
             error: format!("{}", e),
             end: Some(ident.end),
-        })
+        })?;
+        if let Some(up) = up {
+            camera.set_up(up);
+        }
+        camera.set_fov_axis(fov_axis);
+        camera.set_projection(projection);
+        camera.set_aperture(aperture);
+        if let Some(distance) = focus_distance {
+            camera.set_focus_distance(distance);
+        }
+        Ok(camera)
     }
 
     fn build_primitive(ident: &Ident, options: &mut Options) -> Result<Primitive, SceneParseError> {
@@ -51,6 +192,26 @@ impl SceneObject {
                 options.check_empty()?;
                 Ok(Primitive::Triangle(Triangle::new(t1, t2, t3)))
             }
+            "box" => {
+                // Either two opposite corners or a center and a size
+                let cuboid = if let Ok((_, min)) = options.get("min", start) {
+                    let max = options.get("max", start)?.1.get_vec3()?;
+                    Cuboid::new(min.get_vec3()?, max)
+                } else {
+                    let center = options.get("pos", start)?.1.get_vec3()?;
+                    let half_size = options.get("size", start)?.1.get_vec3()? * 0.5;
+                    Cuboid::new(center - half_size, center + half_size)
+                };
+                options.check_empty()?;
+                Ok(Primitive::Cuboid(cuboid))
+            }
+            "disk" => {
+                let center = options.get("pos", start)?.1.get_vec3()?;
+                let normal = options.get("normal", start)?.1.get_vec3()?;
+                let radius = options.get("r", start)?.1.get_double()?;
+                options.check_empty()?;
+                Ok(Primitive::Disk(Disk::new(center, normal, radius)))
+            }
             "plane" => {
                 let point = options.get("point", start)?.1.get_vec3()?;
                 let normal = options.get("normal", start)?.1.get_vec3()?;
@@ -68,54 +229,235 @@ impl SceneObject {
     fn build_material(ident: &Ident, options: &mut Options) -> Result<Material, SceneParseError> {
         let start = ident.start;
 
-        let color = options.get("color", start)?.1.get_color()?;
+        let (color_ident, color_lit) = options.get("color", start)?;
+        let (color, checker) = Self::build_color(&color_ident, color_lit)?;
         let lambert = options.get("lambert", start).map(|(_, l)| l.get_color());
         let specular = options.get("specular", start).map(|(_, l)| l.get_color());
         let ambient = options.get("ambient", start).map(|(_, l)| l.get_color());
+        let emission = options.get("emission", start).map(|(_, l)| l.get_color());
 
-        let mat = if let Ok((_, lit)) = options.get("template", start) {
+        let mut mat = if let Ok((_, lit)) = options.get("template", start) {
             let name = lit.get_string()?;
-            let mut mat = MaterialTemplate::from_str(&name)
+            MaterialTemplate::from_str(&name)
                 .map_err(|_| SceneParseError::UnknownMaterial {
                     start: lit.start,
                     name,
                     end: lit.end,
                 })?
-                .get_material(color);
-
-            if let Ok(Ok(l)) = lambert {
-                mat.lambert = l;
-            }
-
-            if let Ok(Ok(s)) = specular {
-                mat.specular = s;
-            }
-
-            if let Ok(Ok(a)) = ambient {
-                mat.ambient = a;
-            }
-
-            mat
+                .get_material_with_overrides(
+                    color,
+                    lambert.ok().transpose()?,
+                    specular.ok().transpose()?,
+                    ambient.ok().transpose()?,
+                    emission.ok().transpose()?,
+                )
         } else {
             Material {
                 color,
                 lambert: lambert??,
                 specular: specular??,
                 ambient: ambient??,
+                emission: emission.map_or(Ok(Color::zero()), |e| e)?,
+                ..Default::default()
             }
         };
 
+        if let Ok((_, lit)) = options.get("clearcoat", start) {
+            mat.clearcoat = lit.get_double()?;
+            if !(0.0..=1.0).contains(&mat.clearcoat) {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Clearcoat must be between 0 and 1".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
+        if let Ok((_, lit)) = options.get("opacity", start) {
+            mat.opacity = lit.get_double()?;
+            if !(0.0..=1.0).contains(&mat.opacity) {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Opacity must be between 0 and 1".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
+        if let Ok((_, lit)) = options.get("refraction", start) {
+            mat.refraction = lit.get_double()?;
+            if !(0.0..=1.0).contains(&mat.refraction) {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Refraction must be between 0 and 1".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
+        if let Ok((_, lit)) = options.get("ior", start) {
+            mat.ior = lit.get_double()?;
+            if mat.ior <= 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Index of refraction must be positive".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
+        if let Ok((_, lit)) = options.get("shininess", start) {
+            mat.shininess = lit.get_double()?;
+            if mat.shininess < 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Shininess must not be negative".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
+        if let Ok((texture_ident, lit)) = options.get("texture", start) {
+            let texture: &mut Options = &mut lit.try_into()?;
+            if checker.is_some() {
+                return Err(SceneParseError::Custom {
+                    start: texture_ident.start,
+                    error: "A material can't have both a checker color and a texture".to_string(),
+                    end: Some(texture_ident.end),
+                });
+            }
+            mat.texture = Some(Self::build_texture(&texture_ident, texture)?);
+        }
+        if checker.is_some() {
+            mat.texture = checker;
+        }
+        if let Ok((_, lit)) = options.get("clearcoat_roughness", start) {
+            mat.clearcoat_roughness = lit.get_double()?;
+            if mat.clearcoat_roughness <= 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Clearcoat roughness must be positive".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
+
         options.check_empty()?;
         Ok(mat)
     }
 
+    /// Parse a plain color, or a checker pattern like
+    /// `{ checker: ("white", "black"), scale: 1.0 }` whose first color is also returned.
+    fn build_color(
+        ident: &Ident,
+        lit: SpannedLit,
+    ) -> Result<(Color, Option<Texture>), SceneParseError> {
+        if !lit.is_object() {
+            return Ok((lit.get_color()?, None));
+        }
+
+        let start = ident.start;
+        let options: &mut Options = &mut lit.try_into()?;
+        let (a, b) = options.get("checker", start)?.1.get_color_pair()?;
+        let scale = match options.get("scale", start) {
+            Ok((_, lit)) => {
+                let scale = lit.get_double()?;
+                if scale <= 0.0 {
+                    return Err(SceneParseError::Custom {
+                        start: lit.start,
+                        error: "Checker scale must be positive".to_string(),
+                        end: Some(lit.end),
+                    });
+                }
+                scale
+            }
+            Err(_) => 1.0,
+        };
+
+        options.check_empty()?;
+        Ok((a, Some(Texture::Checker { a, b, scale })))
+    }
+
+    fn build_texture(ident: &Ident, options: &mut Options) -> Result<Texture, SceneParseError> {
+        let start = ident.start;
+        let (_, kind) = options.get("type", start)?;
+
+        let texture = match kind.get_string()?.as_str() {
+            "lat_long_grid" => Texture::LatLongGrid {
+                line_color: options.get("line_color", start)?.1.get_color()?,
+                bg_color: options.get("bg_color", start)?.1.get_color()?,
+                divisions: options.get("divisions", start)?.1.get_u32()?,
+            },
+            name => {
+                return Err(SceneParseError::Custom {
+                    start: kind.start,
+                    error: format!("No texture named '{name}'"),
+                    end: Some(kind.end),
+                })
+            }
+        };
+
+        options.check_empty()?;
+        Ok(texture)
+    }
+
     fn build_light(ident: Ident, options: &mut Options) -> Result<Light, SceneParseError> {
         let start = ident.start;
         let pos = options.get("pos", start)?.1.get_vec3()?;
-        let intensity = options.get("intensity", start)?.1.get_double()?;
+        let mut light = match options.get("radiant_power", start) {
+            Ok((_, lit)) => {
+                if let Ok((ident, _)) = options.get("intensity", start) {
+                    return Err(SceneParseError::Custom {
+                        start: ident.start,
+                        error: "A light can't have both intensity and radiant_power".to_string(),
+                        end: Some(ident.end),
+                    });
+                }
+                let power = lit.get_double()?;
+                if power < 0.0 {
+                    return Err(SceneParseError::Custom {
+                        start: lit.start,
+                        error: "Radiant power must not be negative".to_string(),
+                        end: Some(lit.end),
+                    });
+                }
+                Light::with_radiant_power(pos, power)
+            }
+            Err(_) => {
+                let intensity = options.get("intensity", start)?.1.get_double()?;
+                Light::new(pos, intensity)
+            }
+        };
+
+        if let Ok((ident, lit)) = options.get("attenuation", start) {
+            if light.radiant_power.is_some() {
+                return Err(SceneParseError::Custom {
+                    start: ident.start,
+                    error: "Radiant power always falls off with the distance squared".to_string(),
+                    end: Some(ident.end),
+                });
+            }
+            let Vec3 { x, y, z } = lit.get_vec3()?;
+            if x < 0.0 || y < 0.0 || z < 0.0 || x + y + z == 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Attenuation must be non-negative and not all zero".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+            light.attenuation = (x, y, z);
+        }
+        if let Ok((_, lit)) = options.get("shadow_samples", start) {
+            light.shadow_samples = lit.get_u32()?;
+        }
+        if let Ok((_, lit)) = options.get("shadow_radius", start) {
+            light.shadow_radius = lit.get_double()?;
+            if light.shadow_radius < 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Shadow radius must not be negative".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
 
         options.check_empty()?;
-        Ok(Light { pos, intensity })
+        Ok(light)
     }
 
     fn build_global(ident: Ident, options: &mut Options) -> Result<GlobalOptions, SceneParseError> {
@@ -124,27 +466,139 @@ impl SceneObject {
         if let Ok((_, lit)) = options.get("recurse_depth", start) {
             go.recurse_depth = lit.get_u32()?;
         }
+        if let Ok((material_ident, lit)) = options.get("default_material", start) {
+            let material: &mut Options = &mut lit.try_into()?;
+            go.default_material = Some(Self::build_material(&material_ident, material)?);
+        }
+        if let Ok((_, lit)) = options.get("scale", start) {
+            go.scale = lit.get_double()?;
+            if go.scale <= 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Scale must be positive".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
+        if let Ok((_, lit)) = options.get("epsilon", start) {
+            let epsilon = lit.get_double()?;
+            if epsilon <= 0.0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Epsilon must be positive".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+            go.epsilon = Some(epsilon);
+        }
+        if let Ok((_, lit)) = options.get("background", start) {
+            go.background = Some(lit.get_color()?);
+        }
+        if let Ok((_, lit)) = options.get("samples", start) {
+            go.samples = lit.get_u32()?;
+            if go.samples == 0 {
+                return Err(SceneParseError::Custom {
+                    start: lit.start,
+                    error: "Samples must be positive".to_string(),
+                    end: Some(lit.end),
+                });
+            }
+        }
         options.check_empty()?;
 
         Ok(go)
     }
 
+    /// Instantiate `objects` `count` times, replacing `$i` with the index of the copy.
+    pub fn repeat(
+        (start, count, end): (usize, i64, usize),
+        objects: Vec<Result<RawObject, SceneParseError>>,
+    ) -> Vec<Result<Self, SceneParseError>> {
+        if !(0..=MAX_REPEAT).contains(&count) {
+            return vec![Err(SceneParseError::Custom {
+                start,
+                error: format!("Repeat count must be between 0 and {MAX_REPEAT}"),
+                end: Some(end),
+            })];
+        }
+        let objects = match objects.into_iter().collect::<Result<Vec<_>, _>>() {
+            Ok(objects) => objects,
+            Err(err) => return vec![Err(err)],
+        };
+
+        (0..count)
+            .flat_map(|i| {
+                objects.iter().map(move |(ident, options)| {
+                    let options = options
+                        .iter()
+                        .map(|(k, v)| (k.clone(), v.bind("i", i)))
+                        .collect();
+                    Self::new(ident.clone(), options)
+                })
+            })
+            .collect()
+    }
+
+    /// Build a material that objects can refer to by `name`.
+    pub fn named_material(
+        name: Ident,
+        options: Vec<(Ident, SpannedLit)>,
+    ) -> Result<Self, SceneParseError> {
+        let options = &mut Options::build(options)?;
+        let material = Self::build_material(&name, options)?;
+        Ok(Self::NamedMaterial(material, name))
+    }
+
     pub fn new(ident: Ident, options: Vec<(Ident, SpannedLit)>) -> Result<Self, SceneParseError> {
         let options = &mut Options::build(options)?;
 
         match ident.name.to_lowercase().as_str() {
-            "global" => Ok(Self::GlobalOptions(Self::build_global(ident, options)?)),
-            "camera" => Ok(Self::Camera(Self::build_camera(ident, options)?)),
-            "light" => Ok(Self::Light(Self::build_light(ident, options)?)),
+            "global" => Ok(Self::GlobalOptions(
+                Self::build_global(ident.clone(), options)?,
+                ident,
+            )),
+            "camera" => Ok(Self::Camera(
+                Self::build_camera(ident.clone(), options)?,
+                ident,
+            )),
+            "light" => Ok(Self::Light(
+                Self::build_light(ident.clone(), options)?,
+                ident,
+            )),
             _ => {
                 let material = options.get("material", ident.start);
+                let name = if let Ok((_, lit)) = options.get("name", ident.start) {
+                    Some(lit.get_string()?)
+                } else {
+                    None
+                };
+                let priority = if let Ok((_, lit)) = options.get("priority", ident.start) {
+                    lit.get_i32()?
+                } else {
+                    0
+                };
                 let prim = Self::build_primitive(&ident, options)?;
-                let material = material?;
-                let material_ident = material.0;
-                let material: &mut Options = &mut material.1.try_into()?;
-                let material = Self::build_material(&material_ident, material)?;
+                let material = match material {
+                    Ok((_, lit)) if !lit.is_object() => {
+                        Some(MaterialRef::Named(lit.get_string()?, lit.start, lit.end))
+                    }
+                    Ok((material_ident, lit)) => {
+                        let material: &mut Options = &mut lit.try_into()?;
+                        Some(MaterialRef::Inline(Self::build_material(
+                            &material_ident,
+                            material,
+                        )?))
+                    }
+                    Err(_) => None,
+                };
 
-                Ok(Self::Object(prim, material))
+                Ok(Self::Object {
+                    primitive: Box::new(prim),
+                    material,
+                    name,
+                    priority,
+                    ident,
+                })
             }
         }
     }
@@ -153,10 +607,27 @@ impl SceneObject {
 #[derive(Debug)]
 pub struct GlobalOptions {
     pub recurse_depth: u32,
+    /// Multiplier applied to all positions and sizes in the scene.
+    pub scale: f64,
+    /// Material for objects that don't specify one.
+    pub default_material: Option<Material>,
+    /// Overrides the distance rays leaving a surface are moved away from it.
+    pub epsilon: Option<f64>,
+    /// Color of the pixels that don't hit any object, black if not set.
+    pub background: Option<Color>,
+    /// Number of jittered primary rays averaged into each pixel.
+    pub samples: u32,
 }
 
 impl Default for GlobalOptions {
     fn default() -> Self {
-        Self { recurse_depth: 5 }
+        Self {
+            recurse_depth: 5,
+            scale: 1.0,
+            default_material: None,
+            epsilon: None,
+            background: None,
+            samples: 1,
+        }
     }
 }